@@ -0,0 +1,154 @@
+//! Builder for tool call arguments
+//!
+//! An alternative to writing `serde_json::json!` by hand. Given the tool's
+//! input schema, each field is checked as it is set, so a misspelled argument
+//! name or a wrongly typed value is reported by [`Arguments::build`] instead
+//! of by the server.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::McpError;
+use crate::types::ToolSchema;
+use crate::validation::{missing_required, validate_property};
+
+/// Arguments for a tool call, built field by field
+#[derive(Debug, Clone, Default)]
+pub struct Arguments {
+    values: Map<String, Value>,
+    schema: Option<Value>,
+    errors: Vec<String>,
+}
+
+impl Arguments {
+    /// Arguments without a schema; nothing is validated
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Arguments validated against `tool`'s input schema
+    pub fn for_tool(tool: &ToolSchema) -> Self {
+        Self::with_schema(tool.input_schema.clone())
+    }
+
+    /// Arguments validated against an input schema
+    pub fn with_schema(schema: Value) -> Self {
+        Self {
+            schema: Some(schema),
+            ..Self::default()
+        }
+    }
+
+    /// Set `key` to any serializable value
+    pub fn set(mut self, key: impl Into<String>, value: impl Serialize) -> Self {
+        let key = key.into();
+        match serde_json::to_value(value) {
+            Ok(value) => self.insert(key, value),
+            Err(e) => self.errors.push(format!("arguments.{}: {}", key, e)),
+        }
+        self
+    }
+
+    /// Set `key` to a string
+    pub fn set_string(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(key.into(), Value::String(value.into()));
+        self
+    }
+
+    /// Set `key` to a number; whole numbers are sent as integers
+    pub fn set_number(mut self, key: impl Into<String>, value: f64) -> Self {
+        let key = key.into();
+        let number = if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+            Some(serde_json::Number::from(value as i64))
+        } else {
+            serde_json::Number::from_f64(value)
+        };
+        match number {
+            Some(number) => self.insert(key, Value::Number(number)),
+            None => self.errors.push(format!("arguments.{}: {} is not a finite number", key, value)),
+        }
+        self
+    }
+
+    /// Set `key` to a resource URI
+    pub fn set_resource(mut self, key: impl Into<String>, uri: impl Into<String>) -> Self {
+        self.insert(key.into(), Value::String(uri.into()));
+        self
+    }
+
+    /// Problems found so far, without the final required-property check
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Finish, returning the arguments object for `call_tool`.
+    ///
+    /// Fails with `McpError::InvalidArguments` listing every problem found
+    /// while setting fields, plus any required property left unset.
+    pub fn build(self) -> Result<Value, McpError> {
+        let mut errors = self.errors;
+        if let Some(schema) = &self.schema {
+            errors.extend(missing_required(schema, &self.values));
+        }
+        if !errors.is_empty() {
+            return Err(McpError::InvalidArguments { errors });
+        }
+        Ok(Value::Object(self.values))
+    }
+
+    fn insert(&mut self, key: String, value: Value) {
+        if let Some(schema) = &self.schema {
+            self.errors.extend(validate_property(schema, &key, &value));
+        }
+        self.values.insert(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "limit": {"type": "integer"},
+                "tags": {"type": "array"}
+            },
+            "required": ["path"]
+        })
+    }
+
+    #[test]
+    fn test_build_valid_arguments() {
+        let arguments = Arguments::with_schema(schema())
+            .set_resource("path", "file:///tmp/a")
+            .set_number("limit", 3.0)
+            .set("tags", ["a", "b"])
+            .build()
+            .unwrap();
+        assert_eq!(arguments, json!({"path": "file:///tmp/a", "limit": 3, "tags": ["a", "b"]}));
+    }
+
+    #[test]
+    fn test_build_reports_typos_and_missing_fields() {
+        let arguments = Arguments::with_schema(schema())
+            .set_string("pth", "/tmp")
+            .set_number("limit", 2.5);
+        assert_eq!(arguments.errors().len(), 2);
+
+        let Err(McpError::InvalidArguments { errors }) = arguments.build() else {
+            panic!("expected invalid arguments");
+        };
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("'pth'"));
+    }
+
+    #[test]
+    fn test_unvalidated_arguments() {
+        let arguments = Arguments::new().set("anything", json!({"nested": true})).build().unwrap();
+        assert_eq!(arguments["anything"]["nested"], true);
+    }
+}
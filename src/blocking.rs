@@ -0,0 +1,127 @@
+//! Blocking facade for synchronous hosts
+//!
+//! [`BlockingManager`] owns a multi-threaded tokio runtime and drives an
+//! [`McpManager`] on it, so applications without an async runtime can use
+//! the crate directly. Background work (response routing, log forwarding,
+//! `list_changed` refreshes) keeps running on the runtime between calls.
+//!
+//! Don't use this from async code: blocking calls panic inside a runtime,
+//! as does dropping the facade there. Enabled by the `blocking` feature.
+
+use tokio::runtime::Runtime;
+
+use warhorn::McpServerConfig;
+use crate::error::McpError;
+use crate::manager::McpManager;
+use crate::types::{ServerHealth, ToolContent, ToolSchema};
+
+/// Synchronous wrapper around [`McpManager`] with its own runtime
+pub struct BlockingManager {
+    runtime: Runtime,
+    inner: McpManager,
+}
+
+impl BlockingManager {
+    /// Create a facade over a default manager
+    pub fn new() -> Result<Self, McpError> {
+        Self::from_manager(McpManager::new())
+    }
+
+    /// Create a facade over a configured manager
+    pub fn from_manager(manager: McpManager) -> Result<Self, McpError> {
+        Ok(Self {
+            runtime: Runtime::new()?,
+            inner: manager,
+        })
+    }
+
+    /// The wrapped manager, for its non-async methods
+    pub fn manager(&self) -> &McpManager {
+        &self.inner
+    }
+
+    /// Connect to an MCP server
+    pub fn connect(&self, config: McpServerConfig) -> Result<(), McpError> {
+        self.runtime.block_on(self.inner.connect(config))
+    }
+
+    /// Disconnect from an MCP server
+    pub fn disconnect(&self, server_id: &str) -> Result<(), McpError> {
+        self.runtime.block_on(self.inner.disconnect(server_id))
+    }
+
+    /// List all connected server IDs
+    pub fn server_ids(&self) -> Vec<String> {
+        self.inner.server_ids()
+    }
+
+    /// List all available tools across all servers
+    pub fn list_tools(&self) -> Vec<ToolSchema> {
+        self.inner.list_tools()
+    }
+
+    /// Refresh tools from a server
+    pub fn refresh_tools(&self, server_id: &str) -> Result<Vec<ToolSchema>, McpError> {
+        self.runtime.block_on(self.inner.refresh_tools(server_id))
+    }
+
+    /// Call a tool on a specific server
+    pub fn call_tool(
+        &self,
+        server_id: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<Vec<ToolContent>, McpError> {
+        self.runtime.block_on(self.inner.call_tool(server_id, tool_name, arguments))
+    }
+
+    /// Call a tool and deserialize its structured result into `T`
+    pub fn call_tool_as<T: serde::de::DeserializeOwned>(
+        &self,
+        server_id: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<T, McpError> {
+        self.runtime.block_on(self.inner.call_tool_as(server_id, tool_name, arguments))
+    }
+
+    /// Notify all servers of sandbox state change
+    pub fn notify_sandbox_state(&self, enabled: bool, policy: &str) {
+        self.runtime.block_on(self.inner.notify_sandbox_state(enabled, policy))
+    }
+
+    /// Check health of all connections
+    pub fn health_check(&self) {
+        self.runtime.block_on(self.inner.health_check())
+    }
+
+    /// Get health status of a server
+    pub fn server_health(&self, server_id: &str) -> Option<ServerHealth> {
+        self.inner.server_health(server_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fake_server, initialize_result, reply, test_config, FakeFactory};
+
+    #[test]
+    fn test_blocking_call_tool() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            Some("tools/call") => vec![reply(request, serde_json::json!({
+                "content": [{"type": "text", "text": "sync"}]
+            }))],
+            _ => vec![],
+        }));
+        let manager = BlockingManager::from_manager(
+            McpManager::new().with_transport_factory(factory)
+        ).unwrap();
+
+        manager.connect(test_config("sync")).unwrap();
+        let result = manager.call_tool("sync", "echo", serde_json::json!({})).unwrap();
+        assert_eq!(result, [ToolContent::Text { text: "sync".into() }]);
+    }
+}
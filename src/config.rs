@@ -0,0 +1,182 @@
+//! Loading server configs from files
+//!
+//! Supports the `mcpServers` layout used by desktop MCP hosts, either as an
+//! object keyed by server id or as an array of entries carrying their own
+//! `id`. A bare top-level array is accepted too. Files ending in `.toml` are
+//! parsed as TOML, everything else as JSON.
+//!
+//! Entries with `"disabled": true` (or `"enabled": false`) are kept aside
+//! rather than dropped, so hosts can toggle them on at runtime.
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::Deserialize;
+
+use warhorn::McpServerConfig;
+use crate::error::McpError;
+
+/// One server entry as written in a config file
+#[derive(Debug, Deserialize)]
+struct ServerEntry {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    enabled: Option<bool>,
+}
+
+impl ServerEntry {
+    fn is_disabled(&self) -> bool {
+        self.disabled || self.enabled == Some(false)
+    }
+}
+
+/// Server configs from a file, split by whether they are enabled
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfigs {
+    /// Servers to connect to
+    pub enabled: Vec<McpServerConfig>,
+    /// Servers configured but switched off
+    pub disabled: Vec<McpServerConfig>,
+}
+
+/// Read and parse a config file into the configs of its enabled servers
+pub fn load_configs(path: impl AsRef<Path>) -> Result<Vec<McpServerConfig>, McpError> {
+    Ok(load_server_configs(path)?.enabled)
+}
+
+/// Read and parse a config file, keeping disabled servers separately
+pub fn load_server_configs(path: impl AsRef<Path>) -> Result<ServerConfigs, McpError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)?;
+
+    let document: serde_json::Value = if path.extension().is_some_and(|e| e == "toml") {
+        toml::from_str(&text)
+            .map_err(|e| McpError::ConfigError(format!("{}: {}", path.display(), e)))?
+    } else {
+        serde_json::from_str(&text)
+            .map_err(|e| McpError::ConfigError(format!("{}: {}", path.display(), e)))?
+    };
+
+    parse_configs(document)
+}
+
+/// Parse an already-loaded config document into server configs
+pub fn parse_configs(document: serde_json::Value) -> Result<ServerConfigs, McpError> {
+    let servers = match document {
+        serde_json::Value::Object(mut root) => root.remove("mcpServers")
+            .ok_or_else(|| McpError::ConfigError("missing `mcpServers`".into()))?,
+        other => other,
+    };
+
+    let entries: Vec<(Option<String>, serde_json::Value)> = match servers {
+        serde_json::Value::Object(servers) => servers.into_iter()
+            .map(|(id, entry)| (Some(id), entry))
+            .collect(),
+        serde_json::Value::Array(servers) => servers.into_iter()
+            .map(|entry| (None, entry))
+            .collect(),
+        _ => return Err(McpError::ConfigError("`mcpServers` must be an object or array".into())),
+    };
+
+    let mut configs = ServerConfigs::default();
+    for (key, entry) in entries {
+        let entry: ServerEntry = serde_json::from_value(entry)
+            .map_err(|e| McpError::ConfigError(format!("invalid server entry: {}", e)))?;
+        if entry.is_disabled() {
+            configs.disabled.push(to_config(key, entry)?);
+        } else {
+            configs.enabled.push(to_config(key, entry)?);
+        }
+    }
+    Ok(configs)
+}
+
+fn to_config(key: Option<String>, entry: ServerEntry) -> Result<McpServerConfig, McpError> {
+    let id = key.or(entry.id)
+        .ok_or_else(|| McpError::ConfigError("server entry has no `id`".into()))?;
+
+    let transport = match (entry.command, entry.url) {
+        (Some(command), _) => warhorn::McpTransport::Stdio {
+            command,
+            args: entry.args,
+        },
+        (None, Some(url)) => warhorn::McpTransport::Http { url: url.into() },
+        (None, None) => {
+            return Err(McpError::ConfigError(format!("server '{}' has neither `command` nor `url`", id)));
+        }
+    };
+
+    Ok(McpServerConfig {
+        name: entry.name.unwrap_or_else(|| id.clone()),
+        id,
+        transport,
+        env: entry.env,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_object_and_array_formats() {
+        let keyed = parse_configs(serde_json::json!({
+            "mcpServers": {
+                "fs": {"command": "mcp-fs", "args": ["/tmp"], "env": {"DEBUG": "1"}}
+            }
+        })).unwrap().enabled;
+        assert_eq!(keyed[0].id, "fs");
+        assert_eq!(keyed[0].name, "fs");
+        assert_eq!(keyed[0].env["DEBUG"], "1");
+        assert!(matches!(
+            &keyed[0].transport,
+            warhorn::McpTransport::Stdio { command, args } if command == "mcp-fs" && args == &["/tmp"]
+        ));
+
+        let listed = parse_configs(serde_json::json!([
+            {"id": "web", "name": "Web", "url": "http://localhost:8080/mcp"}
+        ])).unwrap().enabled;
+        assert_eq!(listed[0].name, "Web");
+
+        assert!(parse_configs(serde_json::json!([{"command": "anon"}])).is_err());
+    }
+
+    #[test]
+    fn test_disabled_servers_kept_aside() {
+        let configs = parse_configs(serde_json::json!({
+            "mcpServers": {
+                "on": {"command": "a"},
+                "off": {"command": "b", "disabled": true},
+                "also-off": {"command": "c", "enabled": false}
+            }
+        })).unwrap();
+
+        assert_eq!(configs.enabled.len(), 1);
+        let mut disabled: Vec<_> = configs.disabled.iter().map(|c| c.id.as_str()).collect();
+        disabled.sort();
+        assert_eq!(disabled, ["also-off", "off"]);
+    }
+
+    #[test]
+    fn test_load_toml() {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        writeln!(file, "[mcpServers.git]\ncommand = \"mcp-git\"\nargs = [\"--repo\", \".\"]").unwrap();
+
+        let configs = load_configs(file.path()).unwrap();
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].id, "git");
+    }
+}
@@ -1,26 +1,260 @@
 //! Single MCP server connection
 
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::sync::Mutex;
-use tracing::{debug, info, warn};
+use std::time::{Duration, Instant, SystemTime};
+use futures::Stream;
+use serde::de::DeserializeOwned;
+use tokio::sync::{broadcast, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn, Instrument};
 
 use warhorn::McpServerConfig;
-use crate::transport::McpTransport;
-use crate::types::{ToolSchema, ServerInfo};
+use crate::metrics::{McpMetrics, ServerMetrics};
+use crate::reconnect::{ReconnectLimiter, ReconnectPolicy};
+use crate::tasks::spawn_on;
+use crate::transport::{ConfigTransportFactory, McpTransport, TransportFactory, TransportOptions};
+use crate::types::{
+    ToolSchema, ServerInfo, LogLevel, ResourceContents, ResourceSchema, PromptSchema, PromptResult, ResourceUpdate, InFlightRequest, ToolCallResult,
+    ToolInvocation, ProcessExit, TrafficStats, ToolOutputChunk, ToolContent, Progress, PARTIAL_CONTENT_METHOD, ProbeOutcome,
+    ClientCapabilities, EffectiveCapabilities, ConnectionEvent,
+};
 use crate::error::McpError;
 
+/// Capacity of the channel fanning out server-initiated messages
+const INBOUND_CAPACITY: usize = 256;
+
+/// Capacity of the channel fanning out connection events
+const EVENT_CAPACITY: usize = 16;
+
+/// Default cap on pages fetched by a single paginated list call
+pub const DEFAULT_MAX_LIST_PAGES: usize = 100;
+
+/// Default time to wait for the response to a single request
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time a server process gets to exit by itself on `shutdown`
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// How often `drain` re-checks for outstanding requests
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Methods tried by `probe_methods`
+const PROBED_METHODS: [&str; 4] = ["tools/list", "resources/list", "prompts/list", "ping"];
+
+/// JSON-RPC "method not found" error code
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Protocol version requested in `initialize` unless pinned per connection
+pub const DEFAULT_PROTOCOL_VERSION: &str = "2024-11-05";
+
 /// Connection to a single MCP server
 pub struct McpConnection {
     /// Server configuration
     config: McpServerConfig,
     /// Transport layer
-    transport: Mutex<Option<Box<dyn McpTransport>>>,
+    transport: parking_lot::RwLock<Option<Arc<dyn McpTransport>>>,
+    /// Server-initiated messages, forwarded from whichever transport is current
+    inbound: broadcast::Sender<serde_json::Value>,
+    /// Minimum log level requested via `logging/setLevel`
+    log_level: parking_lot::Mutex<Option<LogLevel>>,
     /// Whether connected
     connected: AtomicBool,
     /// Server info (after initialization)
     server_info: Mutex<Option<ServerInfo>>,
     /// Request ID counter
     request_id: std::sync::atomic::AtomicU64,
+    /// Prefix making request ids unique across clients, e.g. `hostA` for `"hostA-42"`
+    id_prefix: Option<String>,
+    /// Options passed to the transport on initialization
+    transport_options: TransportOptions,
+    /// Creates the transport on initialize and reconnect
+    transport_factory: Arc<dyn TransportFactory>,
+    /// Maximum pages fetched by one paginated list call
+    max_list_pages: usize,
+    /// `_meta` key carrying the trace context (None disables propagation)
+    trace_meta_key: Option<String>,
+    /// Cache of `resources/read` results, if enabled
+    resource_cache: Option<ResourceCache>,
+    /// Method and start time of each request awaiting a response, by id
+    in_flight: parking_lot::Mutex<HashMap<u64, (String, Instant)>>,
+    /// Maximum serialized size of a tool result's content (None is unlimited)
+    max_result_size: Option<usize>,
+    /// Maximum serialized size of tool call arguments (None is unlimited)
+    max_argument_size: Option<usize>,
+    /// Most recent tool calls, if enabled
+    invocations: Option<InvocationHistory>,
+    /// Protocol version the server must accept, overriding the default
+    protocol_version: Option<String>,
+    /// Last sandbox state sent, replayed after every (re)initialize
+    sandbox_state: parking_lot::Mutex<Option<(bool, String)>>,
+    /// Global pacing for `reconnect`, shared with other connections
+    reconnect_limiter: Option<Arc<ReconnectLimiter>>,
+    /// Capabilities advertised in `initialize`
+    client_capabilities: ClientCapabilities,
+    /// Leave out empty `params` on every message
+    omit_empty_params: bool,
+    /// Leave out empty `params` on messages for these methods
+    omit_empty_params_for: HashSet<String>,
+    /// Time to wait for the response to a single request
+    request_timeout: Duration,
+    /// How to reconnect when a request finds the transport broken (None never does)
+    reconnect_policy: Option<ReconnectPolicy>,
+    /// Set while recovering, so concurrent failures trigger a single reconnect
+    recovering: AtomicBool,
+    /// Connection lifecycle events, e.g. automatic reconnects
+    events: broadcast::Sender<ConnectionEvent>,
+    /// The `Arc` this connection lives in, if made with `into_shared`
+    this: std::sync::OnceLock<std::sync::Weak<McpConnection>>,
+    /// Set by `shutdown`; recovery stops once it is
+    shut_down: AtomicBool,
+    /// Request and tool call counters
+    metrics: McpMetrics,
+    /// Time a server process gets to exit by itself before it is killed
+    shutdown_grace: Duration,
+}
+
+/// Resource contents cached by URI for a fixed time-to-live.
+///
+/// `notifications/resources/updated` evicts the URI early. Notifications
+/// are drained lazily on lookup rather than by a background task.
+struct ResourceCache {
+    ttl: Duration,
+    entries: parking_lot::Mutex<HashMap<String, (Instant, Vec<ResourceContents>)>>,
+    updates: parking_lot::Mutex<broadcast::Receiver<serde_json::Value>>,
+}
+
+impl ResourceCache {
+    fn new(ttl: Duration, updates: broadcast::Receiver<serde_json::Value>) -> Self {
+        Self {
+            ttl,
+            entries: parking_lot::Mutex::new(HashMap::new()),
+            updates: parking_lot::Mutex::new(updates),
+        }
+    }
+
+    fn get(&self, uri: &str) -> Option<Vec<ResourceContents>> {
+        self.apply_updates();
+
+        let mut entries = self.entries.lock();
+        match entries.get(uri) {
+            Some((stored, contents)) if stored.elapsed() < self.ttl => Some(contents.clone()),
+            Some(_) => {
+                entries.remove(uri);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, uri: &str, contents: Vec<ResourceContents>) {
+        self.entries.lock().insert(uri.to_string(), (Instant::now(), contents));
+    }
+
+    /// Apply updates the server reported since the last lookup.
+    ///
+    /// Pushed contents replace the entry; a bare URI evicts it.
+    fn apply_updates(&self) {
+        let mut updates = self.updates.lock();
+        loop {
+            match updates.try_recv() {
+                Ok(message) => {
+                    if message["method"] != "notifications/resources/updated" {
+                        continue;
+                    }
+                    match ResourceUpdate::from_params(&message["params"]) {
+                        Some(ResourceUpdate { uri, contents: Some(contents) }) => self.insert(&uri, contents),
+                        Some(ResourceUpdate { uri, contents: None }) => {
+                            self.entries.lock().remove(&uri);
+                        }
+                        None => {}
+                    }
+                }
+                // We can't know what was missed, so start over
+                Err(broadcast::error::TryRecvError::Lagged(_)) => self.entries.lock().clear(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Ring buffer of the most recent tool calls
+struct InvocationHistory {
+    capacity: usize,
+    entries: parking_lot::Mutex<VecDeque<ToolInvocation>>,
+}
+
+impl InvocationHistory {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: parking_lot::Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    fn record(&self, invocation: ToolInvocation) {
+        let mut entries = self.entries.lock();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(invocation);
+    }
+}
+
+/// Removes a request from the in-flight map however it finishes
+struct InFlightGuard<'a> {
+    in_flight: &'a parking_lot::Mutex<HashMap<u64, (String, Instant)>>,
+    id: u64,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.lock().remove(&self.id);
+    }
+}
+
+/// Cancels a request on the server if its caller goes away before the response.
+///
+/// Dropped while armed (the request future was dropped mid-flight), it sends
+/// `notifications/cancelled` from a spawned task, since `Drop` can't await.
+struct CancelOnDrop {
+    transport: Arc<dyn McpTransport>,
+    request_id: serde_json::Value,
+    armed: bool,
+}
+
+impl CancelOnDrop {
+    /// The exchange finished or was cancelled explicitly; nothing to do on drop
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        // No runtime means the process is shutting down; the server will notice
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let transport = self.transport.clone();
+        let notice = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": {
+                "requestId": self.request_id,
+                "reason": "Request dropped by client"
+            }
+        });
+        runtime.spawn(async move {
+            if let Err(e) = transport.send_notification(notice).await {
+                debug!(error = %e, "Failed to send cancellation for dropped request");
+            }
+        });
+    }
 }
 
 impl McpConnection {
@@ -28,44 +262,262 @@ impl McpConnection {
     pub async fn new(config: McpServerConfig) -> Result<Self, McpError> {
         Ok(Self {
             config,
-            transport: Mutex::new(None),
+            transport: parking_lot::RwLock::new(None),
+            inbound: broadcast::channel(INBOUND_CAPACITY).0,
+            log_level: parking_lot::Mutex::new(None),
             connected: AtomicBool::new(false),
             server_info: Mutex::new(None),
             request_id: std::sync::atomic::AtomicU64::new(0),
+            id_prefix: None,
+            transport_options: TransportOptions::default(),
+            transport_factory: Arc::new(ConfigTransportFactory),
+            max_list_pages: DEFAULT_MAX_LIST_PAGES,
+            trace_meta_key: None,
+            resource_cache: None,
+            in_flight: parking_lot::Mutex::new(HashMap::new()),
+            max_result_size: None,
+            max_argument_size: None,
+            invocations: None,
+            protocol_version: None,
+            sandbox_state: parking_lot::Mutex::new(None),
+            reconnect_limiter: None,
+            client_capabilities: ClientCapabilities::default(),
+            omit_empty_params: false,
+            omit_empty_params_for: HashSet::new(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            reconnect_policy: None,
+            recovering: AtomicBool::new(false),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            this: std::sync::OnceLock::new(),
+            shut_down: AtomicBool::new(false),
+            metrics: McpMetrics::default(),
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+        })
+    }
+
+    /// Set the transport options used when the connection is initialized
+    pub fn with_transport_options(mut self, options: TransportOptions) -> Self {
+        self.transport_options = options;
+        self
+    }
+
+    /// Send string request ids like `"{prefix}-42"` instead of plain numbers.
+    ///
+    /// For clients sharing a server through a multiplexing proxy, where
+    /// numeric ids from different clients would collide.
+    pub fn with_id_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.id_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set the maximum number of pages fetched by one paginated list call
+    pub fn with_max_list_pages(mut self, max_pages: usize) -> Self {
+        self.max_list_pages = max_pages;
+        self
+    }
+
+    /// Truncate tool results larger than `bytes` of serialized content.
+    ///
+    /// Truncated results are flagged rather than failed; see
+    /// `ToolCallResult::truncate_to`.
+    pub fn with_max_result_size(mut self, bytes: usize) -> Self {
+        self.max_result_size = Some(bytes);
+        self
+    }
+
+    /// Reject tool calls whose serialized arguments exceed `bytes`.
+    ///
+    /// Oversized calls fail with `McpError::InvalidArguments` before
+    /// anything is sent.
+    pub fn with_max_argument_size(mut self, bytes: usize) -> Self {
+        self.max_argument_size = Some(bytes);
+        self
+    }
+
+    /// Keep the last `capacity` tool calls for `recent_invocations`.
+    ///
+    /// Without this nothing is recorded or timed.
+    pub fn with_invocation_history(mut self, capacity: usize) -> Self {
+        self.invocations = (capacity > 0).then(|| InvocationHistory::new(capacity));
+        self
+    }
+
+    /// Set the factory used to create transports on initialize and reconnect
+    pub fn with_transport_factory(mut self, factory: Arc<dyn TransportFactory>) -> Self {
+        self.transport_factory = factory;
+        self
+    }
+
+    /// Attach a W3C traceparent to every request's `_meta` under `key`.
+    ///
+    /// See [`crate::trace`] for how the trace id is chosen.
+    pub fn with_trace_propagation(mut self, key: impl Into<String>) -> Self {
+        self.trace_meta_key = Some(key.into());
+        self
+    }
+
+    /// Cache `read_resource` results for `ttl`.
+    ///
+    /// Entries for subscribed URIs are evicted as soon as the server sends
+    /// `notifications/resources/updated`.
+    pub fn with_resource_cache(mut self, ttl: Duration) -> Self {
+        self.resource_cache = Some(ResourceCache::new(ttl, self.inbound.subscribe()));
+        self
+    }
+
+    /// Pin the protocol version sent in `initialize`.
+    ///
+    /// Initialization fails with `McpError::ProtocolError` if the server
+    /// answers with a different version.
+    pub fn with_protocol_version(mut self, version: impl Into<String>) -> Self {
+        self.protocol_version = Some(version.into());
+        self
+    }
+
+    /// Pace `reconnect` through a limiter shared with other connections
+    pub fn with_reconnect_limiter(mut self, limiter: Arc<ReconnectLimiter>) -> Self {
+        self.reconnect_limiter = Some(limiter);
+        self
+    }
+
+    /// Fail requests with `McpError::Timeout` when the server hasn't answered within `timeout`.
+    ///
+    /// Defaults to [`DEFAULT_REQUEST_TIMEOUT`]. The server is sent
+    /// `notifications/cancelled` for a request that times out, and a late
+    /// response to it is discarded.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Set how long `shutdown` lets a server process exit by itself.
+    ///
+    /// Defaults to [`DEFAULT_SHUTDOWN_GRACE`]. Zero skips the `shutdown`
+    /// request and `exit` notification and kills the process straight away.
+    pub fn with_shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = grace;
+        self
+    }
+
+    /// Reconnect automatically when a request finds the transport broken.
+    ///
+    /// The request that hit the failure fails straight away while a
+    /// background task reconnects, so callers can retry it. After a
+    /// successful reconnect `events` delivers `ConnectionEvent::Reconnected`,
+    /// as the new server process may expose different tools. The background
+    /// task needs the connection to be shared via `into_shared`; otherwise
+    /// the policy is ignored with a warning.
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy.is_enabled().then_some(policy);
+        self
+    }
+
+    /// Move the connection into an `Arc`, which automatic reconnects need
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new_cyclic(|this| {
+            let _ = self.this.set(this.clone());
+            self
         })
     }
 
-    /// Initialize the connection
+    /// Set the capabilities advertised in `initialize`
+    pub fn with_client_capabilities(mut self, capabilities: ClientCapabilities) -> Self {
+        self.client_capabilities = capabilities;
+        self
+    }
+
+    /// Leave out the `params` field instead of sending `{}` on every message.
+    ///
+    /// JSON-RPC allows omitting `params`, and some strict servers reject an
+    /// empty object where they expect none. Params that aren't empty are
+    /// always sent.
+    pub fn with_empty_params_omitted(mut self) -> Self {
+        self.omit_empty_params = true;
+        self
+    }
+
+    /// Like `with_empty_params_omitted`, but only for requests and notifications of `method`
+    pub fn with_empty_params_omitted_for(mut self, method: impl Into<String>) -> Self {
+        self.omit_empty_params_for.insert(method.into());
+        self
+    }
+
+    /// Use an already-established transport instead of creating one from the config
+    pub fn with_transport(self, transport: Arc<dyn McpTransport>) -> Self {
+        self.install_transport(transport);
+        self
+    }
+
+    /// Initialize the connection.
+    ///
+    /// Fails if the `notifications/initialized` that completes the handshake
+    /// can't be sent, even though the server answered `initialize`.
     pub async fn initialize(&self) -> Result<ServerInfo, McpError> {
         info!(server_id = %self.config.id, "Initializing MCP connection");
         
-        // Create transport based on config
-        let transport = crate::transport::create_transport(&self.config).await?;
-        *self.transport.lock().await = Some(transport);
+        // Create transport based on config, unless one was supplied
+        if self.transport.read().is_none() {
+            let transport = self.transport_factory.create(&self.config, &self.transport_options).await?;
+            self.install_transport(transport);
+        }
         
         // Send initialize request
+        let protocol_version = self.protocol_version.as_deref().unwrap_or(DEFAULT_PROTOCOL_VERSION);
         let init_response = self.send_request("initialize", serde_json::json!({
-            "protocolVersion": "2024-11-05",
-            "capabilities": {
-                "tools": {},
-                "sampling": {}
-            },
+            "protocolVersion": protocol_version,
+            "capabilities": self.client_capabilities.to_json(),
             "clientInfo": {
                 "name": "lair",
                 "version": env!("CARGO_PKG_VERSION")
             }
         })).await?;
         
+        // Tolerate servers that omit the (required) name
+        let mut init_response = init_response;
+        if let Some(result) = init_response.as_object_mut() {
+            if !result.get("name").is_some_and(|n| n.is_string()) {
+                warn!(server_id = %self.config.id, "Initialize result has no server name, using server id");
+                result.insert("name".into(), serde_json::Value::String(self.config.id.clone()));
+            }
+        }
+        
         // Parse server info
         let server_info: ServerInfo = serde_json::from_value(init_response)
             .map_err(|e| McpError::ProtocolError(format!("Invalid server info: {}", e)))?;
         
+        if let Some(pinned) = &self.protocol_version {
+            if server_info.protocol_version != *pinned {
+                return Err(McpError::ProtocolError(format!(
+                    "Server negotiated protocol version {:?}, expected pinned {}",
+                    server_info.protocol_version, pinned
+                )));
+            }
+        }
+        
+        // The handshake isn't complete until the server has been told, so a
+        // failure here fails initialize rather than leaving a half-open session.
+        // Other variants (e.g. ProcessExited) keep their meaning for callers
+        self.send_notification("notifications/initialized", serde_json::json!({})).await
+            .map_err(|e| {
+                warn!(server_id = %self.config.id, error = %e, "Failed to send notifications/initialized");
+                match e {
+                    McpError::TransportError(message) => McpError::TransportError(format!(
+                        "Failed to send notifications/initialized: {}", message
+                    )),
+                    e => e,
+                }
+            })?;
+        
+        // A fresh server session knows nothing of the sandbox; replay the last
+        // state before reporting connected, so a failed replay fails initialize
+        let sandbox_state = self.sandbox_state.lock().clone();
+        if let Some((enabled, policy)) = sandbox_state {
+            self.send_sandbox_state(enabled, &policy).await?;
+        }
+        
         *self.server_info.lock().await = Some(server_info.clone());
         self.connected.store(true, Ordering::SeqCst);
         
-        // Send initialized notification
-        self.send_notification("notifications/initialized", serde_json::json!({})).await?;
-        
         info!(
             server_id = %self.config.id,
             server_name = %server_info.name,
@@ -75,135 +527,2090 @@ impl McpConnection {
         Ok(server_info)
     }
 
-    /// List available tools
+    /// List available tools, following pagination cursors
     pub async fn list_tools(&self) -> Result<Vec<ToolSchema>, McpError> {
-        let response = self.send_request("tools/list", serde_json::json!({})).await?;
-        
-        let tools: Vec<ToolSchema> = response["tools"]
-            .as_array()
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| serde_json::from_value(v.clone()).ok())
-                    .collect()
-            })
-            .unwrap_or_default();
+        let tools: Vec<ToolSchema> = self.list_all("tools/list", "tools").await?
+            .into_iter()
+            .filter_map(|v| serde_json::from_value(v).ok())
+            .collect();
         
         debug!(server_id = %self.config.id, num_tools = tools.len(), "Listed tools");
         Ok(tools)
     }
 
-    /// Call a tool
+    /// List available resources, following pagination cursors.
+    ///
+    /// Fails with `McpError::Unsupported` unless the server advertised resources.
+    pub async fn list_resources(&self) -> Result<Vec<ResourceSchema>, McpError> {
+        self.require_capability("resources", EffectiveCapabilities::can_use_resources).await?;
+        let resources: Vec<ResourceSchema> = self.list_all("resources/list", "resources").await?
+            .into_iter()
+            .filter_map(|v| serde_json::from_value(v).ok())
+            .collect();
+        
+        debug!(server_id = %self.config.id, num_resources = resources.len(), "Listed resources");
+        Ok(resources)
+    }
+
+    /// List available prompts, following pagination cursors.
+    ///
+    /// Fails with `McpError::Unsupported` unless the server advertised prompts.
+    pub async fn list_prompts(&self) -> Result<Vec<PromptSchema>, McpError> {
+        self.require_capability("prompts", EffectiveCapabilities::can_use_prompts).await?;
+        let prompts: Vec<PromptSchema> = self.list_all("prompts/list", "prompts").await?
+            .into_iter()
+            .filter_map(|v| serde_json::from_value(v).ok())
+            .collect();
+        
+        debug!(server_id = %self.config.id, num_prompts = prompts.len(), "Listed prompts");
+        Ok(prompts)
+    }
+
+    /// Fetch every page of a paginated list method and collect the `key` arrays.
+    ///
+    /// `nextCursor` is opaque: whatever JSON value the server returns is sent
+    /// back verbatim. Fails with `McpError::ProtocolError` if the server is
+    /// still returning cursors after `max_list_pages` pages.
+    async fn list_all(&self, method: &str, key: &str) -> Result<Vec<serde_json::Value>, McpError> {
+        let mut items = Vec::new();
+        let mut cursor: Option<serde_json::Value> = None;
+        
+        for _ in 0..self.max_list_pages {
+            let params = match &cursor {
+                Some(cursor) => serde_json::json!({ "cursor": cursor }),
+                None => serde_json::json!({}),
+            };
+            let response = self.send_request(method, params).await?;
+            
+            if let Some(page) = response[key].as_array() {
+                items.extend(page.iter().cloned());
+            }
+            
+            match response.get("nextCursor") {
+                Some(next) if !next.is_null() => cursor = Some(next.clone()),
+                _ => return Ok(items),
+            }
+        }
+        
+        Err(McpError::ProtocolError(format!(
+            "{} still paginating after {} pages",
+            method, self.max_list_pages
+        )))
+    }
+
+    /// Call a tool and return its content blocks parsed into [`ToolContent`].
+    ///
+    /// A result flagged `isError` fails with `McpError::ToolError` carrying
+    /// the result's text; use `call_tool_result` to inspect such results, or
+    /// for the raw JSON of the blocks.
     pub async fn call_tool(
         &self,
         name: &str,
         arguments: serde_json::Value,
-    ) -> Result<serde_json::Value, McpError> {
-        debug!(server_id = %self.config.id, tool = %name, "Calling tool");
-        
-        let response = self.send_request("tools/call", serde_json::json!({
-            "name": name,
-            "arguments": arguments
-        })).await?;
-        
-        // Check for error in response
-        if let Some(error) = response.get("error") {
-            return Err(McpError::ToolError(error.to_string()));
+    ) -> Result<Vec<ToolContent>, McpError> {
+        let result = self.call_tool_result(name, arguments).await?;
+        if result.is_error {
+            return Err(McpError::ToolError(result.text()));
         }
-        
-        Ok(response["content"].clone())
+        Ok(result.typed_content())
     }
 
-    /// Send sandbox state notification
-    pub async fn notify_sandbox_state(&self, enabled: bool, policy: &str) -> Result<(), McpError> {
-        self.send_notification("notifications/sandbox_state", serde_json::json!({
-            "enabled": enabled,
-            "policy": policy
-        })).await
+    /// Call a tool and return its typed result.
+    ///
+    /// Results over the configured size limit come back truncated, with
+    /// `truncated` and `original_size` set. A result flagged `isError` is
+    /// returned as is, with `is_error` set.
+    pub async fn call_tool_result(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<ToolCallResult, McpError> {
+        let response = self.call_tool_raw(name, arguments).await?;
+        self.parse_tool_result(name, response)
     }
 
-    /// Ping the server
-    pub async fn ping(&self) -> Result<(), McpError> {
-        self.send_request("ping", serde_json::json!({})).await?;
-        Ok(())
+    /// Parse a `tools/call` result, applying the result size limit
+    fn parse_tool_result(&self, name: &str, response: serde_json::Value) -> Result<ToolCallResult, McpError> {
+        let mut result: ToolCallResult = serde_json::from_value(response)
+            .map_err(|e| McpError::ProtocolError(format!("Invalid tool result: {}", e)))?;
+        
+        if let Some(max) = self.max_result_size {
+            result.truncate_to(max);
+            if let Some(original_size) = result.original_size {
+                warn!(server_id = %self.config.id, tool = %name, original_size, max, "Truncated tool result");
+            }
+        }
+        Ok(result)
     }
 
-    /// Check if connected
-    pub fn is_connected(&self) -> bool {
-        self.connected.load(Ordering::SeqCst)
+    /// Call a tool and deserialize its `structuredContent` into `T`.
+    ///
+    /// Fails with `McpError::ToolError` if the result is flagged `isError`,
+    /// `McpError::MissingStructuredContent` if the tool only returned
+    /// unstructured content, and `McpError::Deserialize` if the structured
+    /// output doesn't match `T`.
+    pub async fn call_tool_as<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<T, McpError> {
+        let response = self.call_tool_raw(name, arguments).await?;
+        if response["isError"] == true {
+            return Err(McpError::ToolError(self.parse_tool_result(name, response)?.text()));
+        }
+        
+        let structured = response.get("structuredContent")
+            .filter(|v| !v.is_null())
+            .ok_or_else(|| McpError::MissingStructuredContent(name.to_string()))?;
+        
+        serde_json::from_value(structured.clone())
+            .map_err(|e| McpError::Deserialize(format!("{}: {}", name, e)))
     }
 
-    /// Get server info
-    pub async fn server_info(&self) -> Option<ServerInfo> {
-        self.server_info.lock().await.clone()
+    /// Call a tool, streaming its text output as it is produced.
+    ///
+    /// The call carries a progress token; the `message` of each matching
+    /// `notifications/progress` is yielded as a chunk. If the server sent no
+    /// progress text, the final result's text content is yielded as a single
+    /// chunk instead. The stream ends when the call completes, yielding the
+    /// error if it failed; a result flagged `isError` yields
+    /// `McpError::ToolError`.
+    pub fn call_tool_streaming_text<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: serde_json::Value,
+    ) -> impl Stream<Item = Result<String, McpError>> + 'a {
+        let token = uuid::Uuid::new_v4().to_string();
+        
+        // Straight from the transport, which broadcasts progress before routing the response
+        let progress = self.current_transport().ok().map(|t| t.subscribe());
+        
+        let mut params = serde_json::json!({
+            "name": name,
+            "arguments": arguments
+        });
+        attach_meta(&mut params, "progressToken", serde_json::Value::String(token.clone()));
+        let call: ToolCall<'a> = Box::pin(self.call_tool_with_params(name, params));
+        
+        futures::stream::unfold(
+            (Some(call), progress, false),
+            move |(call, mut progress, mut streamed)| {
+                let token = token.clone();
+                async move {
+                    let mut call = call?;
+                    loop {
+                        tokio::select! {
+                            biased;
+                            message = next_message(&mut progress) => {
+                                let Some(message) = message else {
+                                    progress = None;
+                                    continue;
+                                };
+                                let params = &message["params"];
+                                if message["method"] != "notifications/progress" || params["progressToken"] != token.as_str() {
+                                    continue;
+                                }
+                                if let Some(text) = params["message"].as_str() {
+                                    streamed = true;
+                                    return Some((Ok(text.to_string()), (Some(call), progress, streamed)));
+                                }
+                            }
+                            result = &mut call => {
+                                let item = match result.and_then(|response| self.parse_tool_result(name, response)) {
+                                    Ok(result) if result.is_error => Err(McpError::ToolError(result.text())),
+                                    Ok(_) if streamed => return None,
+                                    Ok(result) => Ok(result.text()),
+                                    Err(e) => Err(e),
+                                };
+                                return Some((item, (None, progress, streamed)));
+                            }
+                        }
+                    }
+                }
+            },
+        )
     }
 
-    /// Shutdown the connection
-    pub async fn shutdown(&self) -> Result<(), McpError> {
-        self.connected.store(false, Ordering::SeqCst);
+    /// Call a tool, reporting the server's progress updates on the side.
+    ///
+    /// The call carries a progress token; each matching
+    /// `notifications/progress` is sent to the returned stream. Updates are
+    /// only delivered while the returned future is polled, and the stream
+    /// ends once it completes. Results flagged `isError` are returned as is.
+    pub fn call_tool_with_progress<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: serde_json::Value,
+    ) -> (
+        impl Stream<Item = Progress> + 'a,
+        impl std::future::Future<Output = Result<ToolCallResult, McpError>> + 'a,
+    ) {
+        let token = serde_json::Value::String(uuid::Uuid::new_v4().to_string());
         
-        if let Some(transport) = self.transport.lock().await.take() {
-            transport.close().await?;
-        }
+        // Straight from the transport, which broadcasts progress before routing the response
+        let mut updates = self.current_transport().ok().map(|t| t.subscribe());
         
-        info!(server_id = %self.config.id, "MCP connection shutdown");
-        Ok(())
+        let mut params = serde_json::json!({
+            "name": name,
+            "arguments": arguments
+        });
+        attach_meta(&mut params, "progressToken", token.clone());
+        
+        let (progress_tx, progress_rx) = futures::channel::mpsc::unbounded();
+        let call = async move {
+            let call = self.call_tool_with_params(name, params);
+            tokio::pin!(call);
+            loop {
+                tokio::select! {
+                    biased;
+                    message = next_message(&mut updates) => {
+                        let Some(message) = message else {
+                            updates = None;
+                            continue;
+                        };
+                        if let Some(progress) = Progress::from_notification(&message, &token) {
+                            // The caller may have stopped listening
+                            let _ = progress_tx.unbounded_send(progress);
+                        }
+                    }
+                    result = &mut call => {
+                        return result.and_then(|response| self.parse_tool_result(name, response));
+                    }
+                }
+            }
+        };
+        (progress_rx, call)
     }
 
-    /// Send a JSON-RPC request
-    async fn send_request(
+    /// Call a tool, streaming content blocks the server pushes before its result.
+    ///
+    /// Servers that stream send [`PARTIAL_CONTENT_METHOD`] notifications
+    /// carrying the call's progress token; each one is yielded as a
+    /// `Partial` chunk. The stream always ends with a `Final` chunk holding
+    /// the whole result, or with the error if the call failed. If the final
+    /// result has no content of its own, it carries the streamed blocks in
+    /// order. Servers that don't stream yield just the `Final` chunk.
+    ///
+    /// This is separate from `notifications/progress`, which carries status
+    /// rather than content (see `call_tool_streaming_text`).
+    pub fn call_tool_streaming<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: serde_json::Value,
+    ) -> impl Stream<Item = Result<ToolOutputChunk, McpError>> + 'a {
+        let token = uuid::Uuid::new_v4().to_string();
+        
+        // Straight from the transport, which broadcasts notifications before routing the response
+        let partials = self.current_transport().ok().map(|t| t.subscribe());
+        
+        let mut params = serde_json::json!({
+            "name": name,
+            "arguments": arguments
+        });
+        attach_meta(&mut params, "progressToken", serde_json::Value::String(token.clone()));
+        let call: ToolCall<'a> = Box::pin(self.call_tool_with_params(name, params));
+        
+        futures::stream::unfold(
+            (Some(call), partials, Vec::new()),
+            move |(call, mut partials, mut streamed)| {
+                let token = token.clone();
+                async move {
+                    let mut call = call?;
+                    loop {
+                        tokio::select! {
+                            biased;
+                            message = next_message(&mut partials) => {
+                                let Some(message) = message else {
+                                    partials = None;
+                                    continue;
+                                };
+                                let params = &message["params"];
+                                if message["method"] != PARTIAL_CONTENT_METHOD || params["progressToken"] != token.as_str() {
+                                    continue;
+                                }
+                                let Some(blocks) = params["content"].as_array() else {
+                                    continue;
+                                };
+                                streamed.extend(blocks.iter().cloned());
+                                let chunk = ToolOutputChunk::Partial(blocks.clone());
+                                return Some((Ok(chunk), (Some(call), partials, streamed)));
+                            }
+                            result = &mut call => {
+                                let item = result
+                                    .and_then(|response| self.parse_tool_result(name, response))
+                                    .map(|mut result| {
+                                        if result.content.is_empty() {
+                                            result.content = std::mem::take(&mut streamed);
+                                        }
+                                        ToolOutputChunk::Final(result)
+                                    });
+                                return Some((item, (None, partials, streamed)));
+                            }
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    /// Send `tools/call` and return the whole result object
+    async fn call_tool_raw(
         &self,
-        method: &str,
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<serde_json::Value, McpError> {
+        self.call_tool_with_params(name, serde_json::json!({
+            "name": name,
+            "arguments": arguments
+        })).await
+    }
+
+    /// Send `tools/call` with prepared params and return the whole result object
+    async fn call_tool_with_params(
+        &self,
+        name: &str,
         params: serde_json::Value,
     ) -> Result<serde_json::Value, McpError> {
-        let id = self.request_id.fetch_add(1, Ordering::SeqCst);
-        
-        let request = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "method": method,
-            "params": params
-        });
+        debug!(server_id = %self.config.id, tool = %name, "Calling tool");
+        self.metrics.record_tool_call(name);
         
-        let transport = self.transport.lock().await;
-        let transport = transport.as_ref()
-            .ok_or_else(|| McpError::NotConnected)?;
+        let argument_size = || serde_json::to_vec(&params["arguments"]).map(|b| b.len()).unwrap_or(0);
+        if let Some(max) = self.max_argument_size {
+            let size = argument_size();
+            if size > max {
+                return Err(McpError::InvalidArguments {
+                    errors: vec![format!("arguments are {} bytes, limit is {}", size, max)],
+                });
+            }
+        }
         
-        let response = transport.send_request(request).await?;
+        let Some(history) = &self.invocations else {
+            return self.send_tool_call(params).await;
+        };
+        let argument_size = argument_size();
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        let result = self.send_tool_call(params).await;
+        history.record(ToolInvocation {
+            tool: name.to_string(),
+            argument_size,
+            started_at,
+            duration: started.elapsed(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        result
+    }
+
+    /// Send `tools/call` and turn an error in the result into `McpError::ToolFailed`.
+    ///
+    /// A result with none of `content`, `structuredContent` and `isError`
+    /// (e.g. `{}`) violates the spec and fails with `McpError::ProtocolError`
+    /// rather than passing for an empty success.
+    async fn send_tool_call(&self, params: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let response = self.send_request("tools/call", params).await?;
         
-        // Check for JSON-RPC error
+        // Check for error in response
         if let Some(error) = response.get("error") {
-            return Err(McpError::RpcError {
-                code: error["code"].as_i64().unwrap_or(-1),
-                message: error["message"].as_str().unwrap_or("Unknown error").to_string(),
-            });
+            return Err(tool_failure(error, &response));
         }
         
-        Ok(response["result"].clone())
+        if ["content", "structuredContent", "isError"].iter().all(|key| response.get(key).is_none()) {
+            warn!(server_id = %self.config.id, "Tool result has no content");
+            return Err(McpError::ProtocolError("tool result missing content".into()));
+        }
+        
+        Ok(response)
     }
 
-    /// Send a JSON-RPC notification (no response expected)
-    async fn send_notification(
+    /// Read a resource by URI, consulting the resource cache if enabled.
+    ///
+    /// Fails with `McpError::Unsupported` unless the server advertised resources.
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>, McpError> {
+        self.fetch_resource(uri, None).await
+    }
+
+    /// Like `read_resource`, but abandoned when `cancel` fires.
+    ///
+    /// The server is sent `notifications/cancelled` for the read, and the
+    /// call fails with `McpError::Cancelled`. The same applies to every
+    /// `*_cancellable` method.
+    pub async fn read_resource_cancellable(
         &self,
-        method: &str,
-        params: serde_json::Value,
-    ) -> Result<(), McpError> {
-        let notification = serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": method,
-            "params": params
-        });
+        uri: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<ResourceContents>, McpError> {
+        self.fetch_resource(uri, Some(cancel)).await
+    }
+
+    async fn fetch_resource(
+        &self,
+        uri: &str,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<ResourceContents>, McpError> {
+        self.require_capability("resources", EffectiveCapabilities::can_use_resources).await?;
+        if let Some(contents) = self.resource_cache.as_ref().and_then(|c| c.get(uri)) {
+            debug!(server_id = %self.config.id, uri = %uri, "Resource cache hit");
+            return Ok(contents);
+        }
         
-        let transport = self.transport.lock().await;
-        let transport = transport.as_ref()
-            .ok_or_else(|| McpError::NotConnected)?;
+        let response = self.send_request_cancellable("resources/read", serde_json::json!({
+            "uri": uri
+        }), cancel).await?;
         
-        transport.send_notification(notification).await
+        let contents: Vec<ResourceContents> = serde_json::from_value(response["contents"].clone())
+            .map_err(|e| McpError::ProtocolError(format!("Invalid resource contents: {}", e)))?;
+        
+        if let Some(cache) = &self.resource_cache {
+            cache.insert(uri, contents.clone());
+        }
+        Ok(contents)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Ask the server to send `notifications/resources/updated` for `uri`.
+    ///
+    /// Fails with `McpError::Unsupported` unless the server advertised
+    /// resource subscriptions.
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<(), McpError> {
+        self.require_capability("resource subscriptions", EffectiveCapabilities::can_subscribe_resources).await?;
+        self.send_request("resources/subscribe", serde_json::json!({
+            "uri": uri
+        })).await?;
+        Ok(())
+    }
+
+    /// Stop update notifications for `uri`
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<(), McpError> {
+        self.require_capability("resource subscriptions", EffectiveCapabilities::can_subscribe_resources).await?;
+        self.send_request("resources/unsubscribe", serde_json::json!({
+            "uri": uri
+        })).await?;
+        Ok(())
+    }
+
+    /// Render a prompt via `prompts/get`.
+    ///
+    /// Fails with `McpError::Unsupported` unless the server advertised prompts.
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<PromptResult, McpError> {
+        self.fetch_prompt(name, arguments, None).await
+    }
 
-    // Tests would require mock transport
+    /// Like `get_prompt`, but abandoned when `cancel` fires
+    pub async fn get_prompt_cancellable(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+        cancel: &CancellationToken,
+    ) -> Result<PromptResult, McpError> {
+        self.fetch_prompt(name, arguments, Some(cancel)).await
+    }
+
+    async fn fetch_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<PromptResult, McpError> {
+        self.require_capability("prompts", EffectiveCapabilities::can_use_prompts).await?;
+        let result = self.send_request_cancellable("prompts/get", serde_json::json!({
+            "name": name,
+            "arguments": arguments
+        }), cancel).await?;
+        serde_json::from_value(result)
+            .map_err(|e| McpError::ProtocolError(format!("Invalid prompts/get result: {}", e)))
+    }
+
+    /// Ask for completions of argument `argument` currently set to `value`.
+    ///
+    /// `reference` is the prompt or resource template being completed, e.g.
+    /// `{"type": "ref/prompt", "name": "review"}`. Fails with
+    /// `McpError::Unsupported` unless the server advertised completions.
+    pub async fn complete(
+        &self,
+        reference: serde_json::Value,
+        argument: &str,
+        value: &str,
+    ) -> Result<Vec<String>, McpError> {
+        self.fetch_completions(reference, argument, value, None).await
+    }
+
+    /// Like `complete`, but abandoned when `cancel` fires
+    pub async fn complete_cancellable(
+        &self,
+        reference: serde_json::Value,
+        argument: &str,
+        value: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<String>, McpError> {
+        self.fetch_completions(reference, argument, value, Some(cancel)).await
+    }
+
+    async fn fetch_completions(
+        &self,
+        reference: serde_json::Value,
+        argument: &str,
+        value: &str,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<String>, McpError> {
+        self.require_capability("completions", EffectiveCapabilities::can_use_completions).await?;
+        let response = self.send_request_cancellable("completion/complete", serde_json::json!({
+            "ref": reference,
+            "argument": {"name": argument, "value": value}
+        }), cancel).await?;
+        
+        serde_json::from_value(response["completion"]["values"].clone())
+            .map_err(|e| McpError::ProtocolError(format!("Invalid completion result: {}", e)))
+    }
+
+    /// Send an arbitrary request and return its result.
+    ///
+    /// Escape hatch for methods this crate doesn't model, such as those of
+    /// experimental capabilities (see `ServerCapabilities::unknown_keys`).
+    /// Bypasses the capability checks of the typed methods, so it can also
+    /// reach methods of capabilities the server didn't advertise.
+    pub async fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, McpError> {
+        self.send_request(method, params).await
+    }
+
+    /// Send an arbitrary notification
+    pub async fn notify(&self, method: &str, params: serde_json::Value) -> Result<(), McpError> {
+        self.send_notification(method, params).await
+    }
+
+    /// Set the minimum level of log messages the server should send
+    pub async fn set_log_level(&self, level: LogLevel) -> Result<(), McpError> {
+        self.send_request("logging/setLevel", serde_json::json!({
+            "level": level
+        })).await?;
+        *self.log_level.lock() = Some(level);
+        Ok(())
+    }
+
+    /// Log level last set with `set_log_level`, if any
+    pub fn log_level(&self) -> Option<LogLevel> {
+        *self.log_level.lock()
+    }
+
+    /// Subscribe to messages initiated by the server.
+    ///
+    /// The subscription survives transport replacement; it only closes when
+    /// the connection itself is dropped.
+    pub fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.inbound.subscribe()
+    }
+
+    /// Subscribe to connection lifecycle events, e.g. automatic reconnects
+    pub fn events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Send sandbox state notification.
+    ///
+    /// The state is remembered and re-sent whenever the connection is
+    /// re-initialized, so a reconnected server never runs with stale policy.
+    pub async fn notify_sandbox_state(&self, enabled: bool, policy: &str) -> Result<(), McpError> {
+        *self.sandbox_state.lock() = Some((enabled, policy.to_string()));
+        self.send_sandbox_state(enabled, policy).await
+    }
+
+    async fn send_sandbox_state(&self, enabled: bool, policy: &str) -> Result<(), McpError> {
+        self.send_notification("notifications/sandbox_state", serde_json::json!({
+            "enabled": enabled,
+            "policy": policy
+        })).await
+    }
+
+    /// Ping the server
+    pub async fn ping(&self) -> Result<(), McpError> {
+        self.send_request("ping", serde_json::json!({})).await?;
+        Ok(())
+    }
+
+    /// Config this connection was created from
+    pub fn config(&self) -> &McpServerConfig {
+        &self.config
+    }
+
+    /// Check if connected
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// Get server info
+    pub async fn server_info(&self) -> Option<ServerInfo> {
+        self.server_info.lock().await.clone()
+    }
+
+    /// Whether the server can return structured tool output.
+    ///
+    /// False until initialized. See `ServerInfo::supports_structured_output`
+    /// and its siblings for the version and capability checks.
+    pub async fn supports_structured_output(&self) -> bool {
+        self.server_supports(ServerInfo::supports_structured_output).await
+    }
+
+    /// Whether the server may ask the client to elicit user input
+    pub async fn supports_elicitation(&self) -> bool {
+        self.server_supports(ServerInfo::supports_elicitation).await
+    }
+
+    /// Whether the server offers argument completions
+    pub async fn supports_completions(&self) -> bool {
+        self.server_supports(ServerInfo::supports_completions).await
+    }
+
+    /// Whether the server's tools may carry behavior annotations
+    pub async fn supports_tool_annotations(&self) -> bool {
+        self.server_supports(ServerInfo::supports_tool_annotations).await
+    }
+
+    /// Whether the server accepts resource subscriptions
+    pub async fn supports_resource_subscriptions(&self) -> bool {
+        self.server_supports(ServerInfo::supports_resource_subscriptions).await
+    }
+
+    /// Find out by trial which basic methods the server answers.
+    ///
+    /// Sends `tools/list`, `resources/list`, `prompts/list` and `ping` (only
+    /// the first page of each listing), for servers whose advertised
+    /// capabilities can't be trusted. These are real requests, so this is
+    /// never done implicitly; call it only when the empirical map is wanted.
+    pub async fn probe_methods(&self) -> HashMap<String, ProbeOutcome> {
+        let probes = PROBED_METHODS.iter().map(|&method| async move {
+            let outcome = match self.send_request(method, serde_json::json!({})).await {
+                Ok(_) => ProbeOutcome::Supported,
+                Err(McpError::RpcError { code: METHOD_NOT_FOUND, .. }) => ProbeOutcome::NotFound,
+                Err(e) => ProbeOutcome::Failed(e.to_string()),
+            };
+            (method.to_string(), outcome)
+        });
+        futures::future::join_all(probes).await.into_iter().collect()
+    }
+
+    /// What both sides negotiated, or `None` before initialization.
+    ///
+    /// Check this rather than the server's capabilities alone; e.g.
+    /// sampling is only usable if the client advertised it too.
+    pub async fn effective_capabilities(&self) -> Option<EffectiveCapabilities> {
+        self.server_info.lock().await.as_ref()
+            .map(|info| EffectiveCapabilities::negotiate(&self.client_capabilities, info))
+    }
+
+    /// Fail with `Unsupported` unless the negotiated capabilities pass `check`,
+    /// or with `NotConnected` before the connection is initialized
+    async fn require_capability(
+        &self,
+        capability: &'static str,
+        check: fn(&EffectiveCapabilities) -> bool,
+    ) -> Result<(), McpError> {
+        match self.effective_capabilities().await {
+            None => Err(McpError::NotConnected),
+            Some(capabilities) if check(&capabilities) => Ok(()),
+            Some(_) => Err(McpError::Unsupported {
+                server_id: self.config.id.clone(),
+                capability,
+            }),
+        }
+    }
+
+    /// Evaluate a feature check against the initialize result, if any
+    async fn server_supports(&self, check: fn(&ServerInfo) -> bool) -> bool {
+        self.server_info.lock().await.as_ref().is_some_and(check)
+    }
+
+    /// Snapshot of the requests currently awaiting a response, oldest first
+    pub fn in_flight_requests(&self) -> Vec<InFlightRequest> {
+        let mut requests: Vec<_> = self.in_flight.lock()
+            .iter()
+            .map(|(&id, (method, started))| InFlightRequest {
+                id,
+                method: method.clone(),
+                elapsed: started.elapsed(),
+            })
+            .collect();
+        requests.sort_by_key(|r| r.id);
+        requests
+    }
+
+    /// Wait up to `timeout` for every in-flight request to finish.
+    ///
+    /// Returns whether the connection drained. Requests started while
+    /// waiting are waited for too, so stop routing new calls here first.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.in_flight.lock().is_empty() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Most recent tool calls, oldest first (empty unless history is enabled)
+    pub fn recent_invocations(&self) -> Vec<ToolInvocation> {
+        self.invocations.as_ref()
+            .map(|h| h.entries.lock().iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of server messages dropped by the inbound rate limit
+    pub fn dropped_messages(&self) -> u64 {
+        self.current_transport()
+            .map(|t| t.dropped_messages())
+            .unwrap_or_default()
+    }
+
+    /// How the server process ended, if it has (stdio servers only)
+    pub fn exit_status(&self) -> Option<ProcessExit> {
+        self.current_transport().ok()?.exit_status()
+    }
+
+    /// Request and tool call counters since the connection was created
+    pub fn metrics(&self) -> ServerMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Bytes exchanged over the current transport (counters restart on reconnect)
+    pub fn traffic(&self) -> TrafficStats {
+        self.current_transport()
+            .map(|t| t.traffic())
+            .unwrap_or_default()
+    }
+
+    /// Most recent lines the server wrote to stderr, oldest first
+    pub fn recent_stderr(&self) -> Vec<String> {
+        self.current_transport()
+            .map(|t| t.recent_stderr())
+            .unwrap_or_default()
+    }
+
+    /// Replace the transport with a fresh one and re-run initialization.
+    ///
+    /// Requests still waiting on the old transport fail with a transport
+    /// error. Correlation state lives in the transport, so a late reply from
+    /// the old server can never be matched to a request issued afterwards.
+    ///
+    /// With a reconnect limiter set, this first waits for the limiter to
+    /// admit the attempt.
+    pub async fn reconnect(&self) -> Result<ServerInfo, McpError> {
+        if let Some(limiter) = &self.reconnect_limiter {
+            limiter.acquire().await;
+        }
+        info!(server_id = %self.config.id, "Reconnecting MCP connection");
+        self.connected.store(false, Ordering::SeqCst);
+        
+        let old = self.transport.write().take();
+        if let Some(old) = old {
+            if let Err(e) = old.close().await {
+                warn!(server_id = %self.config.id, error = %e, "Error closing old transport");
+            }
+        }
+        
+        self.initialize().await
+    }
+
+    /// Shutdown the connection.
+    ///
+    /// A server process is first sent a `shutdown` request and an `exit`
+    /// notification so it can flush its state, and is only killed if it
+    /// hasn't exited once the shutdown grace period is over.
+    pub async fn shutdown(&self) -> Result<(), McpError> {
+        self.shut_down.store(true, Ordering::SeqCst);
+        self.connected.store(false, Ordering::SeqCst);
+        
+        let transport = self.transport.write().take();
+        if let Some(transport) = transport {
+            if transport.has_process() && !self.shutdown_grace.is_zero() {
+                self.ask_to_exit(transport.as_ref()).await;
+            }
+            transport.close().await?;
+        }
+        
+        info!(server_id = %self.config.id, "MCP connection shutdown");
+        Ok(())
+    }
+
+    /// Send `shutdown` and `exit`, then wait out the rest of the grace period for the process to exit
+    async fn ask_to_exit(&self, transport: &dyn McpTransport) {
+        let started = Instant::now();
+        let (_, wire_id) = self.next_request_id();
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": wire_id,
+            "method": "shutdown"
+        });
+        match tokio::time::timeout(self.shutdown_grace, transport.send_request(request)).await {
+            Ok(Ok(response)) if response.get("error").is_none() => {}
+            Ok(Ok(response)) => {
+                debug!(server_id = %self.config.id, error = %response["error"], "Server rejected shutdown request");
+            }
+            Ok(Err(e)) => debug!(server_id = %self.config.id, error = %e, "Shutdown request failed"),
+            Err(_) => debug!(server_id = %self.config.id, "Shutdown request not acknowledged"),
+        }
+        let _ = transport.send_notification(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "exit"
+        })).await;
+        
+        let remaining = self.shutdown_grace.saturating_sub(started.elapsed());
+        match transport.wait_for_exit(remaining).await {
+            Some(exit) => info!(server_id = %self.config.id, status = %exit, "Server exited gracefully"),
+            None => warn!(
+                server_id = %self.config.id,
+                grace = ?self.shutdown_grace,
+                "Server did not exit within the grace period, killing it"
+            ),
+        }
+    }
+
+    /// Take the next request id, returning it with its wire form
+    fn next_request_id(&self) -> (u64, serde_json::Value) {
+        let id = self.request_id.fetch_add(1, Ordering::SeqCst);
+        let wire_id = match &self.id_prefix {
+            Some(prefix) => serde_json::Value::String(format!("{}-{}", prefix, id)),
+            None => serde_json::Value::from(id),
+        };
+        (id, wire_id)
+    }
+
+    /// Send a JSON-RPC request
+    async fn send_request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, McpError> {
+        self.send_request_cancellable(method, params, None).await
+    }
+
+    /// Send a JSON-RPC request, giving up if `cancel` fires first.
+    ///
+    /// On cancellation the server is told via `notifications/cancelled`
+    /// and the request fails with `McpError::Cancelled`; a late response is
+    /// dropped by the transport.
+    async fn send_request_cancellable(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<serde_json::Value, McpError> {
+        let started = Instant::now();
+        let result = self.exchange(method, params, cancel).await;
+        if !matches!(result, Err(McpError::Cancelled)) {
+            self.metrics.record_request(started.elapsed(), result.is_ok());
+        }
+        result
+    }
+
+    /// Send one request and await its response
+    async fn exchange(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<serde_json::Value, McpError> {
+        let (id, wire_id) = self.next_request_id();
+        
+        let mut params = params;
+        let traceparent = self.trace_meta_key.as_ref().map(|key| {
+            let traceparent = crate::trace::outgoing_trace_parent();
+            attach_meta(&mut params, key, serde_json::Value::String(traceparent.clone()));
+            traceparent
+        });
+        
+        let mut request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": wire_id.clone(),
+            "method": method,
+            "params": params
+        });
+        self.strip_empty_params(&mut request);
+        
+        let span = tracing::debug_span!(
+            "mcp_request",
+            server_id = %self.config.id,
+            method = %method,
+            trace_id = traceparent.as_deref().and_then(crate::trace::trace_id).unwrap_or_default(),
+        );
+        
+        let transport = self.current_transport()?;
+        self.in_flight.lock().insert(id, (method.to_string(), Instant::now()));
+        let _in_flight = InFlightGuard { in_flight: &self.in_flight, id };
+        let mut abandoned = CancelOnDrop {
+            transport: transport.clone(),
+            request_id: wire_id.clone(),
+            armed: true,
+        };
+        // Dropping the exchange on timeout or cancel removes its pending
+        // entry, so a late response can't be matched to another request
+        let exchange = tokio::time::timeout(
+            self.request_timeout,
+            transport.send_request(request).instrument(span),
+        );
+        let response = match cancel {
+            None => exchange.await,
+            Some(cancel) => tokio::select! {
+                biased;
+                _ = cancel.cancelled() => {
+                    debug!(server_id = %self.config.id, method = %method, "Cancelling request");
+                    abandoned.disarm();
+                    self.send_cancellation(&wire_id, "Cancelled by client").await;
+                    return Err(McpError::Cancelled);
+                }
+                response = exchange => response,
+            },
+        };
+        abandoned.disarm();
+        let response = match response {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                if matches!(e, McpError::TransportError(_) | McpError::ProcessExited { .. }) {
+                    self.spawn_recovery(transport);
+                }
+                return Err(e);
+            }
+            Err(_) => {
+                warn!(
+                    server_id = %self.config.id,
+                    method = %method,
+                    timeout_ms = self.request_timeout.as_millis() as u64,
+                    "Request timed out"
+                );
+                self.send_cancellation(&wire_id, "Request timed out").await;
+                return Err(McpError::Timeout);
+            }
+        };
+        
+        // Check for JSON-RPC error
+        if let Some(error) = response.get("error") {
+            return Err(McpError::RpcError {
+                code: error["code"].as_i64().unwrap_or(-1),
+                message: error["message"].as_str().unwrap_or("Unknown error").to_string(),
+            });
+        }
+        
+        Ok(response["result"].clone())
+    }
+
+    /// Start reconnecting in the background per the reconnect policy after `broken` failed.
+    ///
+    /// Does nothing if `broken` was already replaced or recovery is already
+    /// running.
+    fn spawn_recovery(&self, broken: Arc<dyn McpTransport>) {
+        if self.reconnect_policy.is_none() {
+            return;
+        }
+        let Some(this) = self.this.get().and_then(std::sync::Weak::upgrade) else {
+            warn!(server_id = %self.config.id, "Not reconnecting, the connection wasn't made with into_shared");
+            return;
+        };
+        let current = self.transport.read().clone();
+        if !current.is_some_and(|current| Arc::ptr_eq(&current, &broken)) {
+            return;
+        }
+        if self.recovering.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tokio::spawn(async move {
+            this.recover().await;
+            this.recovering.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Reconnect until the policy gives up or the connection shuts down
+    async fn recover(&self) {
+        let Some(policy) = &self.reconnect_policy else {
+            return;
+        };
+        for attempt in 0..policy.max_retries {
+            tokio::time::sleep(policy.backoff(attempt)).await;
+            if self.shut_down.load(Ordering::SeqCst) {
+                return;
+            }
+            match self.reconnect().await {
+                Ok(_) => {
+                    info!(server_id = %self.config.id, attempt, "Reconnected after transport failure");
+                    let _ = self.events.send(ConnectionEvent::Reconnected { attempts: attempt + 1 });
+                    return;
+                }
+                Err(e) => warn!(server_id = %self.config.id, attempt, error = %e, "Reconnect failed"),
+            }
+        }
+        warn!(server_id = %self.config.id, "Giving up reconnecting");
+    }
+
+    /// Tell the server to stop working on an abandoned request; best effort
+    async fn send_cancellation(&self, wire_id: &serde_json::Value, reason: &str) {
+        let notice = serde_json::json!({
+            "requestId": wire_id,
+            "reason": reason
+        });
+        if let Err(e) = self.send_notification("notifications/cancelled", notice).await {
+            debug!(server_id = %self.config.id, error = %e, "Failed to send cancellation");
+        }
+    }
+
+    /// Send a JSON-RPC notification (no response expected)
+    async fn send_notification(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<(), McpError> {
+        let mut notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        });
+        self.strip_empty_params(&mut notification);
+        
+        let transport = self.current_transport()?;
+        transport.send_notification(notification).await
+    }
+
+    /// Drop `params` from an outgoing message if it is empty and configured to be omitted
+    fn strip_empty_params(&self, message: &mut serde_json::Value) {
+        let method = message["method"].as_str().unwrap_or_default();
+        if !self.omit_empty_params && !self.omit_empty_params_for.contains(method) {
+            return;
+        }
+        let empty = match &message["params"] {
+            serde_json::Value::Null => true,
+            serde_json::Value::Object(params) => params.is_empty(),
+            _ => false,
+        };
+        if let (true, Some(message)) = (empty, message.as_object_mut()) {
+            message.remove("params");
+        }
+    }
+
+    /// Get the active transport without holding the lock across a request
+    fn current_transport(&self) -> Result<Arc<dyn McpTransport>, McpError> {
+        self.transport.read()
+            .clone()
+            .ok_or(McpError::NotConnected)
+    }
+
+    /// Make `transport` current and forward its server-initiated messages
+    fn install_transport(&self, transport: Arc<dyn McpTransport>) {
+        let mut incoming = transport.subscribe();
+        let inbound = self.inbound.clone();
+        let server_id = self.config.id.clone();
+
+        spawn_on(self.transport_options.reader_pool.as_ref(), async move {
+            loop {
+                match incoming.recv().await {
+                    Ok(message) => {
+                        let _ = inbound.send(message);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(server_id = %server_id, skipped, "Dropped server messages, consumer too slow");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        *self.transport.write() = Some(transport);
+    }
+}
+
+/// Insert `key: value` into the `_meta` object of request params
+fn attach_meta(params: &mut serde_json::Value, key: &str, value: serde_json::Value) {
+    if params.is_null() {
+        *params = serde_json::json!({});
+    }
+    let Some(params) = params.as_object_mut() else {
+        return;
+    };
+    let meta = params.entry("_meta").or_insert_with(|| serde_json::json!({}));
+    if let Some(meta) = meta.as_object_mut() {
+        meta.insert(key.to_string(), value);
+    }
+}
+
+/// In-progress `tools/call` driven by a streaming call
+type ToolCall<'a> = std::pin::Pin<Box<dyn std::future::Future<Output = Result<serde_json::Value, McpError>> + Send + 'a>>;
+
+/// Next server message, or `None` once the channel is gone (pending forever if there is none)
+async fn next_message(
+    receiver: &mut Option<broadcast::Receiver<serde_json::Value>>,
+) -> Option<serde_json::Value> {
+    let Some(receiver) = receiver else {
+        return std::future::pending().await;
+    };
+    loop {
+        match receiver.recv().await {
+            Ok(message) => return Some(message),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Build a structured tool error, keeping the server's code if it sent one
+fn tool_failure(error: &serde_json::Value, result: &serde_json::Value) -> McpError {
+    let code = error["code"].as_i64()
+        .or_else(|| result["_meta"]["code"].as_i64());
+    let message = match error {
+        serde_json::Value::String(message) => message.clone(),
+        _ => error["message"].as_str()
+            .map(String::from)
+            .unwrap_or_else(|| error.to_string()),
+    };
+    
+    McpError::ToolFailed {
+        code,
+        message,
+        data: error.get("data").cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use crate::testing::{fake_server, initialize_result, reply, slow_server, test_config, FakeFactory};
+
+    #[derive(Debug, Deserialize)]
+    struct Sum {
+        total: i64,
+    }
+
+    /// Initialize result advertising resources with subscriptions
+    fn resources_initialize_result() -> serde_json::Value {
+        let mut result = initialize_result();
+        result["capabilities"]["resources"] = serde_json::json!({"subscribe": true});
+        result
+    }
+
+    fn calculator() -> Arc<dyn McpTransport> {
+        fake_server(|request| match request["params"]["name"].as_str() {
+            Some("add") => vec![reply(request, serde_json::json!({
+                "content": [{"type": "text", "text": "3"}],
+                "structuredContent": {"total": 3}
+            }))],
+            Some("echo") => vec![reply(request, serde_json::json!({
+                "content": [{"type": "text", "text": "hi"}]
+            }))],
+            _ => vec![],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_tools_follows_opaque_cursor() {
+        let transport = fake_server(|request| {
+            let tool = |name: &str| serde_json::json!({"name": name, "inputSchema": {"type": "object"}});
+            match request["params"].get("cursor") {
+                None => vec![reply(request, serde_json::json!({
+                    "tools": [tool("a")],
+                    "nextCursor": {"page": 2, "shard": "x"}
+                }))],
+                Some(cursor) if cursor == &serde_json::json!({"page": 2, "shard": "x"}) => {
+                    vec![reply(request, serde_json::json!({"tools": [tool("b")]}))]
+                }
+                Some(_) => vec![],
+            }
+        });
+        let connection = McpConnection::new(test_config("paged")).await.unwrap()
+            .with_transport(transport);
+
+        let tools = connection.list_tools().await.unwrap();
+        let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_tools_page_limit() {
+        let transport = fake_server(|request| vec![reply(request, serde_json::json!({
+            "tools": [],
+            "nextCursor": "same"
+        }))]);
+        let connection = McpConnection::new(test_config("loop")).await.unwrap()
+            .with_transport(transport)
+            .with_max_list_pages(3);
+
+        let result = connection.list_tools().await;
+        assert!(matches!(result, Err(McpError::ProtocolError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_fails_in_flight_request() {
+        // The first server never answers tool calls; the second answers everything
+        let factory = FakeFactory::new(|attempt: usize| {
+            fake_server(move |request| match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, initialize_result())],
+                Some("tools/call") if attempt == 0 => vec![],
+                Some("tools/call") => vec![reply(request, serde_json::json!({
+                    "content": [{"type": "text", "text": "fresh"}]
+                }))],
+                _ => vec![],
+            })
+        });
+        let connection = McpConnection::new(test_config("flaky")).await.unwrap()
+            .with_transport_factory(factory.clone());
+        connection.initialize().await.unwrap();
+
+        let reconnect = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            connection.reconnect().await
+        };
+        let (stale, reconnected) = tokio::join!(
+            connection.call_tool("slow", serde_json::json!({})),
+            reconnect
+        );
+
+        reconnected.unwrap();
+        assert!(matches!(stale, Err(McpError::TransportError(_))));
+        assert_eq!(factory.created(), 2);
+
+        let fresh = connection.call_tool("slow", serde_json::json!({})).await.unwrap();
+        assert_eq!(fresh, [ToolContent::Text { text: "fresh".into() }]);
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_after_transport_breaks() {
+        use crate::fault::{Fault, FaultyTransport};
+
+        // The first server disconnects on the request after initialize
+        let factory = FakeFactory::new(|attempt: usize| {
+            let server = fake_server(|request| match request.get("id") {
+                Some(_) => vec![reply(request, initialize_result())],
+                None => vec![],
+            });
+            if attempt > 0 {
+                return server;
+            }
+            let faulty = FaultyTransport::new(server);
+            faulty.push(Fault::Delay(Duration::ZERO));
+            faulty.disconnect_next();
+            Arc::new(faulty) as Arc<dyn McpTransport>
+        });
+        let policy = ReconnectPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(10),
+            jitter: false,
+        };
+        let connection = McpConnection::new(test_config("crashy")).await.unwrap()
+            .with_transport_factory(factory.clone())
+            .with_reconnect_policy(policy)
+            .into_shared();
+        connection.initialize().await.unwrap();
+        let mut events = connection.events();
+        let mut inbound = connection.subscribe();
+
+        let err = connection.ping().await.unwrap_err();
+        assert!(matches!(err, McpError::TransportError(_)));
+        assert_eq!(factory.created(), 1);
+        let event = tokio::time::timeout(Duration::from_secs(1), events.recv()).await.unwrap().unwrap();
+        assert_eq!(event, ConnectionEvent::Reconnected { attempts: 1 });
+        assert_eq!(factory.created(), 2);
+        assert!(inbound.try_recv().is_err());
+        connection.ping().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_sandbox_replay_fails_initialize() {
+        /// Rejects sandbox notifications, passes everything else through
+        struct RejectsSandbox(Arc<dyn McpTransport>);
+
+        #[async_trait::async_trait]
+        impl McpTransport for RejectsSandbox {
+            async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+                self.0.send_request(request).await
+            }
+
+            async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
+                if notification["method"] == "notifications/sandbox_state" {
+                    return Err(McpError::TransportError("rejected".into()));
+                }
+                self.0.send_notification(notification).await
+            }
+
+            async fn close(&self) -> Result<(), McpError> {
+                self.0.close().await
+            }
+        }
+
+        let server = fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            _ => vec![],
+        });
+        let connection = McpConnection::new(test_config("sandboxed")).await.unwrap()
+            .with_transport(Arc::new(RejectsSandbox(server)));
+        assert!(connection.notify_sandbox_state(true, "read-only").await.is_err());
+
+        assert!(connection.initialize().await.is_err());
+        assert!(!connection.is_connected());
+        assert!(connection.server_info().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_state_replayed_on_reconnect() {
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let factory = {
+            let seen = seen.clone();
+            FakeFactory::new(move |attempt: usize| {
+                let seen = seen.clone();
+                fake_server(move |request| match request["method"].as_str() {
+                    Some("initialize") => vec![reply(request, initialize_result())],
+                    Some("ping") => vec![reply(request, serde_json::json!({}))],
+                    Some("notifications/sandbox_state") => {
+                        seen.lock().push((attempt, request["params"]["policy"].clone()));
+                        vec![]
+                    }
+                    _ => vec![],
+                })
+            })
+        };
+        let connection = McpConnection::new(test_config("sandboxed")).await.unwrap()
+            .with_transport_factory(factory);
+        connection.initialize().await.unwrap();
+        connection.notify_sandbox_state(true, "workspace-write").await.unwrap();
+
+        connection.reconnect().await.unwrap();
+        // The server handles messages in order, so the replay has been seen once ping returns
+        connection.ping().await.unwrap();
+
+        assert_eq!(*seen.lock(), [
+            (0, serde_json::json!("workspace-write")),
+            (1, serde_json::json!("workspace-write")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_requests() {
+        // Never answer tool calls
+        let transport = fake_server(|request| match request["method"].as_str() {
+            Some("tools/call") => vec![],
+            _ => vec![reply(request, serde_json::json!({}))],
+        });
+        let connection = McpConnection::new(test_config("busy")).await.unwrap()
+            .with_transport(transport);
+
+        let call = connection.call_tool("slow", serde_json::json!({}));
+        let observe = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            connection.ping().await.unwrap();
+            connection.in_flight_requests()
+        };
+        let in_flight = tokio::select! {
+            _ = call => panic!("tool call should not complete"),
+            in_flight = observe => in_flight,
+        };
+
+        let methods: Vec<_> = in_flight.iter().map(|r| r.method.as_str()).collect();
+        assert_eq!(methods, ["tools/call"]);
+        assert!(connection.in_flight_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tool_error_keeps_custom_code() {
+        let transport = fake_server(|request| {
+            let result = match request["params"]["name"].as_str() {
+                Some("quota") => serde_json::json!({
+                    "error": {"code": 4029, "message": "quota exceeded", "data": {"retryAfter": 30}}
+                }),
+                _ => serde_json::json!({
+                    "error": "not allowed",
+                    "_meta": {"code": 4030}
+                }),
+            };
+            vec![reply(request, result)]
+        });
+        let connection = McpConnection::new(test_config("coded")).await.unwrap()
+            .with_transport(transport);
+
+        let err = connection.call_tool("quota", serde_json::json!({})).await.unwrap_err();
+        assert_eq!(err.code(), Some(4029));
+        assert!(matches!(
+            err,
+            McpError::ToolFailed { ref message, ref data, .. }
+                if message == "quota exceeded" && data.as_ref().unwrap()["retryAfter"] == 30
+        ));
+
+        let err = connection.call_tool("denied", serde_json::json!({})).await.unwrap_err();
+        assert_eq!(err.code(), Some(4030));
+        assert_eq!(err.to_string(), "Tool error 4030: not allowed");
+    }
+
+    #[tokio::test]
+    async fn test_pinned_protocol_version() {
+        // Echo back whatever version was requested, except for one legacy date
+        let server = || fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => {
+                let requested = request["params"]["protocolVersion"].as_str().unwrap();
+                let version = if requested == "2024-10-07" { "2024-11-05" } else { requested };
+                vec![reply(request, serde_json::json!({"name": "v", "protocolVersion": version}))]
+            }
+            _ => vec![],
+        });
+
+        let connection = McpConnection::new(test_config("pinned")).await.unwrap()
+            .with_protocol_version("2025-03-26")
+            .with_transport(server());
+        assert_eq!(connection.initialize().await.unwrap().protocol_version, "2025-03-26");
+
+        let connection = McpConnection::new(test_config("refused")).await.unwrap()
+            .with_protocol_version("2024-10-07")
+            .with_transport(server());
+        assert!(matches!(connection.initialize().await, Err(McpError::ProtocolError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_with_progress() {
+        use futures::StreamExt;
+
+        let transport = fake_server(|request| {
+            let token = request["params"]["_meta"]["progressToken"].clone();
+            let progress = |done: u64| serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": {"progressToken": token, "progress": done, "total": 2}
+            });
+            vec![
+                progress(1),
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/progress",
+                    "params": {"progressToken": "someone-else", "progress": 9}
+                }),
+                progress(2),
+                reply(request, serde_json::json!({"content": [{"type": "text", "text": "done"}]})),
+            ]
+        });
+        let connection = McpConnection::new(test_config("slowpoke")).await.unwrap()
+            .with_transport(transport);
+
+        let (progress, call) = connection.call_tool_with_progress("index", serde_json::json!({}));
+        let result = call.await.unwrap();
+        assert_eq!(result.text(), "done");
+
+        let fractions: Vec<_> = progress.map(|p| p.fraction()).collect().await;
+        assert_eq!(fractions, [Some(0.5), Some(1.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_streaming_partial_content() {
+        use futures::StreamExt;
+
+        let transport = fake_server(|request| {
+            let token = request["params"]["_meta"]["progressToken"].clone();
+            let partial = |text: &str| serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": PARTIAL_CONTENT_METHOD,
+                "params": {"progressToken": token, "content": [{"type": "text", "text": text}]}
+            });
+            match request["params"]["name"].as_str() {
+                Some("stream") => vec![
+                    partial("Hello, "),
+                    partial("world"),
+                    reply(request, serde_json::json!({"content": []})),
+                ],
+                _ => vec![reply(request, serde_json::json!({
+                    "content": [{"type": "text", "text": "Hello, world"}]
+                }))],
+            }
+        });
+        let connection = McpConnection::new(test_config("gen")).await.unwrap()
+            .with_transport(transport);
+
+        let chunks: Vec<ToolOutputChunk> = connection
+            .call_tool_streaming("stream", serde_json::json!({}))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 3);
+        assert!(matches!(&chunks[0], ToolOutputChunk::Partial(blocks) if blocks[0]["text"] == "Hello, "));
+        let ToolOutputChunk::Final(result) = &chunks[2] else {
+            panic!("expected the final result last");
+        };
+        assert_eq!(result.text(), "Hello, world");
+
+        let chunks: Vec<ToolOutputChunk> = connection
+            .call_tool_streaming("plain", serde_json::json!({}))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert!(matches!(&chunks[..], [ToolOutputChunk::Final(result)] if result.content.len() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_streaming_text() {
+        use futures::StreamExt;
+
+        let transport = fake_server(|request| {
+            let token = request["params"]["_meta"]["progressToken"].clone();
+            let progress = |text: &str| serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": {"progressToken": token, "progress": 1, "message": text}
+            });
+            let done = reply(request, serde_json::json!({
+                "content": [{"type": "text", "text": "Hello, world"}]
+            }));
+            let failed = reply(request, serde_json::json!({
+                "content": [{"type": "text", "text": "boom"}],
+                "isError": true
+            }));
+            match request["params"]["name"].as_str() {
+                Some("stream") => vec![progress("Hello, "), progress("world"), done],
+                Some("broken") => vec![progress("partial"), failed],
+                Some("fails") => vec![failed],
+                _ => vec![done],
+            }
+        });
+        let connection = McpConnection::new(test_config("gen")).await.unwrap()
+            .with_transport(transport);
+
+        let chunks: Vec<String> = connection
+            .call_tool_streaming_text("stream", serde_json::json!({}))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(chunks, ["Hello, ", "world"]);
+
+        let chunks: Vec<String> = connection
+            .call_tool_streaming_text("plain", serde_json::json!({}))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(chunks, ["Hello, world"]);
+
+        let chunks: Vec<_> = connection.call_tool_streaming_text("broken", serde_json::json!({})).collect().await;
+        assert!(matches!(&chunks[..], [Ok(text), Err(McpError::ToolError(error))] if text == "partial" && error == "boom"));
+
+        let chunks: Vec<_> = connection.call_tool_streaming_text("fails", serde_json::json!({})).collect().await;
+        assert!(matches!(&chunks[..], [Err(McpError::ToolError(error))] if error == "boom"));
+    }
+
+    #[tokio::test]
+    async fn test_result_size_limit_truncates() {
+        let transport = fake_server(|request| vec![reply(request, serde_json::json!({
+            "content": [{"type": "text", "text": "a".repeat(10_000)}]
+        }))]);
+        let connection = McpConnection::new(test_config("verbose")).await.unwrap()
+            .with_max_result_size(1024)
+            .with_transport(transport);
+
+        let result = connection.call_tool_result("dump", serde_json::json!({})).await.unwrap();
+        assert!(result.truncated);
+        assert!(result.original_size.unwrap() > 10_000);
+        assert!(result.text().len() <= 1024);
+    }
+
+    #[tokio::test]
+    async fn test_empty_params_omitted() {
+        // Report whether each message carried params
+        let (seen_tx, mut seen_rx) = tokio::sync::mpsc::unbounded_channel();
+        let server = move || {
+            let seen_tx = seen_tx.clone();
+            fake_server(move |request| {
+                let _ = seen_tx.send((request["method"].clone(), request.get("params").is_some()));
+                match request.get("id") {
+                    Some(_) => vec![reply(request, serde_json::json!({}))],
+                    None => vec![],
+                }
+            })
+        };
+
+        let connection = McpConnection::new(test_config("strict")).await.unwrap()
+            .with_empty_params_omitted_for("ping")
+            .with_transport(server());
+        connection.ping().await.unwrap();
+        connection.notify("notifications/initialized", serde_json::json!({})).await.unwrap();
+        connection.request("custom/echo", serde_json::json!({"a": 1})).await.unwrap();
+        assert_eq!(seen_rx.recv().await.unwrap(), (serde_json::json!("ping"), false));
+        assert_eq!(seen_rx.recv().await.unwrap(), (serde_json::json!("notifications/initialized"), true));
+        assert_eq!(seen_rx.recv().await.unwrap(), (serde_json::json!("custom/echo"), true));
+
+        let connection = McpConnection::new(test_config("stricter")).await.unwrap()
+            .with_empty_params_omitted()
+            .with_transport(server());
+        connection.notify("notifications/initialized", serde_json::json!({})).await.unwrap();
+        connection.ping().await.unwrap();
+        assert_eq!(seen_rx.recv().await.unwrap(), (serde_json::json!("notifications/initialized"), false));
+        assert_eq!(seen_rx.recv().await.unwrap(), (serde_json::json!("ping"), false));
+    }
+
+    #[tokio::test]
+    async fn test_prompts_list_and_get() {
+        let serving = |capabilities: serde_json::Value| FakeFactory::new(move |_: usize| {
+            let capabilities = capabilities.clone();
+            fake_server(move |request| match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, serde_json::json!({
+                    "name": "fake",
+                    "version": "1.0.0",
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": capabilities
+                }))],
+                Some("prompts/list") => vec![reply(request, serde_json::json!({"prompts": [{
+                    "name": "review",
+                    "description": "Review a diff",
+                    "arguments": [{"name": "diff", "required": true}]
+                }]}))],
+                Some("prompts/get") => vec![reply(request, serde_json::json!({"messages": [{
+                    "role": "user",
+                    "content": {"type": "text", "text": format!("Review {}", request["params"]["arguments"]["diff"].as_str().unwrap_or_default())}
+                }]}))],
+                _ => vec![],
+            })
+        });
+
+        let connection = McpConnection::new(test_config("prompts")).await.unwrap()
+            .with_transport_factory(serving(serde_json::json!({"prompts": {}})));
+        connection.initialize().await.unwrap();
+        let prompts = connection.list_prompts().await.unwrap();
+        assert_eq!(prompts[0].name, "review");
+        assert!(prompts[0].arguments[0].required);
+        let arguments = HashMap::from([("diff".to_string(), "a.rs".to_string())]);
+        let rendered = connection.get_prompt("review", arguments).await.unwrap();
+        assert_eq!(rendered.messages[0].role, "user");
+        assert_eq!(rendered.messages[0].content["text"], "Review a.rs");
+
+        let connection = McpConnection::new(test_config("tools-only")).await.unwrap()
+            .with_transport_factory(serving(serde_json::json!({"tools": {}})));
+        connection.initialize().await.unwrap();
+        let err = connection.list_prompts().await.unwrap_err();
+        assert!(matches!(err, McpError::Unsupported { capability: "prompts", .. }));
+    }
+
+    #[tokio::test]
+    async fn test_probe_methods() {
+        let transport = fake_server(|request| match request["method"].as_str() {
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            Some("ping") => vec![reply(request, serde_json::json!({}))],
+            Some("prompts/list") => vec![serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32603, "message": "broken"}
+            })],
+            _ => vec![serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32601, "message": "Method not found"}
+            })],
+        });
+        let connection = McpConnection::new(test_config("shy")).await.unwrap()
+            .with_transport(transport);
+
+        let probes = connection.probe_methods().await;
+        assert_eq!(probes.len(), 4);
+        assert!(probes["tools/list"].is_supported());
+        assert!(probes["ping"].is_supported());
+        assert_eq!(probes["resources/list"], ProbeOutcome::NotFound);
+        assert!(matches!(probes["prompts/list"], ProbeOutcome::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_slow_resource_read() {
+        // Never answer reads; report cancellations back on a channel
+        let (cancelled_tx, mut cancelled_rx) = tokio::sync::mpsc::unbounded_channel();
+        let transport = fake_server(move |request| {
+            match request["method"].as_str() {
+                Some("notifications/cancelled") => {
+                    let _ = cancelled_tx.send(request["params"]["requestId"].clone());
+                }
+                Some("resources/read") => {}
+                _ => return vec![reply(request, serde_json::json!({}))],
+            }
+            vec![]
+        });
+        let connection = McpConnection::new(test_config("slow")).await.unwrap()
+            .with_transport(transport);
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+        let err = connection.read_resource_cancellable("file:///big", &cancel).await.unwrap_err();
+        assert!(matches!(err, McpError::Cancelled));
+        assert_eq!(cancelled_rx.recv().await.unwrap(), 0);
+        assert!(connection.in_flight_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_is_cancelled() {
+        let (cancelled_tx, mut cancelled_rx) = tokio::sync::mpsc::unbounded_channel();
+        let transport = fake_server(move |request| {
+            if request["method"] == "notifications/cancelled" {
+                let _ = cancelled_tx.send(request["params"].clone());
+            }
+            vec![]
+        });
+        let connection = McpConnection::new(test_config("slow")).await.unwrap()
+            .with_transport(transport);
+
+        // The outer timeout drops the call future mid-flight
+        let call = connection.call_tool("wait", serde_json::json!({}));
+        assert!(tokio::time::timeout(Duration::from_millis(50), call).await.is_err());
+
+        let params = cancelled_rx.recv().await.unwrap();
+        assert_eq!(params["requestId"], 0);
+        assert_eq!(params["reason"], "Request dropped by client");
+        assert!(connection.in_flight_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_discards_late_reply() {
+        let (cancelled_tx, mut cancelled_rx) = tokio::sync::mpsc::unbounded_channel();
+        let transport = slow_server(Some("tools/call"), Duration::from_millis(200), move |request| {
+            match request["method"].as_str() {
+                Some("notifications/cancelled") => {
+                    let _ = cancelled_tx.send(request["params"]["reason"].clone());
+                    vec![]
+                }
+                Some("tools/call") => vec![reply(request, serde_json::json!({"content": [{"type": "text", "text": "late"}]}))],
+                _ => vec![reply(request, serde_json::json!({"pong": true}))],
+            }
+        });
+        let connection = McpConnection::new(test_config("slow")).await.unwrap()
+            .with_transport(transport)
+            .with_request_timeout(Duration::from_millis(50));
+
+        let err = connection.call_tool("wait", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Timeout));
+        assert!(connection.in_flight_requests().is_empty());
+        assert_eq!(cancelled_rx.recv().await.unwrap(), "Request timed out");
+
+        // The late tools/call reply arrives first and must not answer this request
+        let pong = connection.send_request("ping", serde_json::json!({})).await.unwrap();
+        assert_eq!(pong, serde_json::json!({"pong": true}));
+    }
+
+    #[tokio::test]
+    async fn test_is_error_result_fails_call_tool() {
+        let transport = fake_server(|request| vec![reply(request, serde_json::json!({
+            "content": [{"type": "text", "text": "disk "}, {"type": "text", "text": "full"}],
+            "isError": true
+        }))]);
+        let connection = McpConnection::new(test_config("failing")).await.unwrap()
+            .with_transport(transport);
+
+        let err = connection.call_tool("write", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::ToolError(ref m) if m == "disk full"));
+        assert!(err.is_tool_error());
+
+        let result = connection.call_tool_result("write", serde_json::json!({})).await.unwrap();
+        assert!(result.is_error);
+        assert_eq!(result.text(), "disk full");
+
+        let err = connection.call_tool_as::<Sum>("write", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::ToolError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_empty_tool_result_is_protocol_error() {
+        let transport = fake_server(|request| match request["params"]["name"].as_str() {
+            Some("empty") => vec![reply(request, serde_json::json!({}))],
+            _ => vec![reply(request, serde_json::json!({"content": []}))],
+        });
+        let connection = McpConnection::new(test_config("sloppy")).await.unwrap()
+            .with_transport(transport);
+
+        let err = connection.call_tool("empty", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::ProtocolError(ref m) if m == "tool result missing content"));
+
+        let result = connection.call_tool("nothing", serde_json::json!({})).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_argument_size_limit_rejects_before_sending() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let seen = calls.clone();
+        let transport = fake_server(move |request| {
+            seen.fetch_add(1, Ordering::SeqCst);
+            vec![reply(request, serde_json::json!({"content": []}))]
+        });
+        let connection = McpConnection::new(test_config("strict")).await.unwrap()
+            .with_max_argument_size(64)
+            .with_transport(transport);
+
+        let err = connection
+            .call_tool_result("upload", serde_json::json!({"blob": "a".repeat(1000)}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McpError::InvalidArguments { .. }));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        connection.call_tool_result("upload", serde_json::json!({"blob": "a"})).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_invocation_history() {
+        let transport = fake_server(|request| match request["params"]["name"].as_str() {
+            Some("fail") => vec![serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32000, "message": "nope"}
+            })],
+            _ => vec![reply(request, serde_json::json!({"content": []}))],
+        });
+        let connection = McpConnection::new(test_config("audit")).await.unwrap()
+            .with_invocation_history(2)
+            .with_transport(transport);
+
+        connection.call_tool("first", serde_json::json!({})).await.unwrap();
+        connection.call_tool("second", serde_json::json!({"k": "v"})).await.unwrap();
+        connection.call_tool("fail", serde_json::json!({})).await.unwrap_err();
+
+        let history = connection.recent_invocations();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].tool, "second");
+        assert_eq!(history[0].argument_size, br#"{"k":"v"}"#.len());
+        assert!(history[0].is_success());
+        assert_eq!(history[1].tool, "fail");
+        assert!(history[1].error.as_deref().unwrap().contains("nope"));
+
+        let untracked = McpConnection::new(test_config("quiet")).await.unwrap();
+        assert!(untracked.recent_invocations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_id_prefix() {
+        let transport = fake_server(|request| vec![reply(request, serde_json::json!({
+            "content": [{"type": "text", "text": request["id"]}]
+        }))]);
+        let connection = McpConnection::new(test_config("mux")).await.unwrap()
+            .with_id_prefix("hostA")
+            .with_transport(transport);
+
+        let first = connection.call_tool("whoami", serde_json::json!({})).await.unwrap();
+        let second = connection.call_tool("whoami", serde_json::json!({})).await.unwrap();
+        assert_eq!(first, [ToolContent::Text { text: "hostA-0".into() }]);
+        assert_eq!(second, [ToolContent::Text { text: "hostA-1".into() }]);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_without_name() {
+        let transport = fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, serde_json::json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {}
+            }))],
+            _ => vec![],
+        });
+        let connection = McpConnection::new(test_config("nameless")).await.unwrap()
+            .with_transport(transport);
+
+        let info = connection.initialize().await.unwrap();
+        assert_eq!(info.name, "nameless");
+    }
+
+    #[tokio::test]
+    async fn test_trace_parent_in_meta() {
+        // Echo the request's _meta back as the tool result
+        let transport = fake_server(|request| vec![reply(request, serde_json::json!({
+            "content": [request["params"]["_meta"].clone()]
+        }))]);
+        let connection = McpConnection::new(test_config("traced")).await.unwrap()
+            .with_transport(transport)
+            .with_trace_propagation(crate::trace::DEFAULT_TRACE_META_KEY);
+
+        let parent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let meta = crate::trace::with_trace_parent(
+            parent,
+            connection.call_tool_result("echo", serde_json::json!({})),
+        ).await.unwrap();
+
+        let sent = meta.content[0]["traceparent"].as_str().unwrap();
+        assert_eq!(crate::trace::trace_id(sent), Some("0af7651916cd43dd8448eb211c80319c"));
+    }
+
+    #[tokio::test]
+    async fn test_unadvertised_capabilities_fail_early() {
+        let (sent_tx, mut sent_rx) = tokio::sync::mpsc::unbounded_channel();
+        let transport = fake_server(move |request| {
+            let method = request["method"].as_str().unwrap_or_default();
+            let _ = sent_tx.send(method.to_string());
+            match method {
+                "initialize" => vec![reply(request, initialize_result())],
+                "resources/list" => vec![reply(request, serde_json::json!({"resources": []}))],
+                _ => vec![],
+            }
+        });
+        let connection = McpConnection::new(test_config("tools-only")).await.unwrap()
+            .with_transport(transport);
+        // Nothing was advertised yet
+        let err = connection.list_resources().await.unwrap_err();
+        assert!(matches!(err, McpError::NotConnected));
+
+        let info = connection.initialize().await.unwrap();
+        assert!(info.supports("tools"));
+        assert!(!info.supports("resources") && !info.supports("logging"));
+
+        let err = connection.list_resources().await.unwrap_err();
+        assert!(matches!(err, McpError::Unsupported { capability: "resources", .. }));
+        let err = connection.subscribe_resource("file:///x").await.unwrap_err();
+        assert!(matches!(err, McpError::Unsupported { capability: "resource subscriptions", .. }));
+
+        // `request` bypasses the guard
+        connection.request("resources/list", serde_json::json!({})).await.unwrap();
+        let sent: Vec<_> = std::iter::from_fn(|| sent_rx.try_recv().ok()).collect();
+        assert_eq!(sent, ["initialize", "notifications/initialized", "resources/list"]);
+    }
+
+    #[tokio::test]
+    async fn test_resource_cache_invalidated_by_update() {
+        let mut version = 1;
+        let transport = fake_server(move |request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, resources_initialize_result())],
+            Some("resources/read") => {
+                let contents = serde_json::json!({"contents": [{
+                    "uri": "file:///config",
+                    "text": format!("v{}", version)
+                }]});
+                version += 1;
+                vec![reply(request, contents)]
+            }
+            Some("resources/subscribe") => vec![
+                reply(request, serde_json::json!({})),
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/resources/updated",
+                    "params": {"uri": "file:///config"}
+                }),
+            ],
+            _ => vec![],
+        });
+        let connection = McpConnection::new(test_config("res")).await.unwrap()
+            .with_resource_cache(Duration::from_secs(60))
+            .with_transport(transport);
+        connection.initialize().await.unwrap();
+
+        let text = |contents: Vec<ResourceContents>| contents[0].text.clone().unwrap();
+        assert_eq!(text(connection.read_resource("file:///config").await.unwrap()), "v1");
+        assert_eq!(text(connection.read_resource("file:///config").await.unwrap()), "v1");
+
+        connection.subscribe_resource("file:///config").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(text(connection.read_resource("file:///config").await.unwrap()), "v2");
+    }
+
+    #[tokio::test]
+    async fn test_resource_cache_takes_pushed_contents() {
+        let reads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = reads.clone();
+        let transport = fake_server(move |request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, resources_initialize_result())],
+            Some("resources/read") => {
+                counted.fetch_add(1, Ordering::SeqCst);
+                vec![reply(request, serde_json::json!({"contents": [{
+                    "uri": "file:///config",
+                    "text": "v1"
+                }]}))]
+            }
+            Some("resources/subscribe") => vec![
+                reply(request, serde_json::json!({})),
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/resources/updated",
+                    "params": {
+                        "uri": "file:///config",
+                        "contents": [{"uri": "file:///config", "text": "pushed"}]
+                    }
+                }),
+            ],
+            _ => vec![],
+        });
+        let connection = McpConnection::new(test_config("res")).await.unwrap()
+            .with_resource_cache(Duration::from_secs(60))
+            .with_transport(transport);
+        connection.initialize().await.unwrap();
+
+        let text = |contents: Vec<ResourceContents>| contents[0].text.clone().unwrap();
+        assert_eq!(text(connection.read_resource("file:///config").await.unwrap()), "v1");
+
+        connection.subscribe_resource("file:///config").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(text(connection.read_resource("file:///config").await.unwrap()), "pushed");
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_initialized_notification_failure() {
+        // Answer initialize with stdin already closed, so the notification hits a broken pipe
+        let mut config = test_config("early-exit");
+        config.transport = warhorn::McpTransport::Stdio {
+            command: "sh".into(),
+            args: vec![
+                "-c".into(),
+                r#"read line; exec 0<&-; printf '{"jsonrpc":"2.0","id":0,"result":{"name":"gone"}}\n'; sleep 5"#.into(),
+            ],
+        };
+        let connection = McpConnection::new(config).await.unwrap();
+
+        let err = connection.initialize().await.unwrap_err();
+        assert!(err.to_string().contains("notifications/initialized"), "{}", err);
+        assert!(err.is_transport());
+        assert!(!connection.is_connected());
+        assert!(connection.server_info().await.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shutdown_lets_server_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let flushed = dir.path().join("flushed");
+        // Acknowledge shutdown, then write state and exit on the exit notification
+        let script = format!(
+            r#"read line; printf '{{"jsonrpc":"2.0","id":0,"result":{{"name":"tidy"}}}}\n'
+            while read line; do
+                case "$line" in
+                    *'"method":"shutdown"'*)
+                        id=$(echo "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+                        printf '{{"jsonrpc":"2.0","id":%s,"result":{{}}}}\n' "$id" ;;
+                    *'"method":"exit"'*) echo done > {}; exit 0 ;;
+                esac
+            done"#,
+            flushed.display()
+        );
+        let mut config = test_config("tidy");
+        config.transport = warhorn::McpTransport::Stdio {
+            command: "sh".into(),
+            args: vec!["-c".into(), script],
+        };
+        let connection = McpConnection::new(config).await.unwrap()
+            .with_shutdown_grace(Duration::from_secs(10));
+        connection.initialize().await.unwrap();
+
+        let started = Instant::now();
+        connection.shutdown().await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(std::fs::read_to_string(&flushed).unwrap().trim(), "done");
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_as_structured() {
+        let connection = McpConnection::new(test_config("calc")).await.unwrap()
+            .with_transport(calculator());
+
+        let sum: Sum = connection.call_tool_as("add", serde_json::json!({})).await.unwrap();
+        assert_eq!(sum.total, 3);
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_as_text_only() {
+        let connection = McpConnection::new(test_config("calc")).await.unwrap()
+            .with_transport(calculator());
+
+        let result = connection.call_tool_as::<Sum>("echo", serde_json::json!({})).await;
+        assert!(matches!(result, Err(McpError::MissingStructuredContent(_))));
+    }
 }
@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::types::{ConnectPhase, ProcessExit};
+
 /// Errors that can occur in MCP operations
 #[derive(Debug, Error)]
 pub enum McpError {
@@ -32,11 +34,244 @@ pub enum McpError {
     #[error("Tool error: {0}")]
     ToolError(String),
 
+    /// Tool call failed with a structured error from the server.
+    ///
+    /// `code` is the server's machine-readable error code, taken from the
+    /// error object or the result's `_meta.code`, for hosts to switch on.
+    #[error("Tool error{}: {message}", .code.map(|c| format!(" {}", c)).unwrap_or_default())]
+    ToolFailed {
+        code: Option<i64>,
+        message: String,
+        data: Option<serde_json::Value>,
+    },
+
+    /// Tool result has no `structuredContent` to deserialize
+    #[error("Tool returned no structured content: {0}")]
+    MissingStructuredContent(String),
+
+    /// Result could not be deserialized into the requested type
+    #[error("Failed to deserialize result: {0}")]
+    Deserialize(String),
+
+    /// Tool not exposed by the server
+    #[error("Tool not found: {0}")]
+    ToolNotFound(String),
+
+    /// A plain tool name matched tools on more than one server
+    #[error("Tool {name} is exposed by several servers: {}", .servers.join(", "))]
+    AmbiguousTool {
+        name: String,
+        servers: Vec<String>,
+    },
+
+    /// Tool blocked by the server's tool policy
+    #[error("Tool {tool} is not allowed on server {server_id}")]
+    ToolNotAllowed {
+        server_id: String,
+        tool: String,
+    },
+
+    /// The server didn't advertise the capability an operation needs
+    #[error("Server {server_id} does not support {capability}")]
+    Unsupported {
+        server_id: String,
+        capability: &'static str,
+    },
+
+    /// Tool arguments failed schema validation
+    #[error("Invalid arguments: {}", .errors.join("; "))]
+    InvalidArguments {
+        errors: Vec<String>,
+    },
+
+    /// Server is known to be unhealthy or disconnected
+    #[error("Server unhealthy: {0}")]
+    ServerUnhealthy(String),
+
+    /// Every failover candidate was skipped or failed, as (server_id, reason)
+    #[error("All failover candidates failed: {}", .attempts
+        .iter()
+        .map(|(server_id, reason)| format!("{}: {}", server_id, reason))
+        .collect::<Vec<_>>()
+        .join("; "))]
+    FailoverExhausted {
+        attempts: Vec<(String, String)>,
+    },
+
+    /// `connect` failed; the partially set up connection was shut down
+    #[error("Connect to {server_id} failed during {phase}: {source}")]
+    ConnectFailed {
+        server_id: String,
+        phase: ConnectPhase,
+        #[source]
+        source: Box<McpError>,
+    },
+
+    /// The server process exited; `stderr` holds its last few lines
+    #[error("Server process exited with {status}{}", .stderr
+        .last()
+        .map(|line| format!(": {}", line))
+        .unwrap_or_default())]
+    ProcessExited {
+        status: ProcessExit,
+        stderr: Vec<String>,
+    },
+
+    /// Server config file is invalid
+    #[error("Config error: {0}")]
+    ConfigError(String),
+
     /// Connection timeout
     #[error("Connection timeout")]
     Timeout,
 
+    /// Operation was cancelled before it completed
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    /// Starting the operation would exceed the manager's background task budget
+    #[error("Background task budget of {limit} exhausted")]
+    TaskBudgetExhausted { limit: usize },
+
     /// IO error
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
+
+impl McpError {
+    /// Whether retrying the same operation may succeed.
+    ///
+    /// Transport failures, timeouts and degraded servers are transient:
+    /// a reconnect or a later attempt can succeed. Protocol violations, RPC
+    /// and tool errors, and bad arguments will fail the same way again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            McpError::ConnectFailed { source, .. } => source.is_retryable(),
+            other => matches!(
+                other,
+                McpError::NotConnected
+                    | McpError::TransportError(_)
+                    | McpError::ProcessExited { .. }
+                    | McpError::ServerUnhealthy(_)
+                    | McpError::Timeout
+                    | McpError::IoError(_)
+            ),
+        }
+    }
+
+    /// The connection to the server failed or is unavailable
+    pub fn is_transport(&self) -> bool {
+        match self {
+            McpError::ConnectFailed { source, .. } => source.is_transport(),
+            other => matches!(
+                other,
+                McpError::NotConnected
+                    | McpError::TransportError(_)
+                    | McpError::ProcessExited { .. }
+                    | McpError::Timeout
+                    | McpError::IoError(_)
+            ),
+        }
+    }
+
+    /// The server sent something we couldn't understand or rejected a request
+    pub fn is_protocol(&self) -> bool {
+        matches!(
+            self,
+            McpError::ProtocolError(_) | McpError::RpcError { .. }
+        )
+    }
+
+    /// The tool ran (or was looked up) and failed on the server's side
+    pub fn is_tool_error(&self) -> bool {
+        matches!(
+            self,
+            McpError::ToolError(_)
+                | McpError::ToolFailed { .. }
+                | McpError::ToolNotFound(_)
+                | McpError::MissingStructuredContent(_)
+        )
+    }
+
+    /// Numeric error code reported by the server, if any
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            McpError::RpcError { code, .. } => Some(*code),
+            McpError::ToolFailed { code, .. } => *code,
+            _ => None,
+        }
+    }
+
+    /// The caller supplied something invalid; fix the request, don't retry
+    pub fn is_caller_error(&self) -> bool {
+        matches!(
+            self,
+            McpError::ServerNotFound(_)
+                | McpError::ConfigError(_)
+                | McpError::InvalidArguments { .. }
+                | McpError::ToolNotAllowed { .. }
+                | McpError::AmbiguousTool { .. }
+                | McpError::Unsupported { .. }
+                | McpError::Deserialize(_)
+        )
+    }
+}
+
+/// Decides whether a failed operation is worth retrying.
+///
+/// Closures `Fn(&McpError) -> bool` implement this, so a deployment can
+/// e.g. treat a specific RPC error code as transient.
+pub trait RetryClassifier: Send + Sync {
+    /// Whether retrying after `error` may succeed
+    fn is_retryable(&self, error: &McpError) -> bool;
+}
+
+impl<F> RetryClassifier for F
+where
+    F: Fn(&McpError) -> bool + Send + Sync,
+{
+    fn is_retryable(&self, error: &McpError) -> bool {
+        self(error)
+    }
+}
+
+/// The built-in classification, [`McpError::is_retryable`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultRetryClassifier;
+
+impl RetryClassifier for DefaultRetryClassifier {
+    fn is_retryable(&self, error: &McpError) -> bool {
+        error.is_retryable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classification() {
+        assert!(McpError::Timeout.is_retryable());
+        assert!(McpError::Timeout.is_transport());
+        assert!(McpError::ServerUnhealthy("a".into()).is_retryable());
+
+        let rpc = McpError::RpcError { code: -32601, message: "no".into() };
+        assert!(rpc.is_protocol());
+        assert!(!rpc.is_retryable());
+
+        assert!(McpError::ToolError("boom".into()).is_tool_error());
+        assert!(McpError::InvalidArguments { errors: vec![] }.is_caller_error());
+    }
+
+    #[test]
+    fn test_custom_retry_classifier() {
+        let classifier = |e: &McpError| match e {
+            McpError::RpcError { code, .. } => *code == -32000,
+            other => other.is_retryable(),
+        };
+        let overloaded = McpError::RpcError { code: -32000, message: "busy".into() };
+
+        assert!(classifier.is_retryable(&overloaded));
+        assert!(!DefaultRetryClassifier.is_retryable(&overloaded));
+    }
+}
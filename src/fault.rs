@@ -0,0 +1,172 @@
+//! Fault injection for testing
+//!
+//! [`FaultyTransport`] wraps a real transport and fails, delays or drops
+//! requests according to a script, so reconnect, timeout and retry paths
+//! can be exercised deterministically. Enabled by the `test-util` feature.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+
+use crate::error::McpError;
+use crate::transport::McpTransport;
+use crate::types::{ProcessExit, TrafficStats};
+
+/// A fault applied to one request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with `McpError::TransportError`
+    Fail,
+    /// Fail with `McpError::Timeout`
+    Timeout,
+    /// Wait before forwarding the request
+    Delay(Duration),
+    /// Close the inner transport and fail this and every later request
+    Disconnect,
+}
+
+/// Transport that injects scripted faults into requests
+pub struct FaultyTransport {
+    inner: Arc<dyn McpTransport>,
+    script: Mutex<VecDeque<Fault>>,
+    disconnected: AtomicBool,
+    injected: AtomicUsize,
+}
+
+impl FaultyTransport {
+    /// Wrap `inner`; requests pass through until faults are scripted
+    pub fn new(inner: Arc<dyn McpTransport>) -> Self {
+        Self {
+            inner,
+            script: Mutex::new(VecDeque::new()),
+            disconnected: AtomicBool::new(false),
+            injected: AtomicUsize::new(0),
+        }
+    }
+
+    /// Apply `fault` to a future request, after any already scripted
+    pub fn push(&self, fault: Fault) {
+        self.script.lock().push_back(fault);
+    }
+
+    /// Fail the next `count` requests
+    pub fn fail_next(&self, count: usize) {
+        let mut script = self.script.lock();
+        script.extend(std::iter::repeat(Fault::Fail).take(count));
+    }
+
+    /// Delay the next request by `delay`
+    pub fn delay_next(&self, delay: Duration) {
+        self.push(Fault::Delay(delay));
+    }
+
+    /// Drop the connection on the next request
+    pub fn disconnect_next(&self) {
+        self.push(Fault::Disconnect);
+    }
+
+    /// Number of faults injected so far
+    pub fn injected(&self) -> usize {
+        self.injected.load(Ordering::SeqCst)
+    }
+
+    fn check_connected(&self) -> Result<(), McpError> {
+        if self.disconnected.load(Ordering::SeqCst) {
+            return Err(McpError::TransportError("Connection dropped by fault injection".into()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl McpTransport for FaultyTransport {
+    async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        self.check_connected()?;
+
+        let fault = self.script.lock().pop_front();
+        if let Some(fault) = fault {
+            self.injected.fetch_add(1, Ordering::SeqCst);
+            match fault {
+                Fault::Fail => {
+                    return Err(McpError::TransportError("Injected fault".into()));
+                }
+                Fault::Timeout => return Err(McpError::Timeout),
+                Fault::Delay(delay) => tokio::time::sleep(delay).await,
+                Fault::Disconnect => {
+                    self.disconnected.store(true, Ordering::SeqCst);
+                    let _ = self.inner.close().await;
+                    return Err(McpError::TransportError("Connection dropped by fault injection".into()));
+                }
+            }
+        }
+
+        self.inner.send_request(request).await
+    }
+
+    async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
+        self.check_connected()?;
+        self.inner.send_notification(notification).await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.inner.subscribe()
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.inner.dropped_messages()
+    }
+
+    fn recent_stderr(&self) -> Vec<String> {
+        self.inner.recent_stderr()
+    }
+
+    fn exit_status(&self) -> Option<ProcessExit> {
+        self.inner.exit_status()
+    }
+
+    fn has_process(&self) -> bool {
+        self.inner.has_process()
+    }
+
+    async fn wait_for_exit(&self, timeout: Duration) -> Option<ProcessExit> {
+        self.inner.wait_for_exit(timeout).await
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        self.inner.traffic()
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.disconnected.store(true, Ordering::SeqCst);
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fake_server, reply};
+
+    #[tokio::test]
+    async fn test_scripted_faults() {
+        let inner = fake_server(|request| vec![reply(request, serde_json::json!({}))]);
+        let transport = FaultyTransport::new(inner);
+        let request = || serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+
+        transport.fail_next(2);
+        transport.push(Fault::Timeout);
+        assert!(matches!(transport.send_request(request()).await, Err(McpError::TransportError(_))));
+        assert!(matches!(transport.send_request(request()).await, Err(McpError::TransportError(_))));
+        assert!(matches!(transport.send_request(request()).await, Err(McpError::Timeout)));
+        assert!(transport.send_request(request()).await.is_ok());
+
+        transport.disconnect_next();
+        assert!(transport.send_request(request()).await.is_err());
+        assert!(transport.send_request(request()).await.is_err());
+        assert_eq!(transport.injected(), 4);
+    }
+}
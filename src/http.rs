@@ -0,0 +1,388 @@
+//! Streamable HTTP transport
+//!
+//! Each JSON-RPC message is POSTed to the server's endpoint. Requests get
+//! their response either as a JSON body or on a `text/event-stream`, which
+//! may carry server-initiated messages ahead of the response; those are
+//! delivered to subscribers. Notifications are accepted with no body.
+//! The `Mcp-Session-Id` header assigned by the server (normally on the
+//! `initialize` response) is echoed on every later message, and the
+//! session is ended with a DELETE on `close`.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use crate::error::McpError;
+use crate::transport::{parse_message, McpTransport, TransportOptions};
+use crate::types::TrafficStats;
+
+/// Header carrying the session id assigned by the server
+pub const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+/// Capacity of the channel fanning out server-initiated messages
+const INBOUND_CAPACITY: usize = 256;
+
+/// Longest slice of an error response body quoted in `TransportError`
+const ERROR_BODY_LIMIT: usize = 200;
+
+/// JSON-RPC over HTTP POST to a single endpoint
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    /// Session id assigned by the server, echoed on every message
+    session_id: Mutex<Option<String>>,
+    inbound: broadcast::Sender<serde_json::Value>,
+    closed: AtomicBool,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    options: TransportOptions,
+}
+
+impl HttpTransport {
+    /// Create a transport posting to `url`; no request is made until the first message.
+    ///
+    /// Uses `options.http_client` if set, else a default client.
+    pub fn new(url: impl Into<String>, options: TransportOptions) -> Result<Self, McpError> {
+        let client = match &options.http_client {
+            Some(client) => client.clone(),
+            None => reqwest::Client::builder()
+                .build()
+                .map_err(|e| McpError::TransportError(format!("Failed to create HTTP client: {}", e)))?,
+        };
+        Ok(Self::with_client(client, url, options))
+    }
+
+    /// Create a transport posting to `url` through `client`.
+    ///
+    /// Lets the embedder set proxies, TLS roots, default headers and
+    /// timeouts, or share one connection pool across servers.
+    pub fn with_client(client: reqwest::Client, url: impl Into<String>, options: TransportOptions) -> Self {
+        Self {
+            client,
+            url: url.into(),
+            session_id: Mutex::new(None),
+            inbound: broadcast::channel(INBOUND_CAPACITY).0,
+            closed: AtomicBool::new(false),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            options,
+        }
+    }
+
+    /// Session id assigned by the server, if any
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().clone()
+    }
+
+    /// POST one message and return the response once its status is checked
+    async fn post(&self, message: &serde_json::Value) -> Result<reqwest::Response, McpError> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(McpError::TransportError("Connection closed".into()));
+        }
+
+        let body = serde_json::to_vec(message)
+            .map_err(|e| McpError::TransportError(format!("Serialize error: {}", e)))?;
+        self.bytes_sent.fetch_add(body.len() as u64, Ordering::Relaxed);
+
+        let mut request = self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::ACCEPT, "application/json, text/event-stream")
+            .body(body);
+        if let Some(session_id) = self.session_id() {
+            request = request.header(SESSION_HEADER, session_id);
+        }
+
+        let response = request.send().await
+            .map_err(|e| McpError::TransportError(format!("HTTP request to {} failed: {}", self.url, e)))?;
+        if let Some(session_id) = response.headers().get(SESSION_HEADER) {
+            let session_id = session_id.to_str()
+                .map_err(|e| McpError::ProtocolError(format!("Invalid {} header: {}", SESSION_HEADER, e)))?;
+            *self.session_id.lock() = Some(session_id.to_string());
+        }
+        check_status(response).await
+    }
+
+    /// Read events until the response to request `id`, forwarding anything else to subscribers
+    async fn read_event_stream(
+        &self,
+        mut response: reqwest::Response,
+        id: &serde_json::Value,
+    ) -> Result<serde_json::Value, McpError> {
+        let mut parser = SseParser::default();
+        loop {
+            let chunk = response.chunk().await
+                .map_err(|e| McpError::TransportError(format!("Read error: {}", e)))?;
+            let Some(chunk) = chunk else {
+                return Err(McpError::TransportError("Event stream ended without a response".into()));
+            };
+            self.bytes_received.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+
+            for data in parser.push(&chunk) {
+                let message = parse_message(data.as_bytes(), self.options.max_json_depth)?;
+                if message.get("method").is_none() && message.get("id") == Some(id) {
+                    return Ok(message);
+                }
+                // Nobody subscribed is fine
+                let _ = self.inbound.send(message);
+            }
+        }
+    }
+}
+
+/// Whether the server answered with an SSE stream
+fn is_event_stream(response: &reqwest::Response) -> bool {
+    response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+/// Incremental parser for a `text/event-stream` body.
+///
+/// Bytes are fed in as they arrive, split anywhere; each completed event
+/// yields its `data` lines joined by newlines. Other fields and comments
+/// are ignored.
+#[derive(Default)]
+struct SseParser {
+    /// Bytes after the last complete line
+    partial: Vec<u8>,
+    /// `data` lines of the event being read
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feed the next chunk, returning the data of every event it completes
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.partial.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let mut line: Vec<u8> = self.partial.drain(..=end).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            let line = String::from_utf8_lossy(&line);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            } else if line == "data" {
+                self.data.push(String::new());
+            }
+        }
+        events
+    }
+}
+
+/// Map a non-2xx response to `TransportError`, quoting the start of its body
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, McpError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let body: String = body.chars().take(ERROR_BODY_LIMIT).collect();
+    Err(McpError::TransportError(if body.is_empty() {
+        format!("HTTP {}", status)
+    } else {
+        format!("HTTP {}: {}", status, body)
+    }))
+}
+
+#[async_trait]
+impl McpTransport for HttpTransport {
+    async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let response = self.post(&request).await?;
+        if is_event_stream(&response) {
+            return self.read_event_stream(response, &request["id"]).await;
+        }
+        let body = response.bytes().await
+            .map_err(|e| McpError::TransportError(format!("Read error: {}", e)))?;
+        self.bytes_received.fetch_add(body.len() as u64, Ordering::Relaxed);
+        parse_message(&body, self.options.max_json_depth)
+    }
+
+    async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
+        // Usually 202 Accepted with no body; anything in it is ignored
+        self.post(&notification).await?;
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.inbound.subscribe()
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        TrafficStats {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        // Ending the session is a courtesy; servers may not support it
+        if let Some(session_id) = self.session_id.lock().take() {
+            let ended = self.client
+                .delete(&self.url)
+                .header(SESSION_HEADER, session_id)
+                .send()
+                .await;
+            if let Err(e) = ended {
+                debug!(url = %self.url, error = %e, "Failed to end HTTP session");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Serve one connection per request, answering each with `respond(headers, body)`
+    async fn http_server<F>(mut respond: F) -> String
+    where
+        F: FnMut(&str, serde_json::Value) -> String + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let mut socket = BufReader::new(socket);
+                let mut head = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    socket.read_line(&mut line).await.unwrap();
+                    if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let mut body = vec![0; content_length];
+                socket.read_exact(&mut body).await.unwrap();
+                let body = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+                let response = respond(&head, body);
+                socket.get_mut().write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    fn json_response(extra_headers: &str, body: &serde_json::Value) -> String {
+        let body = body.to_string();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            extra_headers,
+            body.len(),
+            body
+        )
+    }
+
+    #[tokio::test]
+    async fn test_session_id_round_trip() {
+        let (seen_tx, mut seen_rx) = tokio::sync::mpsc::unbounded_channel();
+        let url = http_server(move |head, body| {
+            let session = head.lines()
+                .find_map(|line| line.to_ascii_lowercase().strip_prefix("mcp-session-id:").map(|v| v.trim().to_string()));
+            let _ = seen_tx.send(session);
+            match body["method"].as_str() {
+                Some("initialize") => json_response(
+                    "Mcp-Session-Id: abc123\r\n",
+                    &serde_json::json!({"jsonrpc": "2.0", "id": body["id"], "result": {}}),
+                ),
+                Some("notifications/initialized") => {
+                    "HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                }
+                _ => json_response("", &serde_json::json!({"jsonrpc": "2.0", "id": body["id"], "result": {"ok": true}})),
+            }
+        }).await;
+
+        let transport = HttpTransport::new(url, TransportOptions::default()).unwrap();
+        transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": "initialize"})).await.unwrap();
+        assert_eq!(transport.session_id().as_deref(), Some("abc123"));
+        transport.send_notification(serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await.unwrap();
+        let response = transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"})).await.unwrap();
+        assert_eq!(response["result"]["ok"], true);
+
+        assert_eq!(seen_rx.recv().await.unwrap(), None);
+        assert_eq!(seen_rx.recv().await.unwrap().as_deref(), Some("abc123"));
+        assert_eq!(seen_rx.recv().await.unwrap().as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn test_sse_parser_across_chunk_boundaries() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b": keep-alive\r\nevent: message\r\nda").is_empty());
+        assert!(parser.push(b"ta: {\"a\":\r\ndata: 1}\r").is_empty());
+        assert_eq!(parser.push(b"\n\r\ndata:{}\n\n"), ["{\"a\":\n1}", "{}"]);
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_response() {
+        let url = http_server(|_, body| {
+            let events = format!(
+                "data: {}\n\ndata: {}\n\n",
+                serde_json::json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {"progress": 1}}),
+                serde_json::json!({"jsonrpc": "2.0", "id": body["id"], "result": {"done": true}}),
+            );
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                events.len(),
+                events
+            )
+        }).await;
+
+        let transport = HttpTransport::new(url, TransportOptions::default()).unwrap();
+        let mut inbound = transport.subscribe();
+        let response = transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call"})).await.unwrap();
+        assert_eq!(response["result"]["done"], true);
+        assert_eq!(inbound.recv().await.unwrap()["method"], "notifications/progress");
+    }
+
+    #[tokio::test]
+    async fn test_custom_client() {
+        let (seen_tx, mut seen_rx) = tokio::sync::mpsc::unbounded_channel();
+        let url = http_server(move |head, body| {
+            let _ = seen_tx.send(head.to_ascii_lowercase().contains("x-embedder: lair"));
+            json_response("", &serde_json::json!({"jsonrpc": "2.0", "id": body["id"], "result": {}}))
+        }).await;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-Embedder", reqwest::header::HeaderValue::from_static("lair"));
+        let client = reqwest::Client::builder().default_headers(headers).build().unwrap();
+        let options = TransportOptions {
+            http_client: Some(client),
+            ..TransportOptions::default()
+        };
+        let transport = HttpTransport::new(url, options).unwrap();
+        transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"})).await.unwrap();
+        assert!(seen_rx.recv().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_error_status_is_transport_error() {
+        let url = http_server(|_, _| {
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 8\r\nConnection: close\r\n\r\noverload".to_string()
+        }).await;
+
+        let transport = HttpTransport::new(url, TransportOptions::default()).unwrap();
+        let err = transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": "ping"})).await.unwrap_err();
+        assert!(matches!(err, McpError::TransportError(ref m) if m.contains("503") && m.contains("overload")));
+    }
+}
@@ -40,14 +40,41 @@
 pub mod manager;
 pub mod connection;
 pub mod transport;
+pub mod http;
 pub mod types;
 pub mod error;
+pub mod validation;
+pub mod arguments;
+pub mod trace;
+pub mod config;
+pub mod schema_cache;
+pub mod reconnect;
+pub mod metrics;
+pub mod tasks;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "debug-mirror")]
+pub mod mirror;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fault;
+#[cfg(any(test, feature = "test-util"))]
+pub mod replay;
 
-pub use manager::McpManager;
+#[cfg(test)]
+mod testing;
+
+pub use manager::{McpManager, ManagerHandle, function_name, qualified_name};
 pub use connection::McpConnection;
-pub use transport::McpTransport;
+pub use arguments::Arguments;
+pub use config::{load_configs, load_server_configs, ServerConfigs};
+pub use reconnect::{ReconnectLimiter, ReconnectPolicy};
+pub use metrics::{McpMetrics, McpMetricsSnapshot, ServerMetrics};
+pub use tasks::ReaderPool;
+pub use schema_cache::{SchemaCache, MemorySchemaCache, FileSchemaCache};
+pub use transport::{McpTransport, TransportFactory, TransportOptions, SpawnOptions, ResourceLimit, InboundRateLimit, ProtocolStream};
 pub use types::*;
-pub use error::McpError;
+pub use error::{McpError, RetryClassifier, DefaultRetryClassifier};
 
 // Re-export protocol types
 pub use warhorn::McpServerConfig;
+pub use tokio_util::sync::CancellationToken;
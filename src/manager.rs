@@ -1,69 +1,1025 @@
 //! MCP connection manager
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use parking_lot::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use futures::Stream;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn, error};
 
 use warhorn::McpServerConfig;
-use crate::connection::McpConnection;
-use crate::types::{ToolSchema, ServerHealth, ServerInfo};
-use crate::error::McpError;
+use crate::arguments::Arguments;
+use crate::connection::{McpConnection, DEFAULT_REQUEST_TIMEOUT, DEFAULT_SHUTDOWN_GRACE};
+use crate::metrics::McpMetricsSnapshot;
+use crate::reconnect::{ReconnectLimiter, ReconnectPolicy};
+use crate::tasks::{spawn_on, ReaderPool, TaskBudget, TaskPermit};
+use crate::transport::{ConfigTransportFactory, TransportFactory, TransportOptions};
+use crate::types::{
+    ToolSchema, QualifiedTool, ServerHealth, ServerInfo, DryRunReport, LogLevel, ServerLogEntry, UnhealthyPolicy, ListKind,
+    FunctionFormat, ResourceContents, ResourceSchema, ServerDiagnostics, ConnectPhase, ToolCacheStats,
+    InFlightRequest, ToolsDiff, ManagerEvent, ConnectionEvent, ToolCallResult, ResourceUpdate, ServerStatus,
+    ToolInvocation, ToolPolicy, ToolContent,
+};
+use crate::error::{McpError, RetryClassifier, DefaultRetryClassifier};
+use crate::validation::{check_schema, SchemaProblem};
+use crate::schema_cache::{validity_token, SchemaCache};
 
 /// Manages connections to multiple MCP servers
 pub struct McpManager {
     /// Active connections by server ID
     connections: RwLock<HashMap<String, Arc<McpConnection>>>,
+    /// Serializes connects and updates per server ID
+    connect_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Instance number of each primary connection, by server ID
+    primaries: Mutex<HashMap<String, u64>>,
+    /// Idle, initialized standby connections with their instance numbers
+    standbys: Mutex<HashMap<String, (u64, Arc<McpConnection>)>>,
+    /// Server IDs that keep a warm standby
+    standby_servers: HashSet<String>,
+    /// Source of connection instance numbers
+    next_instance: AtomicU64,
     /// Cached tool schemas
-    tool_cache: RwLock<HashMap<String, Vec<ToolSchema>>>,
+    tool_cache: Arc<ToolCache>,
+    /// Resource listings per server, fetched on first use
+    resource_listings: Arc<RwLock<HashMap<String, Vec<ResourceSchema>>>>,
     /// Server health status
     health: RwLock<HashMap<String, ServerHealth>>,
+    /// Transport options applied to new connections
+    transport_options: TransportOptions,
+    /// Creates transports for new connections
+    transport_factory: Arc<dyn TransportFactory>,
+    /// Maximum pages fetched by one paginated list call
+    max_list_pages: usize,
+    /// TTL for per-connection resource caches (None disables caching)
+    resource_cache_ttl: Option<Duration>,
+    /// Longest `connect` waits for the initial tool listing
+    discovery_timeout: Duration,
+    /// Decides which errors are transient
+    retry_classifier: Arc<dyn RetryClassifier>,
+    /// How tool calls treat degraded servers
+    unhealthy_policy: UnhealthyPolicy,
+    /// Window for coalescing `list_changed` notifications before refreshing
+    list_changed_debounce: Duration,
+    /// Window for coalescing rapid sandbox notifications (None sends immediately)
+    notification_debounce: Option<Duration>,
+    /// Generation of the latest pending sandbox notification per server
+    pending_sandbox: Arc<Mutex<HashMap<String, u64>>>,
+    /// Dropped-message count per server as of the last health check
+    dropped_seen: Mutex<HashMap<String, u64>>,
+    /// Health check history per server; health itself lives in `health`
+    statuses: Mutex<HashMap<String, ServerStatus>>,
+    /// Latest sandbox state, sent to servers that connect later
+    sandbox_state: Mutex<Option<(bool, String)>>,
+    /// Log messages from all servers
+    logs: broadcast::Sender<ServerLogEntry>,
+    /// Manager events, e.g. tool catalog changes
+    events: broadcast::Sender<ManagerEvent>,
+    /// Servers configured but disabled, by ID
+    disabled: RwLock<HashMap<String, McpServerConfig>>,
+    /// Protocol version pinned per server ID
+    protocol_versions: HashMap<String, String>,
+    /// Tool result size limit applied to new connections
+    max_result_size: Option<usize>,
+    /// Tool argument size limit applied to new connections
+    max_argument_size: Option<usize>,
+    /// Invocation history capacity for new connections (0 disables it)
+    invocation_history: usize,
+    /// Persisted tool schemas consulted by `connect`, if any
+    schema_cache: Option<Arc<dyn SchemaCache>>,
+    /// Request id prefix applied to new connections
+    id_prefix: Option<String>,
+    /// Paces reconnects across all connections (None is unpaced)
+    reconnect_limiter: Option<Arc<ReconnectLimiter>>,
+    /// Automatic reconnect policy of new connections (None disables it)
+    reconnect_policy: Option<ReconnectPolicy>,
+    /// How long `update_server` lets calls on the replaced connection finish
+    drain_timeout: Duration,
+    /// Omit empty `params` on messages of new connections
+    omit_empty_params: bool,
+    /// Per-request timeout of new connections
+    request_timeout: Duration,
+    /// Time server processes of new connections get to exit on shutdown
+    shutdown_grace: Duration,
+    /// Most connects `connect_all` runs at once (None is unlimited)
+    connect_concurrency: Option<usize>,
+    /// Prefix tool names with their server id in `list_tools`
+    qualify_tool_names: bool,
+    /// Check tool call arguments against the cached input schema before sending
+    validate_arguments: bool,
+    /// Background tasks spawned by the manager, optionally capped
+    tasks: TaskBudget,
+    /// Cancelled when the manager is dropped, ending its background tasks
+    dropped: CancellationToken,
 }
 
+/// Tool schemas per server, updated last-writer-wins.
+///
+/// Every listing takes a ticket before it is fetched, and its result is
+/// only stored if no listing that started later has been stored already.
+/// Concurrent refreshes therefore can't replace a newer catalog with an
+/// older one.
+struct ToolCache {
+    tools: RwLock<HashMap<String, Vec<ToolSchema>>>,
+    /// Ticket of the listing currently stored per server
+    generations: Mutex<HashMap<String, u64>>,
+    /// When each server's listing was last stored
+    refreshed: Mutex<HashMap<String, SystemTime>>,
+    next_ticket: AtomicU64,
+    /// Tools hidden per server; filtered out before anything is stored
+    policies: RwLock<HashMap<String, ToolPolicy>>,
+    /// Receives `ToolsChanged` whenever a store or removal alters a catalog
+    events: broadcast::Sender<ManagerEvent>,
+}
+
+impl ToolCache {
+    fn new(events: broadcast::Sender<ManagerEvent>) -> Self {
+        Self {
+            tools: RwLock::new(HashMap::new()),
+            generations: Mutex::new(HashMap::new()),
+            refreshed: Mutex::new(HashMap::new()),
+            next_ticket: AtomicU64::new(0),
+            policies: RwLock::new(HashMap::new()),
+            events,
+        }
+    }
+
+    /// Drop the tools `server_id`'s policy doesn't allow
+    fn permitted(&self, server_id: &str, mut tools: Vec<ToolSchema>) -> Vec<ToolSchema> {
+        if let Some(policy) = self.policies.read().get(server_id) {
+            tools.retain(|tool| policy.allows(&tool.name));
+        }
+        tools
+    }
+
+    /// Whether `server_id`'s policy allows `tool`
+    fn allows(&self, server_id: &str, tool: &str) -> bool {
+        match self.policies.read().get(server_id) {
+            Some(policy) => policy.allows(tool),
+            None => true,
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Vec<ToolSchema>>> {
+        self.tools.read()
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Vec<ToolSchema>>> {
+        self.tools.write()
+    }
+
+    /// Take a ticket before fetching a listing
+    fn ticket(&self) -> u64 {
+        self.next_ticket.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Store `tools` unless a listing with a later ticket is already stored
+    fn store(&self, server_id: &str, ticket: u64, tools: Vec<ToolSchema>) -> bool {
+        let tools = self.permitted(server_id, tools);
+        let mut cache = self.tools.write();
+        let mut generations = self.generations.lock();
+        let current = generations.entry(server_id.to_string()).or_insert(0);
+        if ticket < *current {
+            return false;
+        }
+        *current = ticket;
+        let diff = ToolsDiff::between(cache.get(server_id).map_or(&[][..], Vec::as_slice), &tools);
+        cache.insert(server_id.to_string(), tools);
+        self.refreshed.lock().insert(server_id.to_string(), SystemTime::now());
+        self.announce(server_id, diff);
+        true
+    }
+
+    /// Drop a server's tools, discarding any listing still in flight
+    fn remove(&self, server_id: &str) {
+        let ticket = self.ticket();
+        let mut cache = self.tools.write();
+        self.generations.lock().insert(server_id.to_string(), ticket);
+        self.refreshed.lock().remove(server_id);
+        if let Some(tools) = cache.remove(server_id) {
+            self.announce(server_id, ToolsDiff::between(&tools, &[]));
+        }
+    }
+
+    fn announce(&self, server_id: &str, diff: ToolsDiff) {
+        if diff.is_empty() {
+            return;
+        }
+        // No subscribers is fine
+        let _ = self.events.send(ManagerEvent::ToolsChanged {
+            server_id: server_id.to_string(),
+            added: diff.added,
+            removed: diff.removed,
+            modified: diff.modified,
+        });
+    }
+}
+
+/// Capacity of the aggregated log channel
+const LOG_CAPACITY: usize = 1024;
+
+/// Capacity of the manager event channel
+const EVENT_CAPACITY: usize = 256;
+
+/// Separator between server id and tool name in function-calling names
+pub const FUNCTION_NAME_SEPARATOR: &str = "__";
+
+/// Separator between server id and tool name in qualified tool names
+pub const QUALIFIED_NAME_SEPARATOR: &str = "::";
+
+/// Longest function name providers accept
+const MAX_FUNCTION_NAME_LEN: usize = 64;
+
+/// Length of the hash suffix of rewritten function names, `_` included
+const FUNCTION_NAME_HASH_LEN: usize = 9;
+
+/// Default window for coalescing `list_changed` notifications
+const DEFAULT_LIST_CHANGED_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Default limit on the initial tool listing during `connect`
+const DEFAULT_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time `update_server` waits for calls on the old connection
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Long-lived manager tasks per connection: log, resource update and list_changed forwarding
+const TASKS_PER_CONNECTION: usize = 3;
+
+/// How often `UnhealthyPolicy::WaitForHealthy` re-checks server health
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 impl McpManager {
     /// Create a new MCP manager
     pub fn new() -> Self {
+        let events = broadcast::channel(EVENT_CAPACITY).0;
         Self {
             connections: RwLock::new(HashMap::new()),
-            tool_cache: RwLock::new(HashMap::new()),
+            connect_locks: Mutex::new(HashMap::new()),
+            primaries: Mutex::new(HashMap::new()),
+            standbys: Mutex::new(HashMap::new()),
+            standby_servers: HashSet::new(),
+            next_instance: AtomicU64::new(0),
+            tool_cache: Arc::new(ToolCache::new(events.clone())),
+            resource_listings: Arc::default(),
             health: RwLock::new(HashMap::new()),
+            transport_options: TransportOptions::default(),
+            transport_factory: Arc::new(ConfigTransportFactory),
+            max_list_pages: crate::connection::DEFAULT_MAX_LIST_PAGES,
+            resource_cache_ttl: None,
+            discovery_timeout: DEFAULT_DISCOVERY_TIMEOUT,
+            retry_classifier: Arc::new(DefaultRetryClassifier),
+            unhealthy_policy: UnhealthyPolicy::default(),
+            list_changed_debounce: DEFAULT_LIST_CHANGED_DEBOUNCE,
+            notification_debounce: None,
+            pending_sandbox: Arc::new(Mutex::new(HashMap::new())),
+            dropped_seen: Mutex::new(HashMap::new()),
+            statuses: Mutex::new(HashMap::new()),
+            sandbox_state: Mutex::new(None),
+            logs: broadcast::channel(LOG_CAPACITY).0,
+            events,
+            disabled: RwLock::new(HashMap::new()),
+            protocol_versions: HashMap::new(),
+            max_result_size: None,
+            max_argument_size: None,
+            invocation_history: 0,
+            schema_cache: None,
+            id_prefix: None,
+            reconnect_limiter: None,
+            reconnect_policy: None,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            omit_empty_params: false,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            connect_concurrency: None,
+            qualify_tool_names: false,
+            validate_arguments: false,
+            tasks: TaskBudget::default(),
+            dropped: CancellationToken::new(),
         }
     }
 
-    /// Connect to an MCP server
+    /// Set the transport options applied to new connections
+    pub fn with_transport_options(mut self, options: TransportOptions) -> Self {
+        self.transport_options = options;
+        self
+    }
+
+    /// Set the factory used to create transports for new connections
+    pub fn with_transport_factory(mut self, factory: Arc<dyn TransportFactory>) -> Self {
+        self.transport_factory = factory;
+        self
+    }
+
+    /// Set the maximum JSON nesting depth accepted from servers
+    pub fn with_max_json_depth(mut self, depth: usize) -> Self {
+        self.transport_options.max_json_depth = depth;
+        self
+    }
+
+    /// Batch requests issued within `window` of each other (servers must support batches)
+    pub fn with_batch_window(mut self, window: Duration) -> Self {
+        self.transport_options.batch_window = Some(window);
+        self
+    }
+
+    /// Connect HTTP servers through `client` instead of a default client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.transport_options.http_client = Some(client);
+        self
+    }
+
+    /// Set the maximum number of pages fetched by one paginated list call
+    pub fn with_max_list_pages(mut self, max_pages: usize) -> Self {
+        self.max_list_pages = max_pages;
+        self
+    }
+
+    /// Cache resource reads on new connections for `ttl`
+    pub fn with_resource_cache(mut self, ttl: Duration) -> Self {
+        self.resource_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Pin the protocol version negotiated with `server_id`, e.g. for a legacy server
+    pub fn with_protocol_version(mut self, server_id: impl Into<String>, version: impl Into<String>) -> Self {
+        self.protocol_versions.insert(server_id.into(), version.into());
+        self
+    }
+
+    /// Truncate tool results larger than `bytes` on new connections
+    pub fn with_max_result_size(mut self, bytes: usize) -> Self {
+        self.max_result_size = Some(bytes);
+        self
+    }
+
+    /// Reject tool calls with arguments larger than `bytes` on new connections
+    pub fn with_max_argument_size(mut self, bytes: usize) -> Self {
+        self.max_argument_size = Some(bytes);
+        self
+    }
+
+    /// Keep the last `capacity` tool calls of each new connection
+    pub fn with_invocation_history(mut self, capacity: usize) -> Self {
+        self.invocation_history = capacity;
+        self
+    }
+
+    /// Persist tool schemas in `cache` and reuse them on later connects.
+    ///
+    /// When the server reports the same name and version as when its tools
+    /// were saved, `connect` uses the saved tools instead of waiting for
+    /// `tools/list`, and refreshes them in the background.
+    pub fn with_schema_cache(mut self, cache: Arc<dyn SchemaCache>) -> Self {
+        self.schema_cache = Some(cache);
+        self
+    }
+
+    /// Send string request ids like `"{prefix}-42"` on new connections.
+    ///
+    /// Keeps ids unique when several managers share servers through a
+    /// multiplexing proxy.
+    pub fn with_id_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.id_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Limit reconnects across all servers to `per_second`, with bursts of `burst`.
+    ///
+    /// One token bucket is shared by every connection, so when a common
+    /// dependency recovers the fleet reconnects at this rate rather than all
+    /// at once. Applies on top of each connection's own backoff (see
+    /// `with_reconnect_policy`); callers retrying `reconnect` in a loop should
+    /// still back off themselves.
+    pub fn with_reconnect_rate(mut self, per_second: u32, burst: u32) -> Self {
+        self.reconnect_limiter = Some(Arc::new(ReconnectLimiter::new(per_second, burst)));
+        self
+    }
+
+    /// Reconnect servers automatically when their transport breaks.
+    ///
+    /// After each successful reconnect tools are refreshed, cached resource
+    /// listings dropped and `ManagerEvent::Reconnected` emitted. Disabled
+    /// unless set; pass `ReconnectPolicy::disabled()` to turn it back off.
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    /// Keep a warm standby connection for `server_id`.
+    ///
+    /// The standby is initialized alongside the primary and pinged by
+    /// `health_check`, but carries no traffic. When a health check finds the
+    /// primary failing, the standby is promoted and a new one started.
+    pub fn with_standby(mut self, server_id: impl Into<String>) -> Self {
+        self.standby_servers.insert(server_id.into());
+        self
+    }
+
+    /// Cap the manager's long-lived background tasks at `max`.
+    ///
+    /// Each connection starts these tasks (a standby only the connection and
+    /// transport ones until it is promoted):
+    ///
+    /// - manager: log forwarding, resource update forwarding and the
+    ///   `list_changed` watcher, all counted against the budget
+    /// - connection: forwarding of server messages, one per transport
+    /// - stream transports: the protocol reader
+    /// - stdio transports: the stderr reader (plus one reader per output
+    ///   and a merger with `ProtocolStream::Both`)
+    ///
+    /// A connect that would push the counted tasks past `max` fails with
+    /// `McpError::TaskBudgetExhausted`. Debounced sandbox notifications and
+    /// background schema refreshes are short-lived; they are counted in
+    /// `background_tasks` but never refused. The connection and transport
+    /// tasks above, and request batch flushes, aren't counted at all. To
+    /// keep all of them off their own tasks, see `with_reader_pool`.
+    pub fn with_task_budget(mut self, max: usize) -> Self {
+        self.tasks = TaskBudget::new(Some(max));
+        self
+    }
+
+    /// Run the per-connection loops of new connections on `pool`.
+    ///
+    /// Covers the manager's forwarding tasks, the connection's message
+    /// forwarding, and transport reader loops, so a whole fleet uses one
+    /// task. Sets `TransportOptions::reader_pool`.
+    pub fn with_reader_pool(mut self, pool: ReaderPool) -> Self {
+        self.transport_options.reader_pool = Some(pool);
+        self
+    }
+
+    /// Restrict which of `server_id`'s tools are exposed.
+    ///
+    /// Applied where listings are cached, so blocked tools never show up in
+    /// `list_tools`, `find_tool` or function schemas, and calling one fails
+    /// with `McpError::ToolNotAllowed` without contacting the server.
+    pub fn with_tool_policy(self, server_id: impl Into<String>, policy: ToolPolicy) -> Self {
+        self.tool_cache.policies.write().insert(server_id.into(), policy);
+        self
+    }
+
+    /// Leave out empty `params` on new connections, for servers that reject `{}`
+    pub fn with_empty_params_omitted(mut self) -> Self {
+        self.omit_empty_params = true;
+        self
+    }
+
+    /// Set the default per-request timeout of new connections
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Set how long server processes of new connections get to exit by
+    /// themselves on disconnect before they are killed
+    pub fn with_shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = grace;
+        self
+    }
+
+    /// Limit how many servers `connect_all` connects at once, e.g. to avoid
+    /// spawning hundreds of server processes together
+    pub fn with_connect_concurrency(mut self, limit: usize) -> Self {
+        self.connect_concurrency = Some(limit.max(1));
+        self
+    }
+
+    /// Check tool call arguments against the tool's cached input schema.
+    ///
+    /// Calls with invalid arguments fail with `McpError::InvalidArguments`
+    /// without contacting the server. Off by default, since some servers
+    /// use schema extensions the checker doesn't understand. Tools missing
+    /// from the cache are sent unchecked.
+    pub fn with_argument_validation(mut self) -> Self {
+        self.validate_arguments = true;
+        self
+    }
+
+    /// Prefix tool names with their server id (`server::tool`) in `list_tools`.
+    ///
+    /// Servers exposing tools of the same name then list distinct entries;
+    /// `find_tool` and `resolve_qualified_name` map them back for dispatch.
+    pub fn with_qualified_tool_names(mut self) -> Self {
+        self.qualify_tool_names = true;
+        self
+    }
+
+    /// Set how long `update_server` waits for in-flight calls on the old connection
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Set how long `connect` waits for the initial tool listing
+    pub fn with_discovery_timeout(mut self, timeout: Duration) -> Self {
+        self.discovery_timeout = timeout;
+        self
+    }
+
+    /// Override which errors retry and failover logic treats as transient
+    pub fn with_retry_classifier(mut self, classifier: impl RetryClassifier + 'static) -> Self {
+        self.retry_classifier = Arc::new(classifier);
+        self
+    }
+
+    /// Whether `error` is retryable according to the configured classifier
+    pub fn is_retryable(&self, error: &McpError) -> bool {
+        self.retry_classifier.is_retryable(error)
+    }
+
+    /// Set how tool calls treat servers marked unhealthy or disconnected
+    pub fn with_unhealthy_policy(mut self, policy: UnhealthyPolicy) -> Self {
+        self.unhealthy_policy = policy;
+        self
+    }
+
+    /// Set how long to collect `list_changed` notifications before refreshing
+    pub fn with_list_changed_debounce(mut self, window: Duration) -> Self {
+        self.list_changed_debounce = window;
+        self
+    }
+
+    /// Coalesce sandbox notifications sent within `window` of each other.
+    ///
+    /// Only the latest state is delivered to each server once the window
+    /// has elapsed without a newer update.
+    pub fn with_notification_debounce(mut self, window: Duration) -> Self {
+        self.notification_debounce = Some(window);
+        self
+    }
+
+    /// Connect to an MCP server.
+    ///
+    /// Connects to the same id are serialized. If the server is already
+    /// connected by the time this one gets its turn, nothing is spawned and
+    /// the existing connection is kept; use `update_server` to replace it.
     pub async fn connect(&self, config: McpServerConfig) -> Result<(), McpError> {
         let server_id = config.id.clone();
+        let lock = self.connect_lock(&server_id);
+        let _guard = lock.lock().await;
+        if self.connections.read().contains_key(&server_id) {
+            debug!(server_id = %server_id, "Already connected");
+            return Ok(());
+        }
         
         info!(server_id = %server_id, "Connecting to MCP server");
         
-        let connection = McpConnection::new(config).await?;
-        let connection = Arc::new(connection);
+        let connection = self.establish(config).await?;
+        
+        // Store connection
+        self.install_primary(&server_id, connection);
+        self.start_standby(&server_id).await;
+        
+        info!(server_id = %server_id, "Connected to MCP server");
+        Ok(())
+    }
+
+    /// Reconnect a server with a new config under the same id.
+    ///
+    /// The new config is connected first; only once it has initialized and
+    /// listed its tools does it replace the old connection. The id stays
+    /// routable throughout, and on failure the old connection is left
+    /// untouched.
+    ///
+    /// Calls started after the swap go to the new connection. Calls already
+    /// in flight on the old one can't move to it (it may be a different
+    /// process), so the old connection is given up to the drain timeout
+    /// (see `with_drain_timeout`) to finish them before it is shut down;
+    /// calls still running then fail with a transport error.
+    pub async fn update_server(&self, id: &str, mut new_config: McpServerConfig) -> Result<(), McpError> {
+        let lock = self.connect_lock(id);
+        let _guard = lock.lock().await;
+        if !self.connections.read().contains_key(id) {
+            return Err(McpError::ServerNotFound(id.to_string()));
+        }
+        new_config.id = id.to_string();
+        
+        info!(server_id = %id, "Updating MCP server config");
+        
+        let connection = self.establish(new_config).await?;
+        
+        if let Some(old) = self.install_primary(id, connection) {
+            if !old.drain(self.drain_timeout).await {
+                warn!(
+                    server_id = %id,
+                    in_flight = old.in_flight_requests().len(),
+                    "Replaced connection did not drain in time"
+                );
+            }
+            if let Err(e) = old.shutdown().await {
+                warn!(server_id = %id, error = %e, "Failed to shut down replaced connection");
+            }
+        }
+        // The standby still runs the old config
+        if let Some((_, standby)) = self.standbys.lock().remove(id) {
+            let _ = standby.shutdown().await;
+        }
+        self.start_standby(id).await;
+        
+        info!(server_id = %id, "Updated MCP server config");
+        Ok(())
+    }
+
+    /// Promote the standby of `server_id` to primary and start a new standby.
+    ///
+    /// The old primary is shut down. Called by `health_check` when the
+    /// primary fails; fails with `ServerUnhealthy` if there's no standby, or
+    /// `TaskBudgetExhausted` (keeping the standby) if its background tasks
+    /// don't fit the budget.
+    pub async fn promote_standby(&self, server_id: &str) -> Result<(), McpError> {
+        let lock = self.connect_lock(server_id);
+        let _guard = lock.lock().await;
+        
+        if !self.standbys.lock().contains_key(server_id) {
+            return Err(McpError::ServerUnhealthy(server_id.to_string()));
+        }
+        let permits = self.tasks.reserve(TASKS_PER_CONNECTION)?;
+        let Some((instance, standby)) = self.standbys.lock().remove(server_id) else {
+            return Err(McpError::ServerUnhealthy(server_id.to_string()));
+        };
+        
+        warn!(server_id = %server_id, instance, "Promoting standby connection");
+        self.spawn_connection_tasks(server_id, &standby, permits);
+        let old = self.connections.write().insert(server_id.to_string(), standby);
+        self.primaries.lock().insert(server_id.to_string(), instance);
+        self.health.write().insert(server_id.to_string(), ServerHealth::Healthy);
+        self.resource_listings.write().remove(server_id);
+        
+        if let Some(old) = old {
+            if let Err(e) = old.shutdown().await {
+                debug!(server_id = %server_id, error = %e, "Error shutting down failed primary");
+            }
+        }
+        // The standby wasn't watched for list changes while idle
+        if let Err(e) = self.refresh_tools(server_id).await {
+            warn!(server_id = %server_id, error = %e, "Failed to list tools of promoted standby");
+        }
+        self.start_standby(server_id).await;
+        Ok(())
+    }
+
+    /// Instance number of the connection currently serving `server_id`.
+    ///
+    /// Every connection the manager establishes gets a new number, so a
+    /// change means the server was reconnected, updated or failed over.
+    pub fn primary_instance(&self, server_id: &str) -> Option<u64> {
+        self.primaries.lock().get(server_id).copied()
+    }
+
+    /// Instance number of the warm standby for `server_id`, if one is ready
+    pub fn standby_instance(&self, server_id: &str) -> Option<u64> {
+        self.standbys.lock().get(server_id).map(|(instance, _)| *instance)
+    }
+
+    /// Make `connection` the primary for `server_id`, returning the one it replaced
+    fn install_primary(&self, server_id: &str, connection: Arc<McpConnection>) -> Option<Arc<McpConnection>> {
+        let instance = self.next_instance.fetch_add(1, Ordering::Relaxed);
+        let old = self.connections.write().insert(server_id.to_string(), connection);
+        self.primaries.lock().insert(server_id.to_string(), instance);
+        self.health.write().insert(server_id.to_string(), ServerHealth::Healthy);
+        self.resource_listings.write().remove(server_id);
+        old
+    }
+
+    /// Establish a standby for `server_id` if it should have one and doesn't.
+    ///
+    /// The standby is only initialized: it gets no background tasks and its
+    /// tools aren't listed until `promote_standby` installs it. Failure is
+    /// logged, not returned; the next health check retries.
+    async fn start_standby(&self, server_id: &str) {
+        if !self.standby_servers.contains(server_id) || self.standbys.lock().contains_key(server_id) {
+            return;
+        }
+        let Some(config) = self.get_connection(server_id).map(|c| c.config().clone()) else {
+            return;
+        };
+        
+        let standby = async {
+            let connection = self.build_connection(config).await?;
+            if let Err(e) = self.initialize_connection(&connection).await {
+                return Err(abort_connect(server_id, &connection, ConnectPhase::Initialize, e).await);
+            }
+            Ok(connection)
+        };
+        match standby.await {
+            Ok(standby) => {
+                let instance = self.next_instance.fetch_add(1, Ordering::Relaxed);
+                debug!(server_id = %server_id, instance, "Standby connection ready");
+                self.standbys.lock().insert(server_id.to_string(), (instance, standby));
+            }
+            Err(e) => warn!(server_id = %server_id, error = %e, "Failed to start standby connection"),
+        }
+    }
+
+    /// Lock held while connecting or updating `server_id`
+    fn connect_lock(&self, server_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.connect_locks.lock()
+            .entry(server_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Forget the connect lock of `server_id` unless someone else is waiting on it
+    fn release_connect_lock(&self, server_id: &str, lock: &Arc<tokio::sync::Mutex<()>>) {
+        let mut locks = self.connect_locks.lock();
+        // One reference is the map's, one the caller's
+        if locks.get(server_id).is_some_and(|held| Arc::ptr_eq(held, lock)) && Arc::strong_count(lock) == 2 {
+            locks.remove(server_id);
+        }
+    }
+
+    /// Build, initialize and discover a connection without registering it
+    async fn establish(&self, config: McpServerConfig) -> Result<Arc<McpConnection>, McpError> {
+        let server_id = config.id.clone();
+        let permits = self.tasks.reserve(TASKS_PER_CONNECTION)?;
+        let connection = self.build_connection(config).await?;
+        
+        // Subscribe before initializing so no early notification is missed
+        self.spawn_connection_tasks(&server_id, &connection, permits);
         
         // Initialize connection
-        connection.initialize().await?;
+        if let Err(e) = self.initialize_connection(&connection).await {
+            return Err(abort_connect(&server_id, &connection, ConnectPhase::Initialize, e).await);
+        }
+        
+        // Skip discovery if the server is unchanged since its tools were saved
+        if let Some(tools) = self.cached_schemas(&server_id, &connection).await {
+            debug!(server_id = %server_id, num_tools = tools.len(), "Using persisted tool schemas");
+            let ticket = self.tool_cache.ticket();
+            self.tool_cache.store(&server_id, ticket, tools);
+            self.spawn_background_refresh(&server_id, &connection);
+            return Ok(connection);
+        }
         
         // Discover tools
-        let tools = connection.list_tools().await?;
+        let ticket = self.tool_cache.ticket();
+        let tools = match tokio::time::timeout(self.discovery_timeout, connection.list_tools()).await {
+            Ok(Ok(tools)) => tools,
+            Ok(Err(e)) => {
+                return Err(abort_connect(&server_id, &connection, ConnectPhase::Discovery, e).await);
+            }
+            Err(_) => {
+                let e = McpError::Timeout;
+                return Err(abort_connect(&server_id, &connection, ConnectPhase::Discovery, e).await);
+            }
+        };
         
-        // Store connection and tools
-        self.connections.write().insert(server_id.clone(), connection);
-        self.tool_cache.write().insert(server_id.clone(), tools);
-        self.health.write().insert(server_id.clone(), ServerHealth::Healthy);
+        persist_schemas(self.schema_cache.as_ref(), &server_id, &connection, &tools).await;
+        self.tool_cache.store(&server_id, ticket, tools);
+        Ok(connection)
+    }
+
+    /// Create a connection with the manager's settings, not yet started
+    async fn build_connection(&self, config: McpServerConfig) -> Result<Arc<McpConnection>, McpError> {
+        let server_id = config.id.clone();
+        let connection = McpConnection::new(config).await?
+            .with_transport_options(self.transport_options.clone())
+            .with_transport_factory(self.transport_factory.clone())
+            .with_max_list_pages(self.max_list_pages);
+        let connection = match self.resource_cache_ttl {
+            Some(ttl) => connection.with_resource_cache(ttl),
+            None => connection,
+        };
+        let connection = match self.protocol_versions.get(&server_id) {
+            Some(version) => connection.with_protocol_version(version.clone()),
+            None => connection,
+        };
+        let connection = match self.max_result_size {
+            Some(max) => connection.with_max_result_size(max),
+            None => connection,
+        };
+        let connection = match self.max_argument_size {
+            Some(max) => connection.with_max_argument_size(max),
+            None => connection,
+        };
+        let connection = connection.with_invocation_history(self.invocation_history);
+        let connection = match &self.id_prefix {
+            Some(prefix) => connection.with_id_prefix(prefix.clone()),
+            None => connection,
+        };
+        let connection = match &self.reconnect_limiter {
+            Some(limiter) => connection.with_reconnect_limiter(limiter.clone()),
+            None => connection,
+        };
+        let connection = match &self.reconnect_policy {
+            Some(policy) => connection.with_reconnect_policy(policy.clone()),
+            None => connection,
+        };
+        let connection = if self.omit_empty_params {
+            connection.with_empty_params_omitted()
+        } else {
+            connection
+        };
+        Ok(connection
+            .with_request_timeout(self.request_timeout)
+            .with_shutdown_grace(self.shutdown_grace)
+            .into_shared())
+    }
+
+    /// Start the manager's per-connection tasks, one permit each
+    fn spawn_connection_tasks(&self, server_id: &str, connection: &Arc<McpConnection>, mut permits: Vec<TaskPermit>) {
+        self.spawn_log_forwarder(server_id, connection, permits.remove(0));
+        self.spawn_resource_update_forwarder(server_id, connection, permits.remove(0));
+        self.spawn_list_changed_watcher(server_id, connection, permits.remove(0));
+    }
+
+    /// Persisted tools for a freshly initialized connection, if still valid
+    async fn cached_schemas(&self, server_id: &str, connection: &McpConnection) -> Option<Vec<ToolSchema>> {
+        let cache = self.schema_cache.as_ref()?;
+        let token = validity_token(&connection.server_info().await?)?;
+        cache.load(server_id, &token)
+    }
+
+    /// Run a background task holding `permit`, on the reader pool if there is one.
+    ///
+    /// The task ends early if the manager is dropped.
+    fn spawn_task(&self, permit: TaskPermit, task: impl std::future::Future<Output = ()> + Send + 'static) {
+        let dropped = self.dropped.clone();
+        spawn_on(self.transport_options.reader_pool.as_ref(), async move {
+            let _permit = permit;
+            tokio::select! {
+                _ = task => {}
+                _ = dropped.cancelled() => {}
+            }
+        });
+    }
+
+    /// Number of background tasks the manager is running, see `with_task_budget`
+    pub fn background_tasks(&self) -> usize {
+        self.tasks.live()
+    }
+
+    /// Replace persisted tools with a live listing once the connection is up
+    fn spawn_background_refresh(&self, server_id: &str, connection: &Arc<McpConnection>) {
+        let connection = Arc::downgrade(connection);
+        let tool_cache = self.tool_cache.clone();
+        let schema_cache = self.schema_cache.clone();
+        let server_id = server_id.to_string();
+
+        self.spawn_task(self.tasks.force(), async move {
+            let Some(connection) = connection.upgrade() else {
+                return;
+            };
+            let ticket = tool_cache.ticket();
+            match connection.list_tools().await {
+                Ok(tools) => {
+                    persist_schemas(schema_cache.as_ref(), &server_id, &connection, &tools).await;
+                    tool_cache.store(&server_id, ticket, tools);
+                }
+                Err(e) => warn!(server_id = %server_id, error = %e, "Background tool refresh failed"),
+            }
+        });
+    }
+
+    /// Run the handshake and bring a late joiner up to date with the sandbox state
+    async fn initialize_connection(&self, connection: &McpConnection) -> Result<(), McpError> {
+        connection.initialize().await?;
         
-        info!(server_id = %server_id, "Connected to MCP server");
+        // The connection replays this itself on reconnect
+        let sandbox_state = self.sandbox_state.lock().clone();
+        if let Some((enabled, policy)) = sandbox_state {
+            connection.notify_sandbox_state(enabled, &policy).await?;
+        }
+        Ok(())
+    }
+
+    /// Connect to several servers concurrently.
+    ///
+    /// Returns each server id with the outcome of its connect, in input order;
+    /// one server failing doesn't affect the others. At most
+    /// `with_connect_concurrency` connects run at once, the rest wait their
+    /// turn. If `cancel` fires, connects still in progress or waiting are
+    /// abandoned (their servers are shut down) and fail with
+    /// `McpError::Cancelled`; servers that already connected stay registered.
+    pub async fn connect_all(
+        &self,
+        configs: Vec<McpServerConfig>,
+        cancel: Option<CancellationToken>,
+    ) -> Vec<(String, Result<(), McpError>)> {
+        let limit = self.connect_concurrency.map(tokio::sync::Semaphore::new);
+        let connects = configs.into_iter().map(|config| {
+            let cancel = cancel.clone();
+            let limit = limit.as_ref();
+            async move {
+                let server_id = config.id.clone();
+                let connect = async {
+                    let _permit = match limit {
+                        Some(limit) => limit.acquire().await.ok(),
+                        None => None,
+                    };
+                    self.connect(config).await
+                };
+                (server_id, cancellable(cancel.as_ref(), connect).await)
+            }
+        });
+        futures::future::join_all(connects).await
+    }
+
+    /// Refresh the tool cache of every connected server concurrently.
+    ///
+    /// Returns each server id with its refreshed tools. If `cancel` fires,
+    /// refreshes still in progress fail with `McpError::Cancelled` and their
+    /// cached tools are left as they were.
+    pub async fn refresh_all_tools(
+        &self,
+        cancel: Option<CancellationToken>,
+    ) -> Vec<(String, Result<Vec<ToolSchema>, McpError>)> {
+        let refreshes = self.server_ids().into_iter().map(|server_id| {
+            let cancel = cancel.clone();
+            async move {
+                let result = cancellable(cancel.as_ref(), self.refresh_tools(&server_id)).await;
+                (server_id, result)
+            }
+        });
+        futures::future::join_all(refreshes).await
+    }
+
+    /// Disconnect every server, one at a time.
+    ///
+    /// If `cancel` fires, the disconnect in progress completes but no further
+    /// ones are started; the remaining servers stay connected and are
+    /// reported as `McpError::Cancelled`.
+    pub async fn disconnect_all(
+        &self,
+        cancel: Option<CancellationToken>,
+    ) -> Vec<(String, Result<(), McpError>)> {
+        let mut outcomes = Vec::new();
+        for server_id in self.server_ids() {
+            let result = match &cancel {
+                Some(cancel) if cancel.is_cancelled() => Err(McpError::Cancelled),
+                _ => self.disconnect(&server_id).await,
+            };
+            outcomes.push((server_id, result));
+        }
+        outcomes
+    }
+
+    /// Load server configs from a file (see [`crate::config`]) and connect to them all.
+    ///
+    /// Disabled servers are not connected and don't appear in the outcomes;
+    /// they are registered so `enable_server` can connect them later.
+    pub async fn connect_from_file(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Vec<(String, Result<(), McpError>)>, McpError> {
+        let configs = crate::config::load_server_configs(path)?;
+        for config in configs.disabled {
+            self.add_disabled(config);
+        }
+        Ok(self.connect_all(configs.enabled, None).await)
+    }
+
+    /// Register a server as configured but disabled
+    pub fn add_disabled(&self, config: McpServerConfig) {
+        debug!(server_id = %config.id, "Server configured but disabled");
+        self.disabled.write().insert(config.id.clone(), config);
+    }
+
+    /// IDs of servers configured but disabled
+    pub fn disabled_servers(&self) -> Vec<String> {
+        self.disabled.read().keys().cloned().collect()
+    }
+
+    /// Connect a disabled server.
+    ///
+    /// If the connect fails the server stays registered as disabled.
+    pub async fn enable_server(&self, server_id: &str) -> Result<(), McpError> {
+        let config = self.disabled.read().get(server_id).cloned()
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        
+        self.connect(config).await?;
+        self.disabled.write().remove(server_id);
         Ok(())
     }
 
-    /// Disconnect from an MCP server
+    /// Disconnect from an MCP server.
+    ///
+    /// Waits for a connect, update or failover of the same id in progress.
     pub async fn disconnect(&self, server_id: &str) -> Result<(), McpError> {
+        let lock = self.connect_lock(server_id);
+        let _guard = lock.lock().await;
         let connection = self.connections.write().remove(server_id);
+        self.primaries.lock().remove(server_id);
+        let standby = self.standbys.lock().remove(server_id);
         
-        if let Some(conn) = connection {
-            conn.shutdown().await?;
+        if let Some((_, standby)) = standby {
+            let _ = standby.shutdown().await;
         }
+        let shutdown = match connection {
+            Some(conn) => conn.shutdown().await,
+            None => Ok(()),
+        };
         
-        self.tool_cache.write().remove(server_id);
+        self.tool_cache.remove(server_id);
+        self.resource_listings.write().remove(server_id);
         self.health.write().remove(server_id);
+        self.pending_sandbox.lock().remove(server_id);
+        self.dropped_seen.lock().remove(server_id);
+        self.statuses.lock().remove(server_id);
+        self.release_connect_lock(server_id, &lock);
+        shutdown?;
         
         info!(server_id = %server_id, "Disconnected from MCP server");
         Ok(())
@@ -79,10 +1035,68 @@ impl McpManager {
         self.connections.read().keys().cloned().collect()
     }
 
-    /// List all available tools across all servers
+    /// List all available tools across all servers.
+    ///
+    /// With `with_qualified_tool_names`, names are `server::tool`.
     pub fn list_tools(&self) -> Vec<ToolSchema> {
+        self.list_tools_filtered(|_| true)
+    }
+
+    /// List tools across all servers that satisfy `predicate`.
+    ///
+    /// `predicate` sees the tool's own name, even when listed names are qualified.
+    pub fn list_tools_filtered<F>(&self, predicate: F) -> Vec<ToolSchema>
+    where
+        F: Fn(&ToolSchema) -> bool,
+    {
+        let cache = self.tool_cache.read();
+        cache.iter()
+            .flat_map(|(server_id, tools)| tools.iter().map(move |tool| (server_id, tool)))
+            .filter(|(_, tool)| predicate(tool))
+            .map(|(server_id, tool)| match self.qualify_tool_names {
+                true => ToolSchema { name: qualified_name(server_id, &tool.name), ..tool.clone() },
+                false => tool.clone(),
+            })
+            .collect()
+    }
+
+    /// List tools that declare themselves read-only
+    pub fn list_read_only_tools(&self) -> Vec<ToolSchema> {
+        self.list_tools_filtered(ToolSchema::is_read_only)
+    }
+
+    /// List tools that are not (potentially) destructive
+    pub fn list_non_destructive_tools(&self) -> Vec<ToolSchema> {
+        self.list_tools_filtered(|t| !t.is_destructive())
+    }
+
+    /// List at most `limit` tools, most relevant to `hint` first.
+    ///
+    /// Each whitespace-separated term in `hint` is matched case-insensitively
+    /// against tool names (exact, prefix, substring, then fuzzy subsequence)
+    /// and descriptions. Tools matching no term are omitted unless `hint` is
+    /// empty. Ties are broken by tool name, then server id, so results are
+    /// stable across calls.
+    pub fn list_tools_ranked(&self, limit: usize, hint: &str) -> Vec<(String, ToolSchema)> {
+        let terms: Vec<String> = hint.split_whitespace().map(str::to_lowercase).collect();
+        
         let cache = self.tool_cache.read();
-        cache.values().flatten().cloned().collect()
+        let mut ranked: Vec<(u32, &String, &ToolSchema)> = cache.iter()
+            .flat_map(|(server_id, tools)| tools.iter().map(move |tool| (server_id, tool)))
+            .map(|(server_id, tool)| (relevance(tool, &terms), server_id, tool))
+            .filter(|(score, _, _)| terms.is_empty() || *score > 0)
+            .collect();
+        
+        ranked.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.2.name.cmp(&b.2.name))
+                .then_with(|| a.1.cmp(b.1))
+        });
+        
+        ranked.into_iter()
+            .take(limit)
+            .map(|(_, server_id, tool)| (server_id.clone(), tool.clone()))
+            .collect()
     }
 
     /// List tools from a specific server
@@ -93,15 +1107,131 @@ impl McpManager {
             .unwrap_or_default()
     }
 
-    /// Find a tool by name (returns server_id, tool_name)
-    pub fn find_tool(&self, name: &str) -> Option<(String, ToolSchema)> {
+    /// Check every cached tool's input schema, e.g. as a startup sanity check.
+    ///
+    /// Returns `(server_id, tool_name, problems)` for each tool whose schema
+    /// has problems, sorted by server and tool. Sound tools are omitted.
+    pub fn validate_all_schemas(&self) -> Vec<(String, String, Vec<SchemaProblem>)> {
+        let cache = self.tool_cache.read();
+        let mut report: Vec<_> = cache.iter()
+            .flat_map(|(server_id, tools)| tools.iter().map(move |tool| (server_id, tool)))
+            .filter_map(|(server_id, tool)| {
+                let problems = check_schema(&tool.input_schema);
+                (!problems.is_empty()).then(|| (server_id.clone(), tool.name.clone(), problems))
+            })
+            .collect();
+        report.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        report
+    }
+
+    /// Start building arguments for a cached tool, validated against its schema
+    pub fn tool_arguments(&self, server_id: &str, tool_name: &str) -> Result<Arguments, McpError> {
+        self.list_server_tools(server_id)
+            .iter()
+            .find(|t| t.name == tool_name)
+            .map(Arguments::for_tool)
+            .ok_or_else(|| McpError::ToolNotFound(tool_name.to_string()))
+    }
+
+    /// Export every cached tool as a provider function-calling definition.
+    ///
+    /// Names are prefixed with the server id (see [`function_name`]) so tools
+    /// from different servers don't collide. Use `resolve_function_name` to
+    /// map a name chosen by the model back to a server and tool.
+    pub fn function_schemas(&self, format: FunctionFormat) -> Vec<serde_json::Value> {
+        let cache = self.tool_cache.read();
+        let mut server_ids: Vec<_> = cache.keys().collect();
+        server_ids.sort();
+
+        server_ids.into_iter()
+            .flat_map(|server_id| {
+                cache[server_id].iter().map(move |tool| {
+                    tool.to_function_schema_named(&function_name(server_id, &tool.name), format)
+                })
+            })
+            .collect()
+    }
+
+    /// Map a name produced by `function_schemas` back to (server_id, tool_name).
+    ///
+    /// Returns None if the name is unknown, or if it somehow belongs to more
+    /// than one tool rather than guessing between them.
+    pub fn resolve_function_name(&self, name: &str) -> Option<(String, String)> {
+        let cache = self.tool_cache.read();
+        let mut matches = cache.iter().flat_map(|(server_id, tools)| {
+            tools.iter()
+                .filter(move |t| function_name(server_id, &t.name) == name)
+                .map(move |t| (server_id.clone(), t.name.clone()))
+        });
+        let found = matches.next()?;
+        if let Some(other) = matches.next() {
+            warn!(name = %name, first = ?found, second = ?other, "Function name matches several tools");
+            return None;
+        }
+        Some(found)
+    }
+
+    /// Map a qualified name (`server::tool`) back to a cached server and tool
+    pub fn resolve_qualified_name(&self, name: &str) -> Option<(String, String)> {
+        self.find_qualified_tool(name).map(|found| (found.server_id, found.tool.name))
+    }
+
+    /// Find a tool by qualified (`server::tool`) or plain name.
+    ///
+    /// A plain name exposed by several servers matches the one with the
+    /// lowest server id; use `resolve_tool` to treat that as an error.
+    pub fn find_tool(&self, name: &str) -> Option<QualifiedTool> {
+        if let Some(tool) = self.find_qualified_tool(name) {
+            return Some(tool);
+        }
+        self.find_all_tools(name)
+            .into_iter()
+            .next()
+            .map(|(server_id, tool)| QualifiedTool { server_id, tool })
+    }
+
+    /// Every server exposing a tool named `name`, sorted by server id
+    pub fn find_all_tools(&self, name: &str) -> Vec<(String, ToolSchema)> {
         let cache = self.tool_cache.read();
-        for (server_id, tools) in cache.iter() {
-            if let Some(tool) = tools.iter().find(|t| t.name == name) {
-                return Some((server_id.clone(), tool.clone()));
+        let mut matches: Vec<_> = cache.iter()
+            .filter_map(|(server_id, tools)| {
+                tools.iter().find(|t| t.name == name).map(|tool| (server_id.clone(), tool.clone()))
+            })
+            .collect();
+        matches.sort_by(|a, b| a.0.cmp(&b.0));
+        matches
+    }
+
+    /// Resolve a qualified or plain tool name to the single server exposing it.
+    ///
+    /// Fails with `McpError::AmbiguousTool` if a plain name is exposed by
+    /// more than one server, and `McpError::ToolNotFound` if by none.
+    pub fn resolve_tool(&self, name: &str) -> Result<QualifiedTool, McpError> {
+        if let Some(tool) = self.find_qualified_tool(name) {
+            return Ok(tool);
+        }
+        let mut matches = self.find_all_tools(name);
+        match matches.len() {
+            0 => Err(McpError::ToolNotFound(name.to_string())),
+            1 => {
+                let (server_id, tool) = matches.remove(0);
+                Ok(QualifiedTool { server_id, tool })
             }
+            _ => Err(McpError::AmbiguousTool {
+                name: name.to_string(),
+                servers: matches.into_iter().map(|(server_id, _)| server_id).collect(),
+            }),
         }
-        None
+    }
+
+    /// Look up a `server::tool` name in the tool cache
+    fn find_qualified_tool(&self, name: &str) -> Option<QualifiedTool> {
+        let (server_id, tool_name) = name.split_once(QUALIFIED_NAME_SEPARATOR)?;
+        self.tool_cache.read()
+            .get(server_id)?
+            .iter()
+            .find(|t| t.name == tool_name)
+            .map(|tool| QualifiedTool { server_id: server_id.to_string(), tool: tool.clone() })
     }
 
     /// Call a tool on a specific server
@@ -110,65 +1240,876 @@ impl McpManager {
         server_id: &str,
         tool_name: &str,
         arguments: serde_json::Value,
-    ) -> Result<serde_json::Value, McpError> {
+    ) -> Result<Vec<ToolContent>, McpError> {
         let connection = self.get_connection(server_id)
             .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
         
+        self.check_tool_allowed(server_id, tool_name)?;
+        self.check_arguments(server_id, tool_name, &arguments)?;
+        self.apply_unhealthy_policy(server_id).await?;
         connection.call_tool(tool_name, arguments).await
     }
 
-    /// Get health status of a server
-    pub fn server_health(&self, server_id: &str) -> Option<ServerHealth> {
-        self.health.read().get(server_id).cloned()
+    /// Call a tool by qualified or plain name, routed per `resolve_tool`
+    pub async fn call_tool_by_name(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<Vec<ToolContent>, McpError> {
+        let QualifiedTool { server_id, tool } = self.resolve_tool(name)?;
+        self.call_tool(&server_id, &tool.name, arguments).await
     }
 
-    /// Refresh tools from a server
-    pub async fn refresh_tools(&self, server_id: &str) -> Result<Vec<ToolSchema>, McpError> {
-        let connection = self.get_connection(server_id)
-            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
-        
-        let tools = connection.list_tools().await?;
-        self.tool_cache.write().insert(server_id.to_string(), tools.clone());
-        
+    /// Fail with `ToolNotAllowed` if `server_id`'s tool policy blocks `tool_name`
+    fn check_tool_allowed(&self, server_id: &str, tool_name: &str) -> Result<(), McpError> {
+        if self.tool_cache.allows(server_id, tool_name) {
+            return Ok(());
+        }
+        Err(McpError::ToolNotAllowed {
+            server_id: server_id.to_string(),
+            tool: tool_name.to_string(),
+        })
+    }
+
+    /// Fail with `InvalidArguments` if validation is on and `arguments` don't fit the cached schema
+    fn check_arguments(&self, server_id: &str, tool_name: &str, arguments: &serde_json::Value) -> Result<(), McpError> {
+        if !self.validate_arguments {
+            return Ok(());
+        }
+        let cache = self.tool_cache.read();
+        let Some(tool) = cache.get(server_id).and_then(|tools| tools.iter().find(|t| t.name == tool_name)) else {
+            return Ok(());
+        };
+        let errors = crate::validation::validate_arguments(&tool.input_schema, arguments);
+        if !errors.is_empty() {
+            return Err(McpError::InvalidArguments { errors });
+        }
+        Ok(())
+    }
+
+    /// Call a tool on the first candidate server able to handle it.
+    ///
+    /// Candidates are tried in order. Servers that aren't connected or are
+    /// known to be degraded are skipped, and a failure the retry classifier
+    /// considers transient moves on to the next candidate. Any other error is
+    /// returned immediately, since another server would likely fail the same
+    /// way. Returns the id of the server that handled the call with its
+    /// result, or `McpError::FailoverExhausted` listing every attempt.
+    pub async fn call_tool_with_failover(
+        &self,
+        tool_name: &str,
+        arguments: serde_json::Value,
+        candidates: &[&str],
+    ) -> Result<(String, Vec<ToolContent>), McpError> {
+        let mut attempts = Vec::new();
+        
+        for &server_id in candidates {
+            let Some(connection) = self.get_connection(server_id) else {
+                attempts.push((server_id.to_string(), "not connected".to_string()));
+                continue;
+            };
+            if self.server_health(server_id).is_some_and(|h| h.is_degraded()) {
+                attempts.push((server_id.to_string(), "unhealthy".to_string()));
+                continue;
+            }
+            if let Err(e) = self.check_tool_allowed(server_id, tool_name) {
+                attempts.push((server_id.to_string(), e.to_string()));
+                continue;
+            }
+            self.check_arguments(server_id, tool_name, &arguments)?;
+            
+            match connection.call_tool(tool_name, arguments.clone()).await {
+                Ok(result) => {
+                    debug!(server_id = %server_id, tool = %tool_name, "Failover call handled");
+                    return Ok((server_id.to_string(), result));
+                }
+                Err(e) if self.is_retryable(&e) => {
+                    warn!(server_id = %server_id, tool = %tool_name, error = %e, "Failing over");
+                    attempts.push((server_id.to_string(), e.to_string()));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        
+        Err(McpError::FailoverExhausted { attempts })
+    }
+
+    /// Gate dispatch to `server_id` according to the unhealthy policy.
+    ///
+    /// Health is only as fresh as the last `health_check`, so
+    /// `WaitForHealthy` relies on something running health checks meanwhile.
+    async fn apply_unhealthy_policy(&self, server_id: &str) -> Result<(), McpError> {
+        let is_degraded = || self.server_health(server_id).is_some_and(|h| h.is_degraded());
+        
+        match self.unhealthy_policy {
+            UnhealthyPolicy::AllowAnyway => Ok(()),
+            UnhealthyPolicy::RejectUnhealthy => {
+                if is_degraded() {
+                    return Err(McpError::ServerUnhealthy(server_id.to_string()));
+                }
+                Ok(())
+            }
+            UnhealthyPolicy::WaitForHealthy(timeout) => {
+                let deadline = tokio::time::Instant::now() + timeout;
+                while is_degraded() {
+                    if tokio::time::Instant::now() >= deadline {
+                        return Err(McpError::ServerUnhealthy(server_id.to_string()));
+                    }
+                    tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Call a tool on a specific server and return its typed result
+    pub async fn call_tool_result(
+        &self,
+        server_id: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<ToolCallResult, McpError> {
+        let connection = self.get_connection(server_id)
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        
+        self.check_tool_allowed(server_id, tool_name)?;
+        self.check_arguments(server_id, tool_name, &arguments)?;
+        self.apply_unhealthy_policy(server_id).await?;
+        connection.call_tool_result(tool_name, arguments).await
+    }
+
+    /// Call a tool and deserialize its structured result into `T`
+    pub async fn call_tool_as<T: serde::de::DeserializeOwned>(
+        &self,
+        server_id: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<T, McpError> {
+        let connection = self.get_connection(server_id)
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        
+        self.check_tool_allowed(server_id, tool_name)?;
+        self.check_arguments(server_id, tool_name, &arguments)?;
+        self.apply_unhealthy_policy(server_id).await?;
+        connection.call_tool_as(tool_name, arguments).await
+    }
+
+    /// Validate a tool call without sending it.
+    ///
+    /// Confirms the server is connected and not known to be unhealthy, that it
+    /// exposes `tool_name`, and that `arguments` satisfy the tool's input schema.
+    /// Nothing is sent to the server.
+    pub fn dry_run_call(
+        &self,
+        server_id: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<DryRunReport, McpError> {
+        if self.get_connection(server_id).is_none() {
+            return Err(McpError::ServerNotFound(server_id.to_string()));
+        }
+        self.check_tool_allowed(server_id, tool_name)?;
+
+        let tool = self.list_server_tools(server_id)
+            .into_iter()
+            .find(|t| t.name == tool_name)
+            .ok_or_else(|| McpError::ToolNotFound(tool_name.to_string()))?;
+
+        let health = self.server_health(server_id).unwrap_or_default();
+        if health.is_degraded() {
+            return Err(McpError::ServerUnhealthy(server_id.to_string()));
+        }
+
+        let errors = crate::validation::validate_arguments(&tool.input_schema, &arguments);
+        if !errors.is_empty() {
+            return Err(McpError::InvalidArguments { errors });
+        }
+
+        Ok(DryRunReport {
+            server_id: server_id.to_string(),
+            tool,
+            health,
+            params: serde_json::json!({
+                "name": tool_name,
+                "arguments": arguments
+            }),
+        })
+    }
+
+    /// Resources a server exposes.
+    ///
+    /// The listing is fetched on first use and cached until the server
+    /// announces a change or is reconnected. Servers that didn't advertise
+    /// the resources capability list none, without being asked.
+    pub async fn list_resources(&self, server_id: &str) -> Result<Vec<ResourceSchema>, McpError> {
+        let connection = self.get_connection(server_id)
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        if let Some(resources) = self.resource_listings.read().get(server_id) {
+            return Ok(resources.clone());
+        }
+        if !supports_resources(&connection).await {
+            return Ok(Vec::new());
+        }
+        
+        let resources = connection.list_resources().await?;
+        self.resource_listings.write().insert(server_id.to_string(), resources.clone());
+        Ok(resources)
+    }
+
+    /// Read a resource from a specific server.
+    ///
+    /// Fails with `McpError::Unsupported` if the server didn't advertise
+    /// the resources capability.
+    pub async fn read_resource(
+        &self,
+        server_id: &str,
+        uri: &str,
+    ) -> Result<Vec<ResourceContents>, McpError> {
+        let connection = self.get_connection(server_id)
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        connection.read_resource(uri).await
+    }
+
+    /// Set the minimum log level a server should send
+    pub async fn set_log_level(&self, server_id: &str, level: LogLevel) -> Result<(), McpError> {
+        let connection = self.get_connection(server_id)
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        
+        connection.set_log_level(level).await
+    }
+
+    /// Stream log messages from every connected server.
+    ///
+    /// Entries below a server's configured log level are filtered out even if
+    /// the server sends them anyway. If the consumer falls behind, the oldest
+    /// entries are skipped.
+    pub fn log_stream(&self) -> impl Stream<Item = ServerLogEntry> {
+        broadcast_stream(self.logs.subscribe())
+    }
+
+    /// Stream manager events.
+    ///
+    /// `ManagerEvent::ToolsChanged` carries the names added, removed and modified
+    /// whenever a server's cached tools change, whether through connect,
+    /// `refresh_tools`, a `list_changed` notification, invalidation or
+    /// disconnect. If the consumer falls behind, the oldest events are skipped.
+    pub fn event_stream(&self) -> impl Stream<Item = ManagerEvent> {
+        broadcast_stream(self.events.subscribe())
+    }
+
+    /// Forward a connection's `notifications/message` into the log channel
+    fn spawn_log_forwarder(&self, server_id: &str, connection: &Arc<McpConnection>, permit: TaskPermit) {
+        let mut incoming = connection.subscribe();
+        let connection = Arc::downgrade(connection);
+        let logs = self.logs.clone();
+        let server_id = server_id.to_string();
+
+        self.spawn_task(permit, async move {
+            loop {
+                let message = match incoming.recv().await {
+                    Ok(message) => message,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if message.get("method").and_then(|m| m.as_str()) != Some("notifications/message") {
+                    continue;
+                }
+                let Some(connection) = connection.upgrade() else {
+                    break;
+                };
+
+                let params = &message["params"];
+                let Ok(level) = serde_json::from_value::<LogLevel>(params["level"].clone()) else {
+                    warn!(server_id = %server_id, "Ignoring log message with invalid level");
+                    continue;
+                };
+                if connection.log_level().is_some_and(|min| level < min) {
+                    continue;
+                }
+
+                let _ = logs.send(ServerLogEntry {
+                    server_id: server_id.clone(),
+                    level,
+                    logger: params["logger"].as_str().map(String::from),
+                    data: params["data"].clone(),
+                });
+            }
+        });
+    }
+
+    /// Publish a connection's `notifications/resources/updated` as manager events
+    fn spawn_resource_update_forwarder(&self, server_id: &str, connection: &Arc<McpConnection>, permit: TaskPermit) {
+        let mut incoming = connection.subscribe();
+        let connection = Arc::downgrade(connection);
+        let events = self.events.clone();
+        let server_id = server_id.to_string();
+
+        self.spawn_task(permit, async move {
+            loop {
+                let message = match incoming.recv().await {
+                    Ok(message) => message,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if message.get("method").and_then(|m| m.as_str()) != Some("notifications/resources/updated") {
+                    continue;
+                }
+                if connection.strong_count() == 0 {
+                    break;
+                }
+                let Some(update) = ResourceUpdate::from_params(&message["params"]) else {
+                    warn!(server_id = %server_id, "Ignoring resource update without a URI");
+                    continue;
+                };
+
+                let _ = events.send(ManagerEvent::ResourceUpdated {
+                    server_id: server_id.clone(),
+                    update,
+                });
+            }
+        });
+    }
+
+    /// Refresh cached catalogs when the server announces they changed.
+    ///
+    /// Notifications arriving within the debounce window are coalesced so a
+    /// burst results in one refresh per catalog.
+    fn spawn_list_changed_watcher(&self, server_id: &str, connection: &Arc<McpConnection>, permit: TaskPermit) {
+        let mut incoming = connection.subscribe();
+        let mut reconnects = connection.events();
+        let connection = Arc::downgrade(connection);
+        let tool_cache = self.tool_cache.clone();
+        let resource_listings = self.resource_listings.clone();
+        let schema_cache = self.schema_cache.clone();
+        let events = self.events.clone();
+        let window = self.list_changed_debounce;
+        let server_id = server_id.to_string();
+
+        self.spawn_task(permit, async move {
+            // A reconnected server may differ in every catalog
+            let changed_kinds = |change: CatalogChange| match change {
+                CatalogChange::Listed(kind) => vec![kind],
+                CatalogChange::Reconnected => {
+                    info!(server_id = %server_id, "Server reconnected, refreshing catalogs");
+                    let _ = events.send(ManagerEvent::Reconnected { server_id: server_id.clone() });
+                    vec![ListKind::Tools, ListKind::Resources, ListKind::Prompts]
+                }
+            };
+
+            while let Some(change) = next_catalog_change(&mut incoming, &mut reconnects).await {
+                let mut changed: HashSet<_> = changed_kinds(change).into_iter().collect();
+
+                let quiet = tokio::time::sleep(window);
+                tokio::pin!(quiet);
+                loop {
+                    tokio::select! {
+                        _ = &mut quiet => break,
+                        next = next_catalog_change(&mut incoming, &mut reconnects) => match next {
+                            Some(change) => changed.extend(changed_kinds(change)),
+                            None => return,
+                        },
+                    }
+                }
+
+                let Some(connection) = connection.upgrade() else {
+                    return;
+                };
+                for kind in changed {
+                    match kind {
+                        ListKind::Tools => {
+                            let ticket = tool_cache.ticket();
+                            match connection.list_tools().await {
+                                Ok(tools) => {
+                                    debug!(server_id = %server_id, num_tools = tools.len(), "Tool list changed");
+                                    persist_schemas(schema_cache.as_ref(), &server_id, &connection, &tools).await;
+                                    tool_cache.store(&server_id, ticket, tools);
+                                }
+                                Err(e) => warn!(server_id = %server_id, error = %e, "Failed to refresh tools"),
+                            }
+                        }
+                        ListKind::Resources => {
+                            // Refetched on the next `list_resources`
+                            debug!(server_id = %server_id, "Resource list changed");
+                            resource_listings.write().remove(&server_id);
+                        }
+                        ListKind::Prompts => {
+                            // Nothing cached for this catalog yet
+                            debug!(server_id = %server_id, kind = ?kind, "Catalog changed");
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Get health status of a server
+    pub fn server_health(&self, server_id: &str) -> Option<ServerHealth> {
+        self.health.read().get(server_id).cloned()
+    }
+
+    /// Health of a server with the detail of recent health checks
+    pub fn server_status(&self, server_id: &str) -> Option<ServerStatus> {
+        let health = self.server_health(server_id)?;
+        let mut status = self.statuses.lock().get(server_id).cloned().unwrap_or_default();
+        status.health = health;
+        Some(status)
+    }
+
+    /// Request and tool call counters of every connected server
+    pub fn metrics(&self) -> McpMetricsSnapshot {
+        let servers = self.connections.read()
+            .iter()
+            .map(|(server_id, connection)| (server_id.clone(), connection.metrics()))
+            .collect();
+        McpMetricsSnapshot { servers }
+    }
+
+    /// Diagnostic snapshot of a server, including its initialize metadata
+    pub async fn diagnostics(&self, server_id: &str) -> Option<ServerDiagnostics> {
+        let connection = self.get_connection(server_id)?;
+        
+        Some(ServerDiagnostics {
+            server_id: server_id.to_string(),
+            health: self.server_health(server_id).unwrap_or_default(),
+            connected: connection.is_connected(),
+            server_info: connection.server_info().await,
+            tool_count: self.tool_cache.read().get(server_id).map_or(0, Vec::len),
+            exit_status: connection.exit_status(),
+            recent_stderr: connection.recent_stderr(),
+            traffic: connection.traffic(),
+        })
+    }
+
+    /// Tool count and last refresh time of every server in the tool cache
+    pub fn cache_stats(&self) -> Vec<ToolCacheStats> {
+        let cache = self.tool_cache.read();
+        let refreshed = self.tool_cache.refreshed.lock();
+        let mut stats: Vec<_> = cache.iter()
+            .map(|(server_id, tools)| ToolCacheStats {
+                server_id: server_id.clone(),
+                tool_count: tools.len(),
+                last_refresh: refreshed.get(server_id).copied(),
+            })
+            .collect();
+        stats.sort_by(|a, b| a.server_id.cmp(&b.server_id));
+        stats
+    }
+
+    /// Clear a server's cached tools without disconnecting it.
+    ///
+    /// With `refresh`, the tools are fetched again straight away; otherwise
+    /// the server lists no tools until the next refresh or `list_changed`.
+    pub async fn invalidate_tool_cache(&self, server_id: &str, refresh: bool) -> Result<(), McpError> {
+        if self.get_connection(server_id).is_none() {
+            return Err(McpError::ServerNotFound(server_id.to_string()));
+        }
+        
+        self.tool_cache.remove(server_id);
+        debug!(server_id = %server_id, "Invalidated tool cache");
+        
+        if refresh {
+            self.refresh_tools(server_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Requests awaiting a response on each server with any outstanding
+    pub fn in_flight_requests(&self) -> HashMap<String, Vec<InFlightRequest>> {
+        self.connections.read()
+            .iter()
+            .map(|(server_id, connection)| (server_id.clone(), connection.in_flight_requests()))
+            .filter(|(_, requests)| !requests.is_empty())
+            .collect()
+    }
+
+    /// Most recent tool calls on a server, oldest first
+    pub fn recent_invocations(&self, server_id: &str) -> Vec<ToolInvocation> {
+        self.get_connection(server_id)
+            .map(|c| c.recent_invocations())
+            .unwrap_or_default()
+    }
+
+    /// Most recent stderr output of a server, oldest line first
+    pub fn recent_stderr(&self, server_id: &str) -> Vec<String> {
+        self.get_connection(server_id)
+            .map(|c| c.recent_stderr())
+            .unwrap_or_default()
+    }
+
+    /// Reconnect a server and refresh its tools.
+    ///
+    /// Waits for the manager's reconnect rate limit, if one is configured.
+    pub async fn reconnect(&self, server_id: &str) -> Result<ServerInfo, McpError> {
+        let connection = self.get_connection(server_id)
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        
+        let info = match connection.reconnect().await {
+            Ok(info) => info,
+            Err(e) => {
+                self.health.write().insert(server_id.to_string(), ServerHealth::Disconnected);
+                return Err(e);
+            }
+        };
+        self.health.write().insert(server_id.to_string(), ServerHealth::Healthy);
+        self.refresh_tools(server_id).await?;
+        Ok(info)
+    }
+
+    /// Refresh tools from a server
+    pub async fn refresh_tools(&self, server_id: &str) -> Result<Vec<ToolSchema>, McpError> {
+        let connection = self.get_connection(server_id)
+            .ok_or_else(|| McpError::ServerNotFound(server_id.to_string()))?;
+        
+        let ticket = self.tool_cache.ticket();
+        let tools = connection.list_tools().await?;
+        persist_schemas(self.schema_cache.as_ref(), server_id, &connection, &tools).await;
+        let tools = self.tool_cache.permitted(server_id, tools);
+        if !self.tool_cache.store(server_id, ticket, tools.clone()) {
+            // A refresh that started after this one already finished
+            debug!(server_id = %server_id, "Discarding stale tool listing");
+            return Ok(self.list_server_tools(server_id));
+        }
+        
         debug!(server_id = %server_id, num_tools = tools.len(), "Refreshed tools");
         Ok(tools)
     }
 
     /// Notify all servers of sandbox state change
+    ///
+    /// If a debounce window is configured the notification is sent from a
+    /// background task after the window, and skipped if superseded. The state
+    /// is also remembered and sent to servers that connect afterwards.
     pub async fn notify_sandbox_state(&self, enabled: bool, policy: &str) {
-        for connection in self.connections.read().values() {
-            if let Err(e) = connection.notify_sandbox_state(enabled, policy).await {
-                warn!(error = %e, "Failed to notify sandbox state");
+        *self.sandbox_state.lock() = Some((enabled, policy.to_string()));
+        
+        let connections: Vec<_> = self.connections.read()
+            .iter()
+            .map(|(id, conn)| (id.clone(), conn.clone()))
+            .collect();
+
+        let Some(window) = self.notification_debounce else {
+            for (server_id, connection) in connections {
+                if let Err(e) = connection.notify_sandbox_state(enabled, policy).await {
+                    warn!(server_id = %server_id, error = %e, "Failed to notify sandbox state");
+                }
             }
+            return;
+        };
+
+        for (server_id, connection) in connections {
+            let generation = {
+                let mut pending = self.pending_sandbox.lock();
+                let generation = pending.entry(server_id.clone()).or_insert(0);
+                *generation += 1;
+                *generation
+            };
+
+            let pending = self.pending_sandbox.clone();
+            let policy = policy.to_string();
+            self.spawn_task(self.tasks.force(), async move {
+                tokio::time::sleep(window).await;
+
+                // A newer state was queued while we slept
+                if pending.lock().get(&server_id) != Some(&generation) {
+                    return;
+                }
+
+                if let Err(e) = connection.notify_sandbox_state(enabled, &policy).await {
+                    warn!(server_id = %server_id, error = %e, "Failed to notify sandbox state");
+                }
+            });
         }
     }
 
     /// Check health of all connections
+    ///
+    /// With an inbound rate limit configured to `mark_unhealthy`, a server
+    /// that had messages dropped since the previous check is marked unhealthy.
     pub async fn health_check(&self) {
-        for (server_id, connection) in self.connections.read().iter() {
-            let health = if connection.is_connected() {
+        let flood_check = self.transport_options.inbound_rate_limit.is_some_and(|l| l.mark_unhealthy);
+        
+        let connections: Vec<_> = self.connections.read()
+            .iter()
+            .map(|(id, conn)| (id.clone(), conn.clone()))
+            .collect();
+        for (server_id, connection) in &connections {
+            let flooding = flood_check && {
+                let dropped = connection.dropped_messages();
+                let previous = self.dropped_seen.lock().insert(server_id.clone(), dropped);
+                dropped > previous.unwrap_or(0)
+            };
+            
+            let (health, outcome) = if flooding {
+                warn!(server_id = %server_id, "Server is flooding notifications");
+                (ServerHealth::Unhealthy, Err("flooding notifications".to_string()))
+            } else if connection.is_connected() {
+                let started = Instant::now();
                 match connection.ping().await {
-                    Ok(_) => ServerHealth::Healthy,
-                    Err(_) => ServerHealth::Unhealthy,
+                    Ok(_) => (ServerHealth::Healthy, Ok(started.elapsed())),
+                    Err(e) => (ServerHealth::Unhealthy, Err(e.to_string())),
                 }
             } else {
-                ServerHealth::Disconnected
+                (ServerHealth::Disconnected, Err("disconnected".to_string()))
             };
             
             self.health.write().insert(server_id.clone(), health);
+            self.statuses.lock().entry(server_id.clone()).or_default().record(outcome);
+            
+            if self.standby_servers.contains(server_id) {
+                self.check_standby(server_id, health).await;
+            }
+        }
+    }
+
+    /// Replace a dead standby, then fail over to it if the primary is failing
+    async fn check_standby(&self, server_id: &str, primary_health: ServerHealth) {
+        let standby = self.standbys.lock().get(server_id).map(|(_, c)| c.clone());
+        if let Some(standby) = standby {
+            if standby.ping().await.is_err() {
+                warn!(server_id = %server_id, "Standby connection failed its health check");
+                self.standbys.lock().remove(server_id);
+                let _ = standby.shutdown().await;
+            }
+        }
+        
+        if primary_health.is_degraded() && self.standbys.lock().contains_key(server_id) {
+            let _ = self.promote_standby(server_id).await;
+        } else {
+            self.start_standby(server_id).await;
         }
     }
 }
 
+/// Save a live tool listing to the schema cache, if there is one
+async fn persist_schemas(
+    cache: Option<&Arc<dyn SchemaCache>>,
+    server_id: &str,
+    connection: &McpConnection,
+    tools: &[ToolSchema],
+) {
+    let Some(cache) = cache else {
+        return;
+    };
+    if let Some(token) = connection.server_info().await.as_ref().and_then(validity_token) {
+        cache.save(server_id, &token, tools);
+    }
+}
+
+/// Run `operation` unless `cancel` fires first, in which case it's dropped
+async fn cancellable<T>(
+    cancel: Option<&CancellationToken>,
+    operation: impl std::future::Future<Output = Result<T, McpError>>,
+) -> Result<T, McpError> {
+    let Some(cancel) = cancel else {
+        return operation.await;
+    };
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(McpError::Cancelled),
+        result = operation => result,
+    }
+}
+
+/// Tear down a connection that failed to connect, tagging the error with the phase
+async fn abort_connect(
+    server_id: &str,
+    connection: &McpConnection,
+    phase: ConnectPhase,
+    error: McpError,
+) -> McpError {
+    warn!(server_id = %server_id, phase = %phase, error = %error, "Connect failed");
+    if let Err(e) = connection.shutdown().await {
+        debug!(server_id = %server_id, error = %e, "Error shutting down failed connection");
+    }
+    McpError::ConnectFailed {
+        server_id: server_id.to_string(),
+        phase,
+        source: Box::new(error),
+    }
+}
+
+/// Score how well a tool matches lowercase search terms
+fn relevance(tool: &ToolSchema, terms: &[String]) -> u32 {
+    let name = tool.name.to_lowercase();
+    let description = tool.description.to_lowercase();
+    
+    terms.iter()
+        .map(|term| {
+            let name_score = if name == *term {
+                100
+            } else if name.starts_with(term.as_str()) {
+                60
+            } else if name.contains(term.as_str()) {
+                40
+            } else if is_subsequence(term, &name) {
+                10
+            } else {
+                0
+            };
+            let description_score = if description.contains(term.as_str()) { 20 } else { 0 };
+            name_score + description_score
+        })
+        .sum()
+}
+
+/// Whether all characters of `needle` appear in `haystack` in order
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+/// Function-calling name for a server's tool: `{server}__{tool}`.
+///
+/// Characters providers reject are replaced with `_` and the result is
+/// kept within 64 characters. A name that had to be rewritten gets a hash
+/// of the original appended (`{server}__{tool}_1a2b3c4d`), so `web.v2` and
+/// `web_v2`, or two long names sharing a prefix, don't end up the same.
+pub fn function_name(server_id: &str, tool_name: &str) -> String {
+    let original = format!("{}{}{}", server_id, FUNCTION_NAME_SEPARATOR, tool_name);
+    let mut name: String = original
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    if name == original && name.len() <= MAX_FUNCTION_NAME_LEN {
+        return name;
+    }
+    name.truncate(MAX_FUNCTION_NAME_LEN - FUNCTION_NAME_HASH_LEN);
+    format!("{}_{:08x}", name, fnv1a(original.as_bytes()))
+}
+
+/// 32-bit FNV-1a, stable across builds unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193))
+}
+
+/// Qualified name of a server's tool: `{server}::{tool}`
+pub fn qualified_name(server_id: &str, tool_name: &str) -> String {
+    format!("{}{}{}", server_id, QUALIFIED_NAME_SEPARATOR, tool_name)
+}
+
+/// Whether `connection`'s server advertised resources
+async fn supports_resources(connection: &McpConnection) -> bool {
+    connection.effective_capabilities().await
+        .is_some_and(|capabilities| capabilities.can_use_resources())
+}
+
+/// What made a server's catalogs stale
+enum CatalogChange {
+    /// The server sent a `list_changed` notification
+    Listed(ListKind),
+    /// The connection recovered onto a new transport
+    Reconnected,
+}
+
+/// Wait for the next catalog change, or `None` once the connection is gone
+async fn next_catalog_change(
+    incoming: &mut broadcast::Receiver<serde_json::Value>,
+    reconnects: &mut broadcast::Receiver<ConnectionEvent>,
+) -> Option<CatalogChange> {
+    tokio::select! {
+        kind = next_list_changed(incoming) => kind.map(CatalogChange::Listed),
+        event = reconnects.recv() => match event {
+            Ok(ConnectionEvent::Reconnected { .. }) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                Some(CatalogChange::Reconnected)
+            }
+            Err(broadcast::error::RecvError::Closed) => None,
+        },
+    }
+}
+
+/// Wait for the next `list_changed` notification, or `None` once the connection is gone
+async fn next_list_changed(incoming: &mut broadcast::Receiver<serde_json::Value>) -> Option<ListKind> {
+    loop {
+        match incoming.recv().await {
+            Ok(message) => {
+                let kind = message.get("method")
+                    .and_then(|m| m.as_str())
+                    .and_then(ListKind::from_notification);
+                if kind.is_some() {
+                    return kind;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Adapt a broadcast receiver into a stream, skipping over lagged messages
+fn broadcast_stream<T>(receiver: broadcast::Receiver<T>) -> impl Stream<Item = T>
+where
+    T: Clone + Send + 'static,
+{
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(item) => return Some((item, receiver)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+/// Cheaply cloneable handle for sharing one manager across tasks.
+///
+/// `McpManager` is `Send + Sync` and all its methods take `&self`, so this is
+/// just an `Arc` with conveniences. Background tasks spawned by the manager
+/// hold only weak references to connections and per-server state, never the
+/// manager itself, so dropping the last handle drops the manager, which ends
+/// its tasks.
+#[derive(Clone)]
+pub struct ManagerHandle(Arc<McpManager>);
+
+impl ManagerHandle {
+    /// Share `manager`
+    pub fn new(manager: McpManager) -> Self {
+        Self(Arc::new(manager))
+    }
+
+    /// Stream manager events (see [`McpManager::event_stream`])
+    pub fn events(&self) -> impl Stream<Item = ManagerEvent> {
+        self.0.event_stream()
+    }
+
+    /// Weak reference that doesn't keep the manager alive
+    pub fn downgrade(&self) -> std::sync::Weak<McpManager> {
+        Arc::downgrade(&self.0)
+    }
+}
+
+impl std::ops::Deref for ManagerHandle {
+    type Target = McpManager;
+
+    fn deref(&self) -> &McpManager {
+        &self.0
+    }
+}
+
+impl From<McpManager> for ManagerHandle {
+    fn from(manager: McpManager) -> Self {
+        Self::new(manager)
+    }
+}
+
 impl Default for McpManager {
     fn default() -> Self {
         Self::new()
     }
 }
 
+impl Drop for McpManager {
+    fn drop(&mut self) {
+        // Forwarders only hold weak connections, but the transports they
+        // listen on can outlive the manager
+        self.dropped.cancel();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{fake_server, initialize_result, reply, slow_server, test_config, FakeFactory};
 
     #[test]
     fn test_manager_creation() {
@@ -176,4 +2117,1135 @@ mod tests {
         assert!(manager.server_ids().is_empty());
         assert!(manager.list_tools().is_empty());
     }
+
+    #[tokio::test]
+    async fn test_dry_run_call() {
+        let manager = McpManager::new();
+        let connection = McpConnection::new(test_config("fs")).await.unwrap();
+        manager.connections.write().insert("fs".into(), Arc::new(connection));
+        manager.tool_cache.write().insert("fs".into(), vec![ToolSchema {
+            name: "read".into(),
+            description: String::new(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {"path": {"type": "string"}},
+                "required": ["path"]
+            }),
+            annotations: None,
+        }]);
+
+        let report = manager
+            .dry_run_call("fs", "read", serde_json::json!({"path": "/tmp"}))
+            .unwrap();
+        assert_eq!(report.server_id, "fs");
+        assert_eq!(report.params["name"], "read");
+
+        assert!(matches!(
+            manager.dry_run_call("fs", "read", serde_json::json!({})),
+            Err(McpError::InvalidArguments { .. })
+        ));
+        assert!(matches!(
+            manager.dry_run_call("fs", "write", serde_json::json!({})),
+            Err(McpError::ToolNotFound(_))
+        ));
+
+        manager.health.write().insert("fs".into(), ServerHealth::Unhealthy);
+        assert!(matches!(
+            manager.dry_run_call("fs", "read", serde_json::json!({"path": "/tmp"})),
+            Err(McpError::ServerUnhealthy(_))
+        ));
+    }
+
+    #[test]
+    fn test_list_read_only_tools() {
+        let manager = McpManager::new();
+        let tool = |name: &str, annotations: serde_json::Value| -> ToolSchema {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "inputSchema": {"type": "object"},
+                "annotations": annotations
+            })).unwrap()
+        };
+        manager.tool_cache.write().insert("fs".into(), vec![
+            tool("read", serde_json::json!({"readOnlyHint": true})),
+            tool("write", serde_json::json!({"destructiveHint": false})),
+            tool("delete", serde_json::json!({})),
+        ]);
+
+        let read_only: Vec<_> = manager.list_read_only_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(read_only, ["read"]);
+
+        let mut safe: Vec<_> = manager.list_non_destructive_tools().into_iter().map(|t| t.name).collect();
+        safe.sort();
+        assert_eq!(safe, ["read", "write"]);
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_changed_refreshes_cache() {
+        let factory = FakeFactory::new(|_: usize| {
+            let mut listings = 0;
+            fake_server(move |request| match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, initialize_result())],
+                Some("tools/list") => {
+                    listings += 1;
+                    let tool = |name: &str| serde_json::json!({"name": name, "inputSchema": {}});
+                    if listings == 1 {
+                        vec![
+                            reply(request, serde_json::json!({"tools": [tool("a")]})),
+                            serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "method": "notifications/tools/list_changed"
+                            }),
+                        ]
+                    } else {
+                        vec![reply(request, serde_json::json!({"tools": [tool("a"), tool("b")]}))]
+                    }
+                }
+                _ => vec![],
+            })
+        });
+        let manager = McpManager::new()
+            .with_transport_factory(factory)
+            .with_list_changed_debounce(Duration::from_millis(10));
+
+        manager.connect(test_config("live")).await.unwrap();
+
+        for _ in 0..100 {
+            if manager.list_server_tools("live").len() == 2 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("tool cache was not refreshed after list_changed");
+    }
+
+    #[test]
+    fn test_function_schemas_prefixed() {
+        let manager = McpManager::new();
+        let tool: ToolSchema = serde_json::from_value(serde_json::json!({
+            "name": "search",
+            "inputSchema": {"type": "object"}
+        })).unwrap();
+        manager.tool_cache.write().insert("docs".into(), vec![tool.clone()]);
+        manager.tool_cache.write().insert("web.v2".into(), vec![tool]);
+
+        let schemas = manager.function_schemas(FunctionFormat::Anthropic);
+        let names: Vec<_> = schemas.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["docs__search", function_name("web.v2", "search").as_str()]);
+        assert!(names[1].starts_with("web_v2__search_"));
+
+        assert_eq!(
+            manager.resolve_function_name(names[1]),
+            Some(("web.v2".to_string(), "search".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_function_names_dont_collide() {
+        let manager = McpManager::new();
+        let tool = |name: &str| -> ToolSchema {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "inputSchema": {"type": "object"}
+            })).unwrap()
+        };
+        let long = "x".repeat(70);
+        manager.tool_cache.write().insert("web.v2".into(), vec![tool("search")]);
+        manager.tool_cache.write().insert("web_v2".into(), vec![tool("search")]);
+        manager.tool_cache.write().insert("long".into(), vec![tool(&format!("{}a", long)), tool(&format!("{}b", long))]);
+
+        let schemas = manager.function_schemas(FunctionFormat::Anthropic);
+        let names: HashSet<_> = schemas.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names.len(), 4);
+        assert!(names.iter().all(|name| name.len() <= MAX_FUNCTION_NAME_LEN));
+        assert!(names.contains("web_v2__search"));
+
+        for (server_id, tool_name) in [("web.v2", "search".to_string()), ("web_v2", "search".to_string()), ("long", format!("{}b", long))] {
+            assert_eq!(
+                manager.resolve_function_name(&function_name(server_id, &tool_name)),
+                Some((server_id.to_string(), tool_name))
+            );
+        }
+    }
+
+    #[test]
+    fn test_qualified_tool_names() {
+        let manager = McpManager::new().with_qualified_tool_names();
+        let tool: ToolSchema = serde_json::from_value(serde_json::json!({
+            "name": "search",
+            "inputSchema": {"type": "object"}
+        })).unwrap();
+        manager.tool_cache.write().insert("docs".into(), vec![tool.clone()]);
+        manager.tool_cache.write().insert("web".into(), vec![tool]);
+
+        let mut names: Vec<_> = manager.list_tools().into_iter().map(|t| t.name).collect();
+        names.sort();
+        assert_eq!(names, ["docs::search", "web::search"]);
+
+        let found = manager.find_tool("web::search").unwrap();
+        assert_eq!((found.server_id.as_str(), found.tool.name.as_str()), ("web", "search"));
+        assert_eq!(found.qualified_name(), "web::search");
+        assert!(manager.find_tool("search").is_some());
+        assert!(manager.find_tool("mail::search").is_none());
+
+        assert_eq!(
+            manager.resolve_qualified_name("docs::search"),
+            Some(("docs".to_string(), "search".to_string()))
+        );
+        assert_eq!(manager.resolve_qualified_name("docs::missing"), None);
+        assert_eq!(manager.resolve_qualified_name("search"), None);
+    }
+
+    #[test]
+    fn test_ambiguous_tool_names() {
+        let manager = McpManager::new();
+        let tool = |name: &str| -> ToolSchema {
+            serde_json::from_value(serde_json::json!({"name": name, "inputSchema": {}})).unwrap()
+        };
+        manager.tool_cache.write().insert("web".into(), vec![tool("search")]);
+        manager.tool_cache.write().insert("docs".into(), vec![tool("search"), tool("read")]);
+
+        let servers: Vec<_> = manager.find_all_tools("search").into_iter().map(|(id, _)| id).collect();
+        assert_eq!(servers, ["docs", "web"]);
+        assert_eq!(manager.find_tool("search").unwrap().server_id, "docs");
+
+        let err = manager.resolve_tool("search").unwrap_err();
+        assert!(matches!(err, McpError::AmbiguousTool { ref servers, .. } if servers == &["docs", "web"]));
+        assert!(err.is_caller_error());
+        assert_eq!(manager.resolve_tool("web::search").unwrap().server_id, "web");
+        assert_eq!(manager.resolve_tool("read").unwrap().server_id, "docs");
+        assert!(matches!(manager.resolve_tool("write"), Err(McpError::ToolNotFound(_))));
+    }
+
+    #[test]
+    fn test_list_tools_ranked() {
+        let manager = McpManager::new();
+        let tool = |name: &str, description: &str| -> ToolSchema {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "description": description,
+                "inputSchema": {}
+            })).unwrap()
+        };
+        manager.tool_cache.write().insert("fs".into(), vec![
+            tool("read_file", "Read a file from disk"),
+            tool("write_file", "Write a file"),
+            tool("list_dir", "List directory entries"),
+        ]);
+        manager.tool_cache.write().insert("web".into(), vec![
+            tool("fetch", "Fetch a URL and read the body"),
+        ]);
+
+        let ranked = manager.list_tools_ranked(2, "read");
+        let names: Vec<_> = ranked.iter().map(|(_, t)| t.name.as_str()).collect();
+        assert_eq!(names, ["read_file", "fetch"]);
+
+        assert_eq!(manager.list_tools_ranked(10, "").len(), 4);
+        assert!(manager.list_tools_ranked(10, "zzz").is_empty());
+    }
+
+    async fn manager_with_unhealthy_server(policy: UnhealthyPolicy) -> McpManager {
+        let manager = McpManager::new().with_unhealthy_policy(policy);
+        let connection = McpConnection::new(test_config("sick")).await.unwrap();
+        manager.connections.write().insert("sick".into(), Arc::new(connection));
+        manager.health.write().insert("sick".into(), ServerHealth::Unhealthy);
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_reject_unhealthy_policy() {
+        let manager = manager_with_unhealthy_server(UnhealthyPolicy::RejectUnhealthy).await;
+
+        let result = manager.call_tool("sick", "anything", serde_json::json!({})).await;
+        assert!(matches!(result, Err(McpError::ServerUnhealthy(_))));
+        let typed = manager.call_tool_as::<serde_json::Value>("sick", "anything", serde_json::json!({})).await;
+        assert!(matches!(typed, Err(McpError::ServerUnhealthy(_))));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_healthy_policy() {
+        let policy = UnhealthyPolicy::WaitForHealthy(Duration::from_secs(5));
+        let manager = manager_with_unhealthy_server(policy).await;
+
+        let recover = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.health.write().insert("sick".into(), ServerHealth::Healthy);
+        };
+        let (result, _) = tokio::join!(
+            manager.call_tool("sick", "anything", serde_json::json!({})),
+            recover
+        );
+
+        // Dispatched once healthy; there's no transport so it then fails
+        assert!(matches!(result, Err(McpError::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_refresh_keeps_newest_listing() {
+        // Hold the first listing back and answer it only after the second
+        let mut held = None;
+        let transport = fake_server(move |request| {
+            let listing = |version: &str| serde_json::json!({"tools": [
+                {"name": version, "inputSchema": {}}
+            ]});
+            match held.take() {
+                None => {
+                    held = Some(request.clone());
+                    vec![]
+                }
+                Some(first) => vec![
+                    reply(request, listing("new")),
+                    reply(&first, listing("old")),
+                ],
+            }
+        });
+        let manager = McpManager::new();
+        let connection = McpConnection::new(test_config("s")).await.unwrap()
+            .with_transport(transport);
+        manager.connections.write().insert("s".into(), Arc::new(connection));
+
+        let (first, second) = tokio::join!(
+            manager.refresh_tools("s"),
+            async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                manager.refresh_tools("s").await
+            }
+        );
+
+        let names = |tools: Vec<ToolSchema>| tools.into_iter().map(|t| t.name).collect::<Vec<_>>();
+        assert_eq!(names(second.unwrap()), ["new"]);
+        assert_eq!(names(first.unwrap()), ["new"]);
+        assert_eq!(names(manager.list_server_tools("s")), ["new"]);
+    }
+
+    #[tokio::test]
+    async fn test_call_tool_with_failover() {
+        let manager = McpManager::new();
+        let answering = |name: &'static str| fake_server(move |request| vec![reply(request, serde_json::json!({
+            "content": [{"type": "text", "text": name}]
+        }))]);
+
+        let flaky = crate::fault::FaultyTransport::new(answering("flaky"));
+        flaky.fail_next(1);
+        for (id, transport) in [
+            ("flaky", Arc::new(flaky) as Arc<dyn crate::transport::McpTransport>),
+            ("sick", answering("sick")),
+            ("backup", answering("backup")),
+        ] {
+            let connection = McpConnection::new(test_config(id)).await.unwrap()
+                .with_transport(transport);
+            manager.connections.write().insert(id.into(), Arc::new(connection));
+        }
+        manager.health.write().insert("sick".into(), ServerHealth::Unhealthy);
+
+        let (server_id, result) = manager
+            .call_tool_with_failover("echo", serde_json::json!({}), &["missing", "flaky", "sick", "backup"])
+            .await
+            .unwrap();
+        assert_eq!(server_id, "backup");
+        assert_eq!(result, [ToolContent::Text { text: "backup".into() }]);
+
+        let err = manager
+            .call_tool_with_failover("echo", serde_json::json!({}), &["missing", "sick"])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McpError::FailoverExhausted { ref attempts } if attempts.len() == 2));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_connect_discovery_timeout_kills_server() {
+        let pid_file = tempfile::NamedTempFile::new().unwrap();
+        // Answer initialize, then never answer tools/list
+        let script = format!(
+            r#"echo $$ > {}; read line; printf '{{"jsonrpc":"2.0","id":0,"result":{{"name":"hang"}}}}\n'; exec sleep 30"#,
+            pid_file.path().display()
+        );
+        let mut config = test_config("hang");
+        config.transport = warhorn::McpTransport::Stdio {
+            command: "sh".into(),
+            args: vec!["-c".into(), script],
+        };
+        let manager = McpManager::new().with_discovery_timeout(Duration::from_millis(200));
+
+        let started = tokio::time::Instant::now();
+        let err = manager.connect(config).await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            err,
+            McpError::ConnectFailed { phase: ConnectPhase::Discovery, ref source, .. }
+                if matches!(**source, McpError::Timeout)
+        ));
+        assert!(manager.get_connection("hang").is_none());
+
+        let pid = std::fs::read_to_string(pid_file.path()).unwrap();
+        let alive = std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .status()
+            .unwrap()
+            .success();
+        assert!(!alive);
+    }
+
+    #[test]
+    fn test_validate_all_schemas() {
+        let manager = McpManager::new();
+        let tool = |name: &str, schema: serde_json::Value| -> ToolSchema {
+            serde_json::from_value(serde_json::json!({"name": name, "inputSchema": schema})).unwrap()
+        };
+        let sound = serde_json::json!({"type": "object", "properties": {"path": {"type": "string"}}});
+
+        let ticket = manager.tool_cache.ticket();
+        manager.tool_cache.store("fs", ticket, vec![
+            tool("read", sound.clone()),
+            tool("write", serde_json::json!({"type": "object", "required": "path"})),
+        ]);
+        let ticket = manager.tool_cache.ticket();
+        manager.tool_cache.store("db", ticket, vec![tool("query", serde_json::json!(null))]);
+
+        let report = manager.validate_all_schemas();
+        assert_eq!(report, [
+            ("db".to_string(), "query".to_string(), vec![SchemaProblem::NotAnObject { path: "schema".into() }]),
+            ("fs".to_string(), "write".to_string(), vec![SchemaProblem::InvalidRequired { path: "schema".into() }]),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_tool_cache() {
+        let manager = McpManager::new();
+        let transport = fake_server(|request| vec![reply(request, serde_json::json!({
+            "tools": [{"name": "fresh", "inputSchema": {}}]
+        }))]);
+        let connection = McpConnection::new(test_config("fs")).await.unwrap()
+            .with_transport(transport);
+        manager.connections.write().insert("fs".into(), Arc::new(connection));
+        let ticket = manager.tool_cache.ticket();
+        manager.tool_cache.store("fs", ticket, vec![]);
+
+        let stats = manager.cache_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].tool_count, 0);
+        assert!(stats[0].last_refresh.is_some());
+
+        manager.invalidate_tool_cache("fs", false).await.unwrap();
+        assert!(manager.cache_stats().is_empty());
+        assert!(manager.get_connection("fs").is_some());
+
+        manager.invalidate_tool_cache("fs", true).await.unwrap();
+        assert_eq!(manager.list_server_tools("fs")[0].name, "fresh");
+        assert_eq!(manager.cache_stats()[0].tool_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_tools_changed_events() {
+        use futures::StreamExt;
+
+        let manager = McpManager::new();
+        let events = manager.event_stream();
+        tokio::pin!(events);
+        let tool = |name: &str| -> ToolSchema {
+            serde_json::from_value(serde_json::json!({"name": name, "inputSchema": {}})).unwrap()
+        };
+
+        let ticket = manager.tool_cache.ticket();
+        manager.tool_cache.store("fs", ticket, vec![tool("read"), tool("write")]);
+        let ticket = manager.tool_cache.ticket();
+        manager.tool_cache.store("fs", ticket, vec![tool("read"), tool("write")]);
+        let ticket = manager.tool_cache.ticket();
+        manager.tool_cache.store("fs", ticket, vec![tool("read"), tool("stat")]);
+        manager.tool_cache.remove("fs");
+
+        let mut diffs = Vec::new();
+        for _ in 0..3 {
+            let Some(ManagerEvent::ToolsChanged { server_id, added, removed, .. }) = events.next().await else {
+                panic!("expected a ToolsChanged event");
+            };
+            assert_eq!(server_id, "fs");
+            diffs.push((added, removed));
+        }
+        assert_eq!(diffs, [
+            (vec!["read".to_string(), "write".to_string()], vec![]),
+            (vec!["stat".to_string()], vec!["write".to_string()]),
+            (vec![], vec!["read".to_string(), "stat".to_string()]),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_resource_updated_events() {
+        use futures::StreamExt;
+
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => {
+                let mut result = initialize_result();
+                result["capabilities"]["resources"] = serde_json::json!({"subscribe": true});
+                vec![reply(request, result)]
+            }
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            Some("resources/subscribe") => vec![
+                reply(request, serde_json::json!({})),
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/resources/updated",
+                    "params": {"uri": "file:///log"}
+                }),
+            ],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory);
+        let events = manager.event_stream();
+        tokio::pin!(events);
+        manager.connect(test_config("res")).await.unwrap();
+
+        manager.get_connection("res").unwrap().subscribe_resource("file:///log").await.unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let Some(ManagerEvent::ResourceUpdated { server_id, update }) = events.next().await {
+                    return (server_id, update);
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(event.0, "res");
+        assert_eq!(event.1, ResourceUpdate { uri: "file:///log".into(), contents: None });
+    }
+
+    #[tokio::test]
+    async fn test_resource_listing_cached_until_changed() {
+        let lists = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let factory = {
+            let lists = lists.clone();
+            FakeFactory::new(move |_: usize| {
+                let lists = lists.clone();
+                fake_server(move |request| match request["method"].as_str() {
+                    Some("initialize") => vec![reply(request, serde_json::json!({
+                        "name": "fake",
+                        "version": "1.0.0",
+                        "protocolVersion": "2024-11-05",
+                        "capabilities": {"tools": {}, "resources": {}}
+                    }))],
+                    Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+                    Some("resources/list") => {
+                        let n = lists.fetch_add(1, Ordering::SeqCst);
+                        vec![reply(request, serde_json::json!({
+                            "resources": [{"uri": format!("file:///{}", n), "name": "log", "mimeType": "text/plain"}]
+                        }))]
+                    }
+                    Some("ping") => vec![
+                        serde_json::json!({"jsonrpc": "2.0", "method": "notifications/resources/list_changed"}),
+                        reply(request, serde_json::json!({})),
+                    ],
+                    _ => vec![],
+                })
+            })
+        };
+        let manager = McpManager::new()
+            .with_transport_factory(factory)
+            .with_list_changed_debounce(Duration::from_millis(10));
+        manager.connect(test_config("res")).await.unwrap();
+
+        let first = manager.list_resources("res").await.unwrap();
+        assert_eq!(first[0].uri, "file:///0");
+        assert_eq!(first[0].mime_type.as_deref(), Some("text/plain"));
+        manager.list_resources("res").await.unwrap();
+        assert_eq!(lists.load(Ordering::SeqCst), 1);
+
+        manager.get_connection("res").unwrap().ping().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.list_resources("res").await.unwrap()[0].uri, "file:///1");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_invalidates_resource_listings() {
+        use crate::fault::{Fault, FaultyTransport};
+        use futures::StreamExt;
+
+        let lists = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let factory = {
+            let lists = lists.clone();
+            FakeFactory::new(move |attempt: usize| {
+                let lists = lists.clone();
+                let server = fake_server(move |request| match request["method"].as_str() {
+                    Some("initialize") => {
+                        let mut result = initialize_result();
+                        result["capabilities"]["resources"] = serde_json::json!({});
+                        vec![reply(request, result)]
+                    }
+                    Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+                    Some("resources/list") => {
+                        let n = lists.fetch_add(1, Ordering::SeqCst);
+                        vec![reply(request, serde_json::json!({
+                            "resources": [{"uri": format!("file:///{}", n), "name": "log"}]
+                        }))]
+                    }
+                    _ => vec![],
+                });
+                if attempt > 0 {
+                    return server;
+                }
+                // initialize, tools/list and resources/list pass; the next request disconnects
+                let faulty = FaultyTransport::new(server);
+                for _ in 0..3 {
+                    faulty.push(Fault::Delay(Duration::ZERO));
+                }
+                faulty.disconnect_next();
+                Arc::new(faulty) as Arc<dyn crate::transport::McpTransport>
+            })
+        };
+        let policy = ReconnectPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(10),
+            jitter: false,
+        };
+        let manager = McpManager::new()
+            .with_transport_factory(factory)
+            .with_reconnect_policy(policy)
+            .with_list_changed_debounce(Duration::from_millis(10));
+        let events = manager.event_stream();
+        tokio::pin!(events);
+        manager.connect(test_config("res")).await.unwrap();
+        assert_eq!(manager.list_resources("res").await.unwrap()[0].uri, "file:///0");
+
+        assert!(manager.get_connection("res").unwrap().ping().await.is_err());
+        let event = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let Some(ManagerEvent::Reconnected { server_id }) = events.next().await {
+                    return server_id;
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(event, "res");
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.list_resources("res").await.unwrap()[0].uri, "file:///1");
+    }
+
+    #[tokio::test]
+    async fn test_resources_need_capability() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory);
+        manager.connect(test_config("tools-only")).await.unwrap();
+
+        assert!(manager.list_resources("tools-only").await.unwrap().is_empty());
+        let err = manager.read_resource("tools-only", "file:///x").await.unwrap_err();
+        assert!(matches!(err, McpError::Unsupported { capability: "resources", .. }));
+    }
+
+    #[tokio::test]
+    async fn test_schema_cache_skips_discovery() {
+        let serving = |listing: Option<&'static str>| FakeFactory::new(move |_: usize| {
+            fake_server(move |request| match (request["method"].as_str(), listing) {
+                (Some("initialize"), _) => vec![reply(request, initialize_result())],
+                (Some("tools/list"), Some(name)) => vec![reply(request, serde_json::json!({
+                    "tools": [{"name": name, "inputSchema": {}}]
+                }))],
+                _ => vec![],
+            })
+        });
+        let schemas: Arc<dyn SchemaCache> = Arc::new(crate::schema_cache::MemorySchemaCache::new());
+
+        let first = McpManager::new()
+            .with_transport_factory(serving(Some("cached")))
+            .with_schema_cache(schemas.clone());
+        first.connect(test_config("stable")).await.unwrap();
+
+        // This server never answers tools/list
+        let second = McpManager::new()
+            .with_transport_factory(serving(None))
+            .with_schema_cache(schemas)
+            .with_discovery_timeout(Duration::from_millis(100));
+        second.connect(test_config("stable")).await.unwrap();
+        assert_eq!(second.list_server_tools("stable")[0].name, "cached");
+
+        // Nothing saved under this id, so discovery runs and times out
+        assert!(second.connect(test_config("other")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_enable_disabled_server() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory.clone());
+        manager.add_disabled(test_config("later"));
+
+        assert_eq!(manager.disabled_servers(), ["later"]);
+        assert!(manager.server_ids().is_empty());
+        assert_eq!(factory.created(), 0);
+
+        manager.enable_server("later").await.unwrap();
+        assert_eq!(manager.server_ids(), ["later"]);
+        assert!(manager.disabled_servers().is_empty());
+        assert!(matches!(manager.enable_server("later").await, Err(McpError::ServerNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_update_server_keeps_id_present() {
+        // The second transport rejects initialize; the third lists "v2"
+        let factory = FakeFactory::new(|attempt: usize| fake_server(move |request| match request["method"].as_str() {
+            Some("initialize") if attempt == 1 => vec![serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32603, "message": "bad config"}
+            })],
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({
+                "tools": [{"name": format!("v{}", attempt), "inputSchema": {}}]
+            }))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory.clone());
+        manager.connect(test_config("srv")).await.unwrap();
+        let original = manager.get_connection("srv").unwrap();
+
+        let (result, _) = tokio::join!(
+            manager.update_server("srv", test_config("srv")),
+            async {
+                for _ in 0..10 {
+                    assert!(manager.get_connection("srv").is_some());
+                    tokio::task::yield_now().await;
+                }
+            }
+        );
+        assert!(matches!(result, Err(McpError::ConnectFailed { phase: ConnectPhase::Initialize, .. })));
+        assert!(Arc::ptr_eq(&manager.get_connection("srv").unwrap(), &original));
+        assert_eq!(manager.list_server_tools("srv")[0].name, "v0");
+
+        manager.update_server("srv", test_config("srv")).await.unwrap();
+        assert!(!Arc::ptr_eq(&manager.get_connection("srv").unwrap(), &original));
+        assert_eq!(manager.list_server_tools("srv")[0].name, "v2");
+        assert_eq!(factory.created(), 3);
+
+        assert!(matches!(
+            manager.update_server("missing", test_config("missing")).await,
+            Err(McpError::ServerNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_connects_to_same_id() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory.clone());
+
+        let (first, second) = tokio::join!(
+            manager.connect(test_config("dup")),
+            manager.connect(test_config("dup"))
+        );
+        first.unwrap();
+        second.unwrap();
+
+        assert_eq!(factory.created(), 1);
+        assert_eq!(manager.server_ids(), ["dup"]);
+        assert!(manager.get_connection("dup").unwrap().is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_update_server_drains_in_flight_calls() {
+        // The first server takes a while to answer tool calls
+        let factory = FakeFactory::new(|attempt: usize| {
+            let handler = move |request: &serde_json::Value| match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, initialize_result())],
+                Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+                Some("tools/call") => vec![reply(request, serde_json::json!({
+                    "content": [{"type": "text", "text": format!("instance {}", attempt)}]
+                }))],
+                _ => vec![],
+            };
+            match attempt {
+                0 => slow_server(Some("tools/call"), Duration::from_millis(200), handler),
+                _ => fake_server(handler),
+            }
+        });
+        let manager = McpManager::new().with_transport_factory(factory.clone());
+        manager.connect(test_config("svc")).await.unwrap();
+
+        let in_flight = manager.call_tool("svc", "work", serde_json::json!({}));
+        let update = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            manager.update_server("svc", test_config("svc")).await.unwrap();
+            manager.call_tool("svc", "work", serde_json::json!({})).await
+        };
+        let (old, new) = tokio::join!(in_flight, update);
+        assert_eq!(old.unwrap(), [ToolContent::Text { text: "instance 0".into() }]);
+        assert_eq!(new.unwrap(), [ToolContent::Text { text: "instance 1".into() }]);
+    }
+
+    #[tokio::test]
+    async fn test_tool_policy_hides_and_blocks_tools() {
+        let (calls_tx, mut calls_rx) = tokio::sync::mpsc::unbounded_channel();
+        let factory = FakeFactory::new(move |_: usize| {
+            let calls_tx = calls_tx.clone();
+            fake_server(move |request| match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, initialize_result())],
+                Some("tools/list") => vec![reply(request, serde_json::json!({"tools": [
+                    {"name": "read", "inputSchema": {"type": "object"}},
+                    {"name": "write", "inputSchema": {"type": "object"}},
+                    {"name": "delete", "inputSchema": {"type": "object"}}
+                ]}))],
+                Some("tools/call") => {
+                    let _ = calls_tx.send(request["params"]["name"].clone());
+                    vec![reply(request, serde_json::json!({"content": []}))]
+                }
+                _ => vec![],
+            })
+        });
+        let manager = McpManager::new()
+            .with_transport_factory(factory)
+            .with_tool_policy("fs", ToolPolicy::allow_only(["read", "delete"]).deny(["delete"]));
+        manager.connect(test_config("fs")).await.unwrap();
+
+        let names: Vec<_> = manager.list_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["read"]);
+        assert!(manager.find_tool("write").is_none());
+        assert_eq!(manager.refresh_tools("fs").await.unwrap().len(), 1);
+
+        let err = manager.call_tool("fs", "delete", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::ToolNotAllowed { ref tool, .. } if tool == "delete"));
+        assert!(manager.dry_run_call("fs", "write", serde_json::json!({})).is_err());
+
+        manager.call_tool("fs", "read", serde_json::json!({})).await.unwrap();
+        assert_eq!(calls_rx.recv().await.unwrap(), "read");
+        assert!(calls_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_argument_validation_before_dispatch() {
+        let (calls_tx, mut calls_rx) = tokio::sync::mpsc::unbounded_channel();
+        let factory = FakeFactory::new(move |_: usize| {
+            let calls_tx = calls_tx.clone();
+            fake_server(move |request| match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, initialize_result())],
+                Some("tools/list") => vec![reply(request, serde_json::json!({"tools": [{
+                    "name": "read",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {"path": {"type": "string"}},
+                        "required": ["path"],
+                        "additionalProperties": false
+                    }
+                }]}))],
+                Some("tools/call") => {
+                    let _ = calls_tx.send(request["params"]["arguments"].clone());
+                    vec![reply(request, serde_json::json!({"content": []}))]
+                }
+                _ => vec![],
+            })
+        });
+        let manager = McpManager::new()
+            .with_transport_factory(factory)
+            .with_argument_validation();
+        manager.connect(test_config("fs")).await.unwrap();
+
+        let err = manager.call_tool("fs", "read", serde_json::json!({"pth": "/tmp"})).await.unwrap_err();
+        let McpError::InvalidArguments { errors } = err else {
+            panic!("expected InvalidArguments, got {:?}", err);
+        };
+        assert_eq!(errors.len(), 2);
+        assert!(calls_rx.try_recv().is_err());
+
+        manager.call_tool("fs", "read", serde_json::json!({"path": "/tmp"})).await.unwrap();
+        assert_eq!(calls_rx.recv().await.unwrap()["path"], "/tmp");
+    }
+
+    #[tokio::test]
+    async fn test_task_budget_limits_connections() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            Some("tools/call") => vec![reply(request, serde_json::json!({"content": []}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new()
+            .with_transport_factory(factory)
+            .with_reader_pool(ReaderPool::new())
+            .with_task_budget(TASKS_PER_CONNECTION);
+        manager.connect(test_config("a")).await.unwrap();
+        assert_eq!(manager.background_tasks(), TASKS_PER_CONNECTION);
+        manager.call_tool("a", "noop", serde_json::json!({})).await.unwrap();
+
+        let err = manager.connect(test_config("b")).await.unwrap_err();
+        assert!(matches!(err, McpError::TaskBudgetExhausted { limit: 3 }));
+
+        // The forwarders wind down once the connection is dropped
+        manager.disconnect("a").await.unwrap();
+        for _ in 0..100 {
+            if manager.background_tasks() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        manager.connect(test_config("b")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_share_rate_limit() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new()
+            .with_transport_factory(factory.clone())
+            .with_reconnect_rate(20, 1);
+        manager.connect(test_config("a")).await.unwrap();
+        manager.connect(test_config("b")).await.unwrap();
+
+        // One burst token, then 50ms per reconnect regardless of server
+        let started = std::time::Instant::now();
+        let (a, b, c) = tokio::join!(
+            manager.reconnect("a"),
+            manager.reconnect("b"),
+            manager.reconnect("a"),
+        );
+        a.unwrap();
+        b.unwrap();
+        c.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(90));
+        assert_eq!(factory.created(), 5);
+        assert!(matches!(manager.reconnect("missing").await, Err(McpError::ServerNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_standby_promoted_on_primary_failure() {
+        // Only the first instance fails its pings
+        let factory = FakeFactory::new(|attempt: usize| fake_server(move |request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            Some("ping") if attempt == 0 => vec![serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32603, "message": "wedged"}
+            })],
+            Some("ping") => vec![reply(request, serde_json::json!({}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new()
+            .with_transport_factory(factory.clone())
+            .with_standby("ha");
+        manager.connect(test_config("ha")).await.unwrap();
+
+        assert_eq!(factory.created(), 2);
+        // The idle standby runs no manager tasks
+        assert_eq!(manager.background_tasks(), TASKS_PER_CONNECTION);
+        let standby = manager.standby_instance("ha").unwrap();
+        assert_ne!(manager.primary_instance("ha"), Some(standby));
+
+        manager.health_check().await;
+        assert_eq!(manager.primary_instance("ha"), Some(standby));
+        assert!(manager.standby_instance("ha").is_some_and(|next| next != standby));
+        assert_eq!(manager.server_health("ha"), Some(ServerHealth::Healthy));
+        assert_eq!(factory.created(), 3);
+
+        manager.disconnect("ha").await.unwrap();
+        assert!(manager.primary_instance("ha").is_none());
+        assert!(manager.standby_instance("ha").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_health_check_records_status() {
+        let failing = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let factory = {
+            let failing = failing.clone();
+            FakeFactory::new(move |_: usize| {
+                let failing = failing.clone();
+                fake_server(move |request| match request["method"].as_str() {
+                    Some("initialize") => vec![reply(request, initialize_result())],
+                    Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+                    Some("ping") if failing.load(Ordering::SeqCst) => vec![serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "error": {"code": -32603, "message": "wedged"}
+                    })],
+                    Some("ping") => vec![reply(request, serde_json::json!({}))],
+                    _ => vec![],
+                })
+            })
+        };
+        let manager = McpManager::new().with_transport_factory(factory);
+        manager.connect(test_config("flaky")).await.unwrap();
+
+        manager.health_check().await;
+        manager.health_check().await;
+        let status = manager.server_status("flaky").unwrap();
+        assert_eq!(status.health, ServerHealth::Unhealthy);
+        assert_eq!(status.consecutive_failures, 2);
+        assert!(status.last_error.unwrap().contains("wedged"));
+
+        failing.store(false, Ordering::SeqCst);
+        manager.health_check().await;
+        let status = manager.server_status("flaky").unwrap();
+        assert_eq!(status.health, ServerHealth::Healthy);
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(status.last_ping_latency.is_some() && status.last_checked.is_some());
+        assert!(manager.server_status("missing").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_requests_and_tool_calls() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            Some("tools/call") if request["params"]["name"] == "broken" => vec![serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32603, "message": "boom"}
+            })],
+            Some("tools/call") => vec![reply(request, serde_json::json!({
+                "content": [{"type": "text", "text": "ok"}]
+            }))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory);
+        manager.connect(test_config("counted")).await.unwrap();
+        let before = manager.metrics().servers["counted"].requests;
+
+        let connection = manager.get_connection("counted").unwrap();
+        connection.call_tool("echo", serde_json::json!({})).await.unwrap();
+        connection.call_tool("echo", serde_json::json!({})).await.unwrap();
+        assert!(connection.call_tool("broken", serde_json::json!({})).await.is_err());
+
+        let metrics = manager.metrics();
+        let server = &metrics.servers["counted"];
+        assert_eq!(server.requests, before + 3);
+        assert_eq!(server.errors, 1);
+        assert!(server.average_latency.is_some());
+        assert_eq!(metrics.tool_calls("echo"), 2);
+        assert_eq!(metrics.tool_calls("broken"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_all_respects_concurrency_limit() {
+        let factory = FakeFactory::new(|_: usize| slow_server(Some("initialize"), Duration::from_millis(100), |request| {
+            match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, initialize_result())],
+                Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+                _ => vec![],
+            }
+        }));
+        let manager = McpManager::new()
+            .with_transport_factory(factory)
+            .with_connect_concurrency(2);
+        let configs = ["a", "b", "c", "d"].into_iter().map(test_config).collect();
+
+        let started = tokio::time::Instant::now();
+        let outcomes = manager.connect_all(configs, None).await;
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        let ids: Vec<_> = outcomes.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c", "d"]);
+        assert!(outcomes.iter().all(|(_, result)| result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_waits_for_connect() {
+        let factory = FakeFactory::new(|_: usize| slow_server(Some("initialize"), Duration::from_millis(100), |request| {
+            match request["method"].as_str() {
+                Some("initialize") => vec![reply(request, initialize_result())],
+                Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+                _ => vec![],
+            }
+        }));
+        let manager = McpManager::new().with_transport_factory(factory);
+
+        let disconnect = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            manager.disconnect("slow").await
+        };
+        let (connected, disconnected) = tokio::join!(manager.connect(test_config("slow")), disconnect);
+        connected.unwrap();
+        disconnected.unwrap();
+        assert!(manager.get_connection("slow").is_none());
+        assert!(manager.connect_locks.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_bulk_operations() {
+        // The second server never answers
+        let factory = FakeFactory::new(|attempt: usize| fake_server(move |request| match request["method"].as_str() {
+            _ if attempt == 1 => vec![],
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory);
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+
+        let outcomes = manager
+            .connect_all(vec![test_config("quick"), test_config("stuck")], Some(cancel.clone()))
+            .await;
+        assert!(outcomes[0].1.is_ok());
+        assert!(matches!(outcomes[1].1, Err(McpError::Cancelled)));
+        assert_eq!(manager.server_ids(), ["quick"]);
+
+        let outcomes = manager.refresh_all_tools(Some(cancel.clone())).await;
+        assert!(matches!(outcomes[..], [(_, Err(McpError::Cancelled))]));
+
+        let outcomes = manager.disconnect_all(Some(cancel)).await;
+        assert!(matches!(outcomes[..], [(_, Err(McpError::Cancelled))]));
+        assert_eq!(manager.server_ids(), ["quick"]);
+
+        assert!(manager.disconnect_all(None).await.iter().all(|(_, result)| result.is_ok()));
+        assert!(manager.server_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_is_shareable_and_droppable() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<McpManager>();
+        assert_send_sync::<ManagerHandle>();
+
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            _ => vec![],
+        }));
+        let handle = ManagerHandle::new(McpManager::new().with_transport_factory(factory));
+        let worker = handle.clone();
+        tokio::spawn(async move { worker.connect(test_config("shared")).await })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(handle.server_ids(), ["shared"]);
+
+        let weak = handle.downgrade();
+        drop(handle);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dropping_handle_ends_connection_tasks() {
+        let factory = FakeFactory::new(|_: usize| fake_server(|request| match request["method"].as_str() {
+            Some("initialize") => vec![reply(request, initialize_result())],
+            Some("tools/list") => vec![reply(request, serde_json::json!({"tools": []}))],
+            _ => vec![],
+        }));
+        let manager = McpManager::new().with_transport_factory(factory);
+        let tasks = manager.tasks.clone();
+        let handle = ManagerHandle::new(manager);
+        handle.connect(test_config("held")).await.unwrap();
+        assert_eq!(tasks.live(), TASKS_PER_CONNECTION);
+
+        // The fake server keeps its end open, so only the drop can stop them
+        drop(handle);
+        for _ in 0..100 {
+            if tasks.live() == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("connection tasks outlived the manager");
+    }
 }
@@ -0,0 +1,132 @@
+//! Request and tool call counters
+//!
+//! Every connection keeps an [`McpMetrics`]. Request counters are atomics
+//! and tool call counts sit behind a mutex held only for a map lookup, so
+//! recording stays cheap under concurrent calls. [`McpManager::metrics`]
+//! collects a snapshot of every connection.
+//!
+//! [`McpManager::metrics`]: crate::McpManager::metrics
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Live counters of one connection
+#[derive(Debug, Default)]
+pub struct McpMetrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    total_latency_micros: AtomicU64,
+    last_latency_micros: AtomicU64,
+    tool_calls: Mutex<HashMap<String, u64>>,
+}
+
+impl McpMetrics {
+    /// Record a finished request
+    pub(crate) fn record_request(&self, latency: Duration, succeeded: bool) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_latency_micros.fetch_add(micros, Ordering::Relaxed);
+        self.last_latency_micros.store(micros, Ordering::Relaxed);
+    }
+
+    /// Record a call to `tool`
+    pub(crate) fn record_tool_call(&self, tool: &str) {
+        let mut calls = self.tool_calls.lock();
+        match calls.get_mut(tool) {
+            Some(count) => *count += 1,
+            None => {
+                calls.insert(tool.to_string(), 1);
+            }
+        }
+    }
+
+    /// Copy of the counters as they are now
+    pub fn snapshot(&self) -> ServerMetrics {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let total = self.total_latency_micros.load(Ordering::Relaxed);
+        let (average_latency, last_latency) = if requests == 0 {
+            (None, None)
+        } else {
+            (
+                Some(Duration::from_micros(total / requests)),
+                Some(Duration::from_micros(self.last_latency_micros.load(Ordering::Relaxed))),
+            )
+        };
+        ServerMetrics {
+            requests,
+            errors: self.errors.load(Ordering::Relaxed),
+            average_latency,
+            last_latency,
+            tool_calls: self.tool_calls.lock().clone(),
+        }
+    }
+}
+
+/// Counters of one server at a point in time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerMetrics {
+    /// Requests sent, cancelled ones excluded
+    pub requests: u64,
+    /// Requests that failed, timeouts and JSON-RPC errors included
+    pub errors: u64,
+    /// Mean time to a response (None before the first request)
+    pub average_latency: Option<Duration>,
+    /// Time to the most recent response
+    pub last_latency: Option<Duration>,
+    /// Calls by tool name
+    pub tool_calls: HashMap<String, u64>,
+}
+
+/// Counters of every connected server at a point in time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct McpMetricsSnapshot {
+    /// Counters by server id
+    pub servers: HashMap<String, ServerMetrics>,
+}
+
+impl McpMetricsSnapshot {
+    /// Requests sent across all servers
+    pub fn total_requests(&self) -> u64 {
+        self.servers.values().map(|m| m.requests).sum()
+    }
+
+    /// Failed requests across all servers
+    pub fn total_errors(&self) -> u64 {
+        self.servers.values().map(|m| m.errors).sum()
+    }
+
+    /// Calls of `tool` across all servers
+    pub fn tool_calls(&self, tool: &str) -> u64 {
+        self.servers.values().filter_map(|m| m.tool_calls.get(tool)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_averages_latency() {
+        let metrics = McpMetrics::default();
+        assert_eq!(metrics.snapshot(), ServerMetrics::default());
+
+        metrics.record_request(Duration::from_millis(10), true);
+        metrics.record_request(Duration::from_millis(30), false);
+        metrics.record_tool_call("echo");
+        metrics.record_tool_call("echo");
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.requests, 2);
+        assert_eq!(snapshot.errors, 1);
+        assert_eq!(snapshot.average_latency, Some(Duration::from_millis(20)));
+        assert_eq!(snapshot.last_latency, Some(Duration::from_millis(30)));
+        assert_eq!(snapshot.tool_calls.get("echo"), Some(&2));
+    }
+}
@@ -0,0 +1,301 @@
+//! Mirroring server traffic to a debug tool
+//!
+//! [`MirrorTransport`] wraps a transport and copies every message it sends
+//! or receives to a [`DebugSink`], as one JSON line per message tagged with
+//! the server id and direction:
+//!
+//! ```text
+//! {"serverId":"fs","direction":"out","message":{"jsonrpc":"2.0","id":1,"method":"tools/list"}}
+//! ```
+//!
+//! A sink can serve these lines on a Unix socket for a dev tool to attach
+//! to (browser tools can bridge it to a WebSocket, e.g. with `websocat`),
+//! or be read in-process via [`DebugSink::subscribe`]. Mirroring never
+//! blocks traffic: a reader that falls behind misses lines. Enabled by the
+//! `debug-mirror` feature; meant for development only.
+
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use warhorn::McpServerConfig;
+use crate::error::McpError;
+use crate::transport::{McpTransport, TransportFactory, TransportOptions};
+use crate::types::{ProcessExit, TrafficStats};
+
+/// Lines buffered per sink reader before it starts missing them
+const SINK_CAPACITY: usize = 1024;
+
+/// Which way a mirrored message went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Sent to the server
+    Out,
+    /// Received from the server
+    In,
+}
+
+/// One mirrored message
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirroredMessage {
+    /// Server the message was exchanged with
+    pub server_id: String,
+    /// Whether it was sent or received
+    pub direction: Direction,
+    /// The JSON-RPC message
+    pub message: serde_json::Value,
+}
+
+/// Destination for mirrored traffic, shared by any number of transports
+#[derive(Clone)]
+pub struct DebugSink {
+    lines: broadcast::Sender<Arc<MirroredMessage>>,
+}
+
+impl DebugSink {
+    /// Sink readable only in-process, via `subscribe`
+    pub fn new() -> Self {
+        Self {
+            lines: broadcast::channel(SINK_CAPACITY).0,
+        }
+    }
+
+    /// Sink that also serves traffic to every client of a Unix socket at `path`.
+    ///
+    /// A stale socket file at `path` is replaced; any other file there is
+    /// left alone and fails with `ConfigError`. Clients receive traffic
+    /// from the moment they connect.
+    #[cfg(unix)]
+    pub fn unix(path: impl AsRef<std::path::Path>) -> Result<Self, McpError> {
+        use std::os::unix::fs::FileTypeExt;
+
+        let path = path.as_ref();
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(McpError::ConfigError(format!(
+                    "{} exists and is not a socket",
+                    path.display()
+                )));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let listener = tokio::net::UnixListener::bind(path)?;
+        let sink = Self::new();
+
+        let lines = sink.lines.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_client(stream, lines.subscribe()));
+            }
+        });
+        Ok(sink)
+    }
+
+    /// Receive mirrored messages from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<MirroredMessage>> {
+        self.lines.subscribe()
+    }
+
+    fn mirror(&self, server_id: &str, direction: Direction, message: &serde_json::Value) {
+        // Nobody attached is fine
+        let _ = self.lines.send(Arc::new(MirroredMessage {
+            server_id: server_id.to_string(),
+            direction,
+            message: message.clone(),
+        }));
+    }
+}
+
+impl Default for DebugSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write mirrored messages to one socket client as JSON lines until it goes away
+#[cfg(unix)]
+async fn serve_client(
+    mut stream: tokio::net::UnixStream,
+    mut lines: broadcast::Receiver<Arc<MirroredMessage>>,
+) {
+    use tokio::io::AsyncWriteExt;
+
+    loop {
+        let message = match lines.recv().await {
+            Ok(message) => message,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                debug!(skipped, "Debug mirror client fell behind");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Ok(mut line) = serde_json::to_vec(&*message) else {
+            continue;
+        };
+        line.push(b'\n');
+        if stream.write_all(&line).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Transport copying all traffic of the wrapped transport to a sink
+pub struct MirrorTransport {
+    inner: Arc<dyn McpTransport>,
+    server_id: String,
+    sink: DebugSink,
+}
+
+impl MirrorTransport {
+    /// Mirror traffic through `inner`, tagged with `server_id`
+    pub fn new(inner: Arc<dyn McpTransport>, server_id: impl Into<String>, sink: DebugSink) -> Self {
+        let server_id = server_id.into();
+
+        // Server-initiated messages; ends when the inner transport does
+        let mut incoming = inner.subscribe();
+        let mirror = (server_id.clone(), sink.clone());
+        tokio::spawn(async move {
+            let (server_id, sink) = mirror;
+            loop {
+                match incoming.recv().await {
+                    Ok(message) => sink.mirror(&server_id, Direction::In, &message),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Self { inner, server_id, sink }
+    }
+}
+
+#[async_trait]
+impl McpTransport for MirrorTransport {
+    async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        self.sink.mirror(&self.server_id, Direction::Out, &request);
+        let response = self.inner.send_request(request).await?;
+        self.sink.mirror(&self.server_id, Direction::In, &response);
+        Ok(response)
+    }
+
+    async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
+        self.sink.mirror(&self.server_id, Direction::Out, &notification);
+        self.inner.send_notification(notification).await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.inner.subscribe()
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.inner.dropped_messages()
+    }
+
+    fn recent_stderr(&self) -> Vec<String> {
+        self.inner.recent_stderr()
+    }
+
+    fn exit_status(&self) -> Option<ProcessExit> {
+        self.inner.exit_status()
+    }
+
+    fn has_process(&self) -> bool {
+        self.inner.has_process()
+    }
+
+    async fn wait_for_exit(&self, timeout: Duration) -> Option<ProcessExit> {
+        self.inner.wait_for_exit(timeout).await
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        self.inner.traffic()
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.inner.close().await
+    }
+}
+
+/// Factory wrapping every transport of another factory in a [`MirrorTransport`]
+pub struct MirrorFactory {
+    inner: Arc<dyn TransportFactory>,
+    sink: DebugSink,
+}
+
+impl MirrorFactory {
+    /// Mirror transports created by `inner` to `sink`
+    pub fn new(inner: Arc<dyn TransportFactory>, sink: DebugSink) -> Self {
+        Self { inner, sink }
+    }
+}
+
+#[async_trait]
+impl TransportFactory for MirrorFactory {
+    async fn create(
+        &self,
+        config: &McpServerConfig,
+        options: &TransportOptions,
+    ) -> Result<Arc<dyn McpTransport>, McpError> {
+        let transport = self.inner.create(config, options).await?;
+        Ok(Arc::new(MirrorTransport::new(transport, config.id.clone(), self.sink.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fake_server, reply};
+
+    #[tokio::test]
+    async fn test_mirror_tags_direction() {
+        let server = fake_server(|request| match request.get("id") {
+            Some(_) => vec![
+                serde_json::json!({"jsonrpc": "2.0", "method": "notifications/message", "params": {}}),
+                reply(request, serde_json::json!({})),
+            ],
+            None => vec![],
+        });
+        let sink = DebugSink::new();
+        let mut lines = sink.subscribe();
+        let transport = MirrorTransport::new(server, "fs", sink);
+
+        transport.send_notification(serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await.unwrap();
+        transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"})).await.unwrap();
+
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            let line = lines.recv().await.unwrap();
+            assert_eq!(line.server_id, "fs");
+            seen.push((line.direction, line.message["method"].as_str().unwrap_or("response").to_string()));
+        }
+        seen.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(seen, [
+            (Direction::Out, "notifications/initialized".to_string()),
+            (Direction::In, "notifications/message".to_string()),
+            (Direction::Out, "ping".to_string()),
+            (Direction::In, "response".to_string()),
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_sink_keeps_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("debug.sock");
+        std::fs::write(&path, "notes").unwrap();
+        assert!(matches!(DebugSink::unix(&path), Err(McpError::ConfigError(_))));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "notes");
+
+        // A leftover socket is replaced
+        std::fs::remove_file(&path).unwrap();
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(DebugSink::unix(&path).is_ok());
+    }
+}
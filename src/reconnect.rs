@@ -0,0 +1,165 @@
+//! Reconnect policies and fleet-wide pacing of reconnect attempts
+//!
+//! A [`ReconnectPolicy`] decides how a single connection retries after its
+//! transport breaks, backing off exponentially with jitter.
+//!
+//! When a dependency shared by many servers (e.g. a gateway) fails, every
+//! connection tends to reconnect at once. A [`ReconnectLimiter`] shared by
+//! those connections admits reconnects at a fixed rate, so recovery doesn't
+//! turn into a correlated storm against the dependency that just came back.
+
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+
+/// How a connection reconnects after its transport breaks.
+///
+/// Attempt `n` (from zero) waits `initial_backoff * 2^n`, capped at
+/// `max_backoff`; with `jitter` the wait is drawn from the upper half of
+/// that, so servers that failed together don't retry in lockstep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Attempts before giving up; zero disables reconnecting
+    pub max_retries: u32,
+    /// Wait before the first attempt
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts
+    pub max_backoff: Duration,
+    /// Randomize each wait
+    pub jitter: bool,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Policy that never reconnects
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Whether any attempt is made
+    pub fn is_enabled(&self) -> bool {
+        self.max_retries > 0
+    }
+
+    /// Wait before attempt `attempt`, counting from zero
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        if !self.jitter {
+            return backoff;
+        }
+        let unit = uuid::Uuid::new_v4().as_u128() as u64 as f64 / u64::MAX as f64;
+        backoff.mul_f64(0.5 + unit / 2.0)
+    }
+}
+
+/// Token bucket admitting reconnect attempts at a global rate
+#[derive(Debug)]
+pub struct ReconnectLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Most tokens the bucket holds, i.e. the largest burst admitted at once
+    burst: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Available tokens; negative while callers are queued for a refill
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl ReconnectLimiter {
+    /// Admit `per_second` reconnects on average, with bursts of up to `burst`.
+    ///
+    /// Both are clamped to at least one.
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate: f64::from(per_second.max(1)),
+            burst,
+            state: Mutex::new(Bucket {
+                tokens: burst,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a reconnect may start.
+    ///
+    /// Callers are admitted in the order they arrive; each one reserves a
+    /// token up front, so concurrent waiters never wake to find it taken.
+    pub async fn acquire(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take a token, returning how long until it is actually available
+    fn reserve(&self) -> Duration {
+        let mut bucket = self.state.lock();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled).as_secs_f64() * self.rate;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst);
+        bucket.refilled = now;
+
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = ReconnectPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            jitter: false,
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(9), Duration::from_secs(1));
+
+        let jittered = ReconnectPolicy { jitter: true, ..policy };
+        for _ in 0..20 {
+            let wait = jittered.backoff(2);
+            assert!(wait >= Duration::from_millis(200) && wait <= Duration::from_millis(400));
+        }
+        assert!(!ReconnectPolicy::disabled().is_enabled());
+    }
+
+    #[tokio::test]
+    async fn test_limiter_paces_after_burst() {
+        let limiter = ReconnectLimiter::new(20, 2);
+        let started = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        // Two from the burst, then two more at 50ms each
+        assert!(started.elapsed() >= Duration::from_millis(90));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,242 @@
+//! Recording and replaying server traffic
+//!
+//! [`RecordingTransport`] wraps a live transport and captures every
+//! request/response exchange. Saved as a JSON-lines fixture, the recording
+//! can be served back by [`ReplayTransport`] to run tool-calling code
+//! deterministically without the server. Enabled by the `test-util` feature.
+//!
+//! Requests are matched by method and a hash of their params (ignoring
+//! `_meta`, which carries per-request trace context). Identical requests are
+//! answered in recorded order, repeating the last answer once exhausted.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::error::McpError;
+use crate::transport::McpTransport;
+use crate::types::{ProcessExit, TrafficStats};
+
+/// One request and the response it received
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedExchange {
+    /// JSON-RPC method
+    pub method: String,
+    /// [`params_hash`] of the request params
+    pub params_hash: String,
+    /// Response without its `id`
+    pub response: serde_json::Value,
+}
+
+/// Stable hash of request params, ignoring `_meta`
+pub fn params_hash(params: &serde_json::Value) -> String {
+    let mut params = params.clone();
+    if let Some(params) = params.as_object_mut() {
+        params.remove("_meta");
+    }
+
+    // FNV-1a, so fixtures stay valid across Rust versions
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in params.to_string().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Transport that records every exchange with the wrapped transport
+pub struct RecordingTransport {
+    inner: Arc<dyn McpTransport>,
+    exchanges: Mutex<Vec<RecordedExchange>>,
+}
+
+impl RecordingTransport {
+    /// Record traffic through `inner`
+    pub fn new(inner: Arc<dyn McpTransport>) -> Self {
+        Self {
+            inner,
+            exchanges: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Exchanges recorded so far
+    pub fn exchanges(&self) -> Vec<RecordedExchange> {
+        self.exchanges.lock().clone()
+    }
+
+    /// Write the recording to `path` as JSON lines
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), McpError> {
+        let mut out = String::new();
+        for exchange in self.exchanges.lock().iter() {
+            let line = serde_json::to_string(exchange)
+                .map_err(|e| McpError::ProtocolError(format!("JSON error: {}", e)))?;
+            out.push_str(&line);
+            out.push('\n');
+        }
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl McpTransport for RecordingTransport {
+    async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let params_hash = params_hash(&request["params"]);
+
+        let response = self.inner.send_request(request).await?;
+
+        let mut recorded = response.clone();
+        if let Some(recorded) = recorded.as_object_mut() {
+            recorded.remove("id");
+        }
+        self.exchanges.lock().push(RecordedExchange {
+            method,
+            params_hash,
+            response: recorded,
+        });
+        Ok(response)
+    }
+
+    async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
+        self.inner.send_notification(notification).await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.inner.subscribe()
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.inner.dropped_messages()
+    }
+
+    fn recent_stderr(&self) -> Vec<String> {
+        self.inner.recent_stderr()
+    }
+
+    fn exit_status(&self) -> Option<ProcessExit> {
+        self.inner.exit_status()
+    }
+
+    fn has_process(&self) -> bool {
+        self.inner.has_process()
+    }
+
+    async fn wait_for_exit(&self, timeout: Duration) -> Option<ProcessExit> {
+        self.inner.wait_for_exit(timeout).await
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        self.inner.traffic()
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.inner.close().await
+    }
+}
+
+/// Transport answering requests from a recording instead of a server
+pub struct ReplayTransport {
+    /// Remaining responses by (method, params hash)
+    responses: Mutex<HashMap<(String, String), VecDeque<serde_json::Value>>>,
+}
+
+impl ReplayTransport {
+    /// Serve the given exchanges
+    pub fn new(exchanges: impl IntoIterator<Item = RecordedExchange>) -> Self {
+        let mut responses: HashMap<_, VecDeque<_>> = HashMap::new();
+        for exchange in exchanges {
+            responses.entry((exchange.method, exchange.params_hash))
+                .or_default()
+                .push_back(exchange.response);
+        }
+        Self {
+            responses: Mutex::new(responses),
+        }
+    }
+
+    /// Load a recording saved by [`RecordingTransport::save`]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, McpError> {
+        let text = std::fs::read_to_string(path)?;
+        let exchanges = text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| McpError::ProtocolError(format!("Invalid recording: {}", e)))
+            })
+            .collect::<Result<Vec<RecordedExchange>, _>>()?;
+        Ok(Self::new(exchanges))
+    }
+}
+
+#[async_trait]
+impl McpTransport for ReplayTransport {
+    async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let hash = params_hash(&request["params"]);
+
+        let mut responses = self.responses.lock();
+        let queue = responses.get_mut(&(method.clone(), hash.clone()))
+            .filter(|queue| !queue.is_empty())
+            .ok_or_else(|| McpError::ProtocolError(format!(
+                "No recorded response for {} with params hash {}",
+                method, hash
+            )))?;
+
+        // Keep the last answer around for any further identical requests
+        let mut response = if queue.len() > 1 {
+            queue.pop_front().unwrap_or_default()
+        } else {
+            queue[0].clone()
+        };
+        response["id"] = request["id"].clone();
+        Ok(response)
+    }
+
+    async fn send_notification(&self, _notification: serde_json::Value) -> Result<(), McpError> {
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::McpConnection;
+    use crate::testing::{fake_server, reply, test_config};
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let live = fake_server(|request| vec![reply(request, serde_json::json!({
+            "content": [{"type": "text", "text": request["params"]["arguments"]["path"]}]
+        }))]);
+        let recorder = Arc::new(RecordingTransport::new(live));
+        let connection = McpConnection::new(test_config("live")).await.unwrap()
+            .with_transport(recorder.clone());
+        connection.call_tool("read", serde_json::json!({"path": "/a"})).await.unwrap();
+        connection.call_tool("read", serde_json::json!({"path": "/b"})).await.unwrap();
+        // Diagnostics come from the wrapped transport
+        assert!(recorder.traffic().bytes_sent > 0);
+
+        let fixture = tempfile::NamedTempFile::new().unwrap();
+        recorder.save(fixture.path()).unwrap();
+
+        let replay = ReplayTransport::load(fixture.path()).unwrap();
+        let connection = McpConnection::new(test_config("replayed")).await.unwrap()
+            .with_transport(Arc::new(replay));
+        let b = connection.call_tool("read", serde_json::json!({"path": "/b"})).await.unwrap();
+        assert_eq!(b, [crate::types::ToolContent::Text { text: "/b".into() }]);
+
+        let missing = connection.call_tool("read", serde_json::json!({"path": "/c"})).await;
+        assert!(matches!(missing, Err(McpError::ProtocolError(_))));
+    }
+}
@@ -0,0 +1,151 @@
+//! Persisted tool schema caches
+//!
+//! A [`SchemaCache`] lets `connect` skip `tools/list` for servers whose tool
+//! set is stable. Entries are keyed by server id and carry a validity token
+//! derived from the server's `initialize` result (see [`validity_token`]);
+//! an entry only matches while the server reports the same identity.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::types::{ServerInfo, ToolSchema};
+
+/// Backend storing tool schemas across manager restarts
+pub trait SchemaCache: Send + Sync {
+    /// Cached tools for `server_id`, if stored under the same `token`
+    fn load(&self, server_id: &str, token: &str) -> Option<Vec<ToolSchema>>;
+
+    /// Store the tools for `server_id`, replacing any previous entry
+    fn save(&self, server_id: &str, token: &str, tools: &[ToolSchema]);
+}
+
+/// Validity token for a server's cached tools.
+///
+/// Combines name, version and protocol version. Servers that report no
+/// version get no token and are never served from the cache.
+pub fn validity_token(info: &ServerInfo) -> Option<String> {
+    if info.version.is_empty() {
+        return None;
+    }
+    Some(format!("{}@{}/{}", info.name, info.version, info.protocol_version))
+}
+
+/// Cache held in memory, e.g. shared by managers in one process
+#[derive(Default)]
+pub struct MemorySchemaCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl MemorySchemaCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SchemaCache for MemorySchemaCache {
+    fn load(&self, server_id: &str, token: &str) -> Option<Vec<ToolSchema>> {
+        self.entries.lock()
+            .get(server_id)
+            .filter(|entry| entry.token == token)
+            .map(|entry| entry.tools.clone())
+    }
+
+    fn save(&self, server_id: &str, token: &str, tools: &[ToolSchema]) {
+        self.entries.lock().insert(server_id.to_string(), CacheEntry {
+            server_id: server_id.to_string(),
+            token: token.to_string(),
+            tools: tools.to_vec(),
+        });
+    }
+}
+
+/// Cache stored as one JSON file per server in a directory
+pub struct FileSchemaCache {
+    dir: PathBuf,
+}
+
+impl FileSchemaCache {
+    /// Store entries in `dir`, which is created on first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, server_id: &str) -> PathBuf {
+        let file: String = server_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.json", file))
+    }
+}
+
+impl SchemaCache for FileSchemaCache {
+    fn load(&self, server_id: &str, token: &str) -> Option<Vec<ToolSchema>> {
+        let bytes = std::fs::read(self.path(server_id)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&bytes).ok()?;
+        // Ids that sanitize to the same file name can't serve each other
+        (entry.server_id == server_id && entry.token == token).then_some(entry.tools)
+    }
+
+    fn save(&self, server_id: &str, token: &str, tools: &[ToolSchema]) {
+        let entry = CacheEntry {
+            server_id: server_id.to_string(),
+            token: token.to_string(),
+            tools: tools.to_vec(),
+        };
+        if let Err(e) = write_entry(&self.dir, &self.path(server_id), &entry) {
+            warn!(server_id = %server_id, error = %e, "Failed to persist tool schemas");
+        }
+    }
+}
+
+/// Write via a temporary file so a crash never leaves a torn entry
+fn write_entry(dir: &Path, path: &Path, entry: &CacheEntry) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(entry)?)?;
+    std::fs::rename(tmp, path)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    server_id: String,
+    token: String,
+    tools: Vec<ToolSchema>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools() -> Vec<ToolSchema> {
+        serde_json::from_value(serde_json::json!([
+            {"name": "read", "inputSchema": {"type": "object"}}
+        ])).unwrap()
+    }
+
+    #[test]
+    fn test_file_cache_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = FileSchemaCache::new(dir.path().join("schemas"));
+        assert!(cache.load("fs/1", "fs@1.0").is_none());
+
+        cache.save("fs/1", "fs@1.0", &tools());
+        assert_eq!(cache.load("fs/1", "fs@1.0").unwrap()[0].name, "read");
+        assert!(cache.load("fs/1", "fs@2.0").is_none());
+        assert!(cache.load("fs_1", "fs@1.0").is_none());
+    }
+
+    #[test]
+    fn test_validity_token_requires_version() {
+        let info = |version: &str| -> ServerInfo {
+            serde_json::from_value(serde_json::json!({"name": "fs", "version": version})).unwrap()
+        };
+        assert!(validity_token(&info("")).is_none());
+        assert_ne!(validity_token(&info("1.0")), validity_token(&info("1.1")));
+    }
+}
@@ -0,0 +1,191 @@
+//! Background task accounting
+//!
+//! Every connection needs a few long-running tasks (see
+//! `McpManager::with_task_budget` for the full list). [`TaskBudget`] counts
+//! the manager's tasks and can cap them; [`ReaderPool`] runs reader and
+//! forwarding loops of many connections on one shared task instead of one
+//! task each, for embedders short on threads or memory.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::future::{AbortHandle, Abortable};
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::error::McpError;
+
+type BoxedTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Runs many background loops on a single tokio task.
+///
+/// Loops on the pool share one task, so they never run in parallel; each
+/// must yield (await) rather than block. Cloning shares the same pool.
+#[derive(Clone)]
+pub struct ReaderPool {
+    submit: mpsc::UnboundedSender<BoxedTask>,
+}
+
+impl ReaderPool {
+    /// Start the pool's task; must be called within a tokio runtime
+    pub fn new() -> Self {
+        let (submit, mut submitted) = mpsc::unbounded_channel::<BoxedTask>();
+        tokio::spawn(async move {
+            let mut running = FuturesUnordered::new();
+            loop {
+                tokio::select! {
+                    next = submitted.recv() => match next {
+                        Some(task) => running.push(task),
+                        None => break,
+                    },
+                    Some(()) = running.next(), if !running.is_empty() => {}
+                }
+            }
+            // Every handle to the pool is gone; finish what's left
+            while running.next().await.is_some() {}
+        });
+        Self { submit }
+    }
+
+    /// Run `task` on the pool
+    pub(crate) fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) -> TaskHandle {
+        let (abort, registration) = AbortHandle::new_pair();
+        let task = Abortable::new(task, registration);
+        match self.submit.send(Box::pin(async move {
+            let _ = task.await;
+        })) {
+            Ok(()) => TaskHandle::Pooled(abort),
+            // The pool's task is gone (runtime shutting down); run it standalone
+            Err(mpsc::error::SendError(task)) => TaskHandle::Task(tokio::spawn(task)),
+        }
+    }
+}
+
+impl Default for ReaderPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ReaderPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReaderPool").finish_non_exhaustive()
+    }
+}
+
+/// A background loop, on its own task or on a [`ReaderPool`]
+pub(crate) enum TaskHandle {
+    Task(JoinHandle<()>),
+    Pooled(AbortHandle),
+}
+
+impl TaskHandle {
+    pub(crate) fn abort(&self) {
+        match self {
+            TaskHandle::Task(handle) => handle.abort(),
+            TaskHandle::Pooled(handle) => handle.abort(),
+        }
+    }
+}
+
+/// Spawn `task` on `pool` if there is one, else on its own task
+pub(crate) fn spawn_on(pool: Option<&ReaderPool>, task: impl Future<Output = ()> + Send + 'static) -> TaskHandle {
+    match pool {
+        Some(pool) => pool.spawn(task),
+        None => TaskHandle::Task(tokio::spawn(task)),
+    }
+}
+
+/// Count of live background tasks, optionally capped
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TaskBudget {
+    live: Arc<AtomicUsize>,
+    limit: Option<usize>,
+}
+
+impl TaskBudget {
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            live: Arc::default(),
+            limit,
+        }
+    }
+
+    /// Tasks currently running
+    pub(crate) fn live(&self) -> usize {
+        self.live.load(Ordering::SeqCst)
+    }
+
+    /// Reserve `count` slots, failing with `TaskBudgetExhausted` if they don't fit
+    pub(crate) fn reserve(&self, count: usize) -> Result<Vec<TaskPermit>, McpError> {
+        let admitted = self.live.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| {
+            match self.limit {
+                Some(limit) if live + count > limit => None,
+                _ => Some(live + count),
+            }
+        });
+        if admitted.is_err() {
+            return Err(McpError::TaskBudgetExhausted {
+                limit: self.limit.unwrap_or_default(),
+            });
+        }
+        Ok((0..count).map(|_| TaskPermit { live: self.live.clone() }).collect())
+    }
+
+    /// Take a slot regardless of the limit, for short-lived tasks that can't be refused
+    pub(crate) fn force(&self) -> TaskPermit {
+        self.live.fetch_add(1, Ordering::SeqCst);
+        TaskPermit { live: self.live.clone() }
+    }
+}
+
+/// One counted task; moved into the task and released when it ends
+pub(crate) struct TaskPermit {
+    live: Arc<AtomicUsize>,
+}
+
+impl Drop for TaskPermit {
+    fn drop(&mut self) {
+        self.live.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_reserves_atomically() {
+        let budget = TaskBudget::new(Some(4));
+        let first = budget.reserve(3).unwrap();
+        assert!(matches!(budget.reserve(2), Err(McpError::TaskBudgetExhausted { limit: 4 })));
+        assert_eq!(budget.live(), 3);
+
+        let forced = budget.force();
+        assert_eq!(budget.live(), 4);
+        drop(first);
+        drop(forced);
+        assert_eq!(budget.live(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pool_runs_and_aborts_tasks() {
+        let pool = ReaderPool::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let done = tx.clone();
+        pool.spawn(async move {
+            let _ = done.send("finished");
+        });
+        let stuck = pool.spawn(async move {
+            std::future::pending::<()>().await;
+            let _ = tx.send("unreachable");
+        });
+
+        assert_eq!(rx.recv().await, Some("finished"));
+        stuck.abort();
+        // The aborted task drops its sender, closing the channel
+        assert_eq!(rx.recv().await, None);
+    }
+}
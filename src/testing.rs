@@ -0,0 +1,129 @@
+//! Test helpers: in-process fake MCP servers
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::io::{AsyncWriteExt, BufReader};
+
+use warhorn::McpServerConfig;
+use crate::error::McpError;
+use crate::transport::{read_frame, McpTransport, StreamTransport, TransportFactory, TransportOptions};
+
+/// Stdio config for tests that never actually spawns anything useful
+pub fn test_config(id: &str) -> McpServerConfig {
+    McpServerConfig {
+        id: id.into(),
+        name: id.into(),
+        transport: warhorn::McpTransport::Stdio {
+            command: "true".into(),
+            args: vec![],
+        },
+        env: Default::default(),
+    }
+}
+
+/// Successful JSON-RPC response to `request`
+pub fn reply(request: &Value, result: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": result
+    })
+}
+
+/// Minimal `initialize` result
+pub fn initialize_result() -> Value {
+    json!({
+        "name": "fake",
+        "version": "1.0.0",
+        "protocolVersion": "2024-11-05",
+        "capabilities": {"tools": {}}
+    })
+}
+
+/// Spawn a fake server and return a transport connected to it.
+///
+/// `handler` sees every message the client sends (requests and
+/// notifications) and returns the messages to write back, in order. An empty
+/// vec leaves a request unanswered.
+pub fn fake_server<F>(handler: F) -> Arc<dyn McpTransport>
+where
+    F: FnMut(&Value) -> Vec<Value> + Send + 'static,
+{
+    slow_server(None, Duration::ZERO, handler)
+}
+
+/// Like [`fake_server`], but replies to `method` are written after `delay`.
+///
+/// The server handles messages one at a time, so a delayed reply holds up
+/// everything the client sent after it.
+pub fn slow_server<F>(method: Option<&'static str>, delay: Duration, mut handler: F) -> Arc<dyn McpTransport>
+where
+    F: FnMut(&Value) -> Vec<Value> + Send + 'static,
+{
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let (client_read, client_write) = tokio::io::split(client);
+    let (server_read, mut server_write) = tokio::io::split(server);
+
+    tokio::spawn(async move {
+        let mut server_read = BufReader::new(server_read);
+        while let Ok(Some(frame)) = read_frame(&mut server_read, b'\n').await {
+            let Ok(message) = serde_json::from_slice::<Value>(&frame) else {
+                continue;
+            };
+            if method.is_some() && message["method"].as_str() == method {
+                tokio::time::sleep(delay).await;
+            }
+            for outgoing in handler(&message) {
+                let mut bytes = serde_json::to_vec(&outgoing).unwrap();
+                bytes.push(b'\n');
+                if server_write.write_all(&bytes).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    Arc::new(StreamTransport::new(client_read, client_write, TransportOptions::default()))
+}
+
+/// Transport factory producing a new fake server on every connect
+pub struct FakeFactory<F> {
+    make: F,
+    created: AtomicUsize,
+}
+
+impl<F> FakeFactory<F>
+where
+    F: Fn(usize) -> Arc<dyn McpTransport> + Send + Sync,
+{
+    /// `make` receives the zero-based index of the transport being created
+    pub fn new(make: F) -> Arc<Self> {
+        Arc::new(Self {
+            make,
+            created: AtomicUsize::new(0),
+        })
+    }
+
+    /// Number of transports created so far
+    pub fn created(&self) -> usize {
+        self.created.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl<F> TransportFactory for FakeFactory<F>
+where
+    F: Fn(usize) -> Arc<dyn McpTransport> + Send + Sync,
+{
+    async fn create(
+        &self,
+        _config: &McpServerConfig,
+        _options: &TransportOptions,
+    ) -> Result<Arc<dyn McpTransport>, McpError> {
+        let attempt = self.created.fetch_add(1, Ordering::SeqCst);
+        Ok((self.make)(attempt))
+    }
+}
@@ -0,0 +1,79 @@
+//! W3C trace context propagation
+//!
+//! When enabled on a connection, each request carries a `traceparent`
+//! value in its `_meta`, so servers that log or echo `_meta` can be
+//! correlated with the host's traces. The trace id is inherited from the
+//! ambient context set with [`with_trace_parent`], or freshly generated.
+
+use std::future::Future;
+
+/// Default `_meta` key used to carry the trace context
+pub const DEFAULT_TRACE_META_KEY: &str = "traceparent";
+
+tokio::task_local! {
+    static TRACE_PARENT: String;
+}
+
+/// Run `future` with `traceparent` as the ambient trace context.
+///
+/// Requests issued inside the future continue this trace instead of
+/// starting a new one.
+pub async fn with_trace_parent<F: Future>(traceparent: impl Into<String>, future: F) -> F::Output {
+    TRACE_PARENT.scope(traceparent.into(), future).await
+}
+
+/// The ambient traceparent, if one was set with [`with_trace_parent`]
+pub fn current_trace_parent() -> Option<String> {
+    TRACE_PARENT.try_with(|t| t.clone()).ok()
+}
+
+/// Traceparent for an outgoing request.
+///
+/// Continues the ambient trace with a new span id, or starts a new trace.
+pub fn outgoing_trace_parent() -> String {
+    let id = current_trace_parent()
+        .as_deref()
+        .and_then(trace_id)
+        .map(String::from)
+        .unwrap_or_else(|| random_hex(32));
+    format!("00-{}-{}-01", id, random_hex(16))
+}
+
+/// Extract the 32-hex-digit trace id from a traceparent
+pub fn trace_id(traceparent: &str) -> Option<&str> {
+    traceparent.split('-')
+        .nth(1)
+        .filter(|id| id.len() == 32)
+}
+
+fn random_hex(len: usize) -> String {
+    let mut hex = String::with_capacity(len);
+    while hex.len() < len {
+        hex.push_str(&uuid::Uuid::new_v4().simple().to_string());
+    }
+    hex.truncate(len);
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_trace_parent_format() {
+        let traceparent = outgoing_trace_parent();
+        let parts: Vec<_> = traceparent.split('-').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[1].len(), 32);
+        assert_eq!(parts[2].len(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_inherits_ambient_trace_id() {
+        let parent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let outgoing = with_trace_parent(parent, async { outgoing_trace_parent() }).await;
+
+        assert_eq!(trace_id(&outgoing), Some("0af7651916cd43dd8448eb211c80319c"));
+        assert_ne!(outgoing, parent);
+    }
+}
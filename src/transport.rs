@@ -1,12 +1,183 @@
 //! MCP transport implementations
 
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use async_trait::async_trait;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use parking_lot::Mutex;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf};
 use tokio::process::{Child, Command};
-use tracing::{debug, error};
+use tokio::sync::{broadcast, oneshot};
+use tracing::{debug, error, warn};
 
 use warhorn::McpServerConfig;
 use crate::error::McpError;
+use crate::tasks::{spawn_on, ReaderPool, TaskHandle};
+use crate::types::{ProcessExit, TrafficStats, PARTIAL_CONTENT_METHOD};
+
+/// Default maximum array/object nesting depth accepted from a server
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 64;
+
+/// Default number of stderr lines kept per stdio server
+pub const DEFAULT_STDERR_LINES: usize = 100;
+
+/// Default number of stderr bytes kept per stdio server
+pub const DEFAULT_STDERR_BYTES: usize = 64 * 1024;
+
+/// Stderr lines carried by `McpError::ProcessExited`
+const EXIT_STDERR_LINES: usize = 5;
+
+/// How long a failed request waits for the process to be reaped
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+
+/// Options controlling how a transport frames and parses messages
+#[derive(Debug, Clone)]
+pub struct TransportOptions {
+    /// Maximum array/object nesting depth of a server message.
+    ///
+    /// Messages nested deeper than this are rejected with
+    /// `McpError::ProtocolError` before being deserialized. Note that
+    /// serde_json enforces its own hard limit of 128 regardless.
+    pub max_json_depth: usize,
+    /// Byte that terminates each message on stream transports (default `\n`)
+    pub delimiter: u8,
+    /// Tolerate non-JSON output on the protocol stream.
+    ///
+    /// When enabled, frames that don't look like JSON (including ones with
+    /// invalid UTF-8) are logged lossily and skipped instead of closing the
+    /// connection. Frames that do look like JSON are still parsed strictly.
+    pub lossy_utf8: bool,
+    /// Process attributes applied when spawning stdio servers
+    pub spawn: SpawnOptions,
+    /// Which output of a stdio server carries the protocol (default stdout)
+    pub protocol_stream: ProtocolStream,
+    /// Most stderr lines kept per stdio server (see `recent_stderr`)
+    pub stderr_lines: usize,
+    /// Most stderr bytes kept per stdio server
+    pub stderr_bytes: usize,
+    /// Throttle for server-initiated messages (None disables throttling)
+    pub inbound_rate_limit: Option<InboundRateLimit>,
+    /// Run reader loops on this shared pool instead of a task per stream.
+    ///
+    /// Covers the protocol reader and stdio stderr reader of each transport,
+    /// and the connection's forwarding of server messages.
+    pub reader_pool: Option<ReaderPool>,
+    /// Coalesce requests issued within this window into one JSON-RPC batch.
+    ///
+    /// Only enable this for servers that accept batches. Notifications are
+    /// never batched.
+    pub batch_window: Option<Duration>,
+    /// Client used by HTTP transports (None builds a default one per transport)
+    pub http_client: Option<reqwest::Client>,
+}
+
+impl Default for TransportOptions {
+    fn default() -> Self {
+        Self {
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+            delimiter: b'\n',
+            lossy_utf8: false,
+            spawn: SpawnOptions::default(),
+            protocol_stream: ProtocolStream::default(),
+            stderr_lines: DEFAULT_STDERR_LINES,
+            stderr_bytes: DEFAULT_STDERR_BYTES,
+            inbound_rate_limit: None,
+            reader_pool: None,
+            batch_window: None,
+            http_client: None,
+        }
+    }
+}
+
+/// Guard against a server flooding us with notifications.
+///
+/// Once more than `max_per_second` server-initiated messages arrive within
+/// a second, further notifications are dropped until the next second.
+/// Responses to our requests, cancellations, `list_changed`, resource
+/// update and partial tool content notifications are always delivered.
+#[derive(Debug, Clone, Copy)]
+pub struct InboundRateLimit {
+    /// Messages per second admitted before dropping
+    pub max_per_second: u32,
+    /// Have `McpManager::health_check` mark the server unhealthy if it
+    /// dropped messages since the previous check
+    pub mark_unhealthy: bool,
+}
+
+impl Default for InboundRateLimit {
+    fn default() -> Self {
+        Self {
+            max_per_second: 1000,
+            mark_unhealthy: false,
+        }
+    }
+}
+
+/// Attributes of a spawned stdio server process.
+///
+/// The process group, niceness and resource limits are Unix-only; on other
+/// platforms they are ignored with a warning.
+///
+/// The server inherits the host's environment unless `clear_env` is set,
+/// minus any variable in `remove_env`. The config's `env` map is applied
+/// last, so its variables are set even if cleared or removed here.
+#[derive(Debug, Clone, Default)]
+pub struct SpawnOptions {
+    /// Start the server in its own process group, so signals sent to the
+    /// host's group (e.g. Ctrl-C) don't reach it
+    pub new_process_group: bool,
+    /// Niceness to run the server at
+    pub nice: Option<i32>,
+    /// Resource limits, each applied as both the soft and hard limit
+    pub rlimits: Vec<(ResourceLimit, u64)>,
+    /// Start from an empty environment instead of the host's
+    pub clear_env: bool,
+    /// Host environment variables the server doesn't inherit
+    pub remove_env: Vec<String>,
+    /// Working directory of the server (None inherits the host's)
+    pub cwd: Option<std::path::PathBuf>,
+}
+
+/// Which output stream of a stdio server JSON-RPC is read from.
+///
+/// The spec puts the protocol on stdout and leaves stderr for logs. The
+/// other settings are workarounds for nonconformant servers only; whichever
+/// stream isn't read as protocol is captured like stderr (see
+/// `recent_stderr`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProtocolStream {
+    /// Stdout, as the spec requires
+    #[default]
+    Stdout,
+    /// Stderr, for servers that write the protocol there
+    Stderr,
+    /// Both: lines that parse as JSON-RPC are protocol, the rest are logs
+    Both,
+}
+
+/// Resource limit that can be applied to a spawned server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceLimit {
+    /// CPU time, in seconds
+    CpuSeconds,
+    /// Virtual address space, in bytes
+    AddressSpace,
+    /// Number of open file descriptors
+    OpenFiles,
+}
+
+/// Buffer between the demultiplexer and the protocol reader for `ProtocolStream::Both`
+const DEMUX_BUFFER: usize = 64 * 1024;
+
+/// Frames read ahead of the protocol stream with `ProtocolStream::Both`;
+/// past this the readers stop reading until the client catches up
+const DEMUX_FRAMES: usize = 64;
+
+/// Capacity of the per-transport channel for server-initiated messages
+const INBOUND_CAPACITY: usize = 256;
 
 /// Transport trait for MCP communication
 #[async_trait]
@@ -17,35 +188,549 @@ pub trait McpTransport: Send + Sync {
     /// Send a notification (no response)
     async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError>;
     
-    /// Close the transport
-    async fn close(self: Box<Self>) -> Result<(), McpError>;
+    /// Subscribe to messages initiated by the server (notifications and requests).
+    ///
+    /// Transports that can't receive unsolicited messages keep the default,
+    /// which returns an already-closed receiver.
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        broadcast::channel(1).1
+    }
+    
+    /// Number of server messages dropped by the inbound rate limit
+    fn dropped_messages(&self) -> u64 {
+        0
+    }
+    
+    /// Most recent lines the server wrote to stderr, oldest first.
+    ///
+    /// Empty for transports without a server process.
+    fn recent_stderr(&self) -> Vec<String> {
+        Vec::new()
+    }
+    
+    /// How the server process ended, if it has.
+    ///
+    /// Always `None` for transports without a server process.
+    fn exit_status(&self) -> Option<ProcessExit> {
+        None
+    }
+    
+    /// Whether the transport runs a server process that can be asked to exit
+    fn has_process(&self) -> bool {
+        false
+    }
+    
+    /// Wait up to `timeout` for the server process to exit by itself.
+    ///
+    /// `None` if it is still running, and always for transports without a
+    /// server process.
+    async fn wait_for_exit(&self, _timeout: Duration) -> Option<ProcessExit> {
+        None
+    }
+    
+    /// Bytes written and read so far, framing included
+    fn traffic(&self) -> TrafficStats {
+        TrafficStats::default()
+    }
+    
+    /// Close the transport, failing any requests still awaiting a response
+    async fn close(&self) -> Result<(), McpError>;
+}
+
+/// Creates the transport for a connection, on first connect and on every reconnect
+#[async_trait]
+pub trait TransportFactory: Send + Sync {
+    /// Create a new transport for `config`
+    async fn create(
+        &self,
+        config: &McpServerConfig,
+        options: &TransportOptions,
+    ) -> Result<Arc<dyn McpTransport>, McpError>;
+}
+
+/// Default factory, building transports from the server config
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConfigTransportFactory;
+
+#[async_trait]
+impl TransportFactory for ConfigTransportFactory {
+    async fn create(
+        &self,
+        config: &McpServerConfig,
+        options: &TransportOptions,
+    ) -> Result<Arc<dyn McpTransport>, McpError> {
+        create_transport(config, options).await.map(Arc::from)
+    }
 }
 
 /// Create a transport from config
 pub async fn create_transport(
     config: &McpServerConfig,
+    options: &TransportOptions,
 ) -> Result<Box<dyn McpTransport>, McpError> {
     match &config.transport {
         warhorn::McpTransport::Stdio { command, args } => {
-            let transport = StdioTransport::new(command, args, &config.env).await?;
+            let transport = StdioTransport::new(command, args, &config.env, options.clone()).await?;
             Ok(Box::new(transport))
         }
+        #[cfg(unix)]
+        warhorn::McpTransport::Socket { path } => {
+            let transport = SocketTransport::connect(path, options.clone()).await?;
+            Ok(Box::new(transport))
+        }
+        #[cfg(not(unix))]
         warhorn::McpTransport::Socket { path: _ } => {
-            // Socket transport not yet implemented
-            Err(McpError::TransportError("Socket transport not implemented".into()))
+            Err(McpError::TransportError("Socket transport is only supported on Unix".into()))
+        }
+        warhorn::McpTransport::Http { url } => {
+            let transport = crate::http::HttpTransport::new(url.clone(), options.clone())?;
+            Ok(Box::new(transport))
+        }
+    }
+}
+
+/// Write half shared with the batch flush task
+type SharedWriter = Arc<tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// Requests awaiting a response, keyed by serialized JSON-RPC id
+type PendingMap = Arc<Mutex<HashMap<String, oneshot::Sender<Result<serde_json::Value, McpError>>>>>;
+
+/// Delimited JSON-RPC over an arbitrary byte stream.
+///
+/// A background task reads incoming frames, routes each response to the
+/// request with the matching `id`, and broadcasts everything else
+/// (notifications and server-initiated requests) to subscribers. This makes
+/// it safe to issue overlapping requests on one stream.
+pub struct StreamTransport {
+    writer: SharedWriter,
+    /// Requests waiting for the current batch window to close
+    batch: Arc<Mutex<Vec<serde_json::Value>>>,
+    pending: PendingMap,
+    closed: Arc<AtomicBool>,
+    inbound: broadcast::Sender<serde_json::Value>,
+    dropped: Arc<AtomicU64>,
+    bytes_sent: Arc<AtomicU64>,
+    bytes_received: Arc<AtomicU64>,
+    reader: TaskHandle,
+    options: TransportOptions,
+}
+
+impl StreamTransport {
+    /// Create a transport over a read half and a write half
+    pub fn new<R, W>(reader: R, writer: W, options: TransportOptions) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let pending = PendingMap::default();
+        let closed = Arc::new(AtomicBool::new(false));
+        let (inbound, _) = broadcast::channel(INBOUND_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let bytes_received = Arc::new(AtomicU64::new(0));
+
+        let reader = CountingReader {
+            inner: reader,
+            count: bytes_received.clone(),
+        };
+        let reader = spawn_on(options.reader_pool.as_ref(), read_loop(
+            BufReader::new(reader),
+            pending.clone(),
+            closed.clone(),
+            inbound.clone(),
+            dropped.clone(),
+            options.clone(),
+        ));
+
+        Self {
+            writer: Arc::new(tokio::sync::Mutex::new(Box::new(writer))),
+            batch: Arc::default(),
+            pending,
+            closed,
+            inbound,
+            dropped,
+            bytes_sent: Arc::default(),
+            bytes_received,
+            reader,
+            options,
         }
-        warhorn::McpTransport::Http { url: _ } => {
-            // HTTP transport not yet implemented
-            Err(McpError::TransportError("HTTP transport not implemented".into()))
+    }
+
+    /// Serialize and write a single framed message
+    async fn write_message(&self, message: &serde_json::Value) -> Result<(), McpError> {
+        write_framed(&self.writer, message, self.options.delimiter, &self.bytes_sent).await
+    }
+
+    /// Add a request to the current batch, opening a new window if needed.
+    ///
+    /// The batch is written from a separate task so that cancelling the
+    /// request that opened the window can't strand the others.
+    fn queue_batched(&self, request: serde_json::Value, window: Duration) {
+        {
+            let mut batch = self.batch.lock();
+            batch.push(request);
+            if batch.len() > 1 {
+                return;
+            }
         }
+
+        let batch = self.batch.clone();
+        let writer = self.writer.clone();
+        let pending = self.pending.clone();
+        let delimiter = self.options.delimiter;
+        let bytes_sent = self.bytes_sent.clone();
+        spawn_on(self.options.reader_pool.as_ref(), async move {
+            tokio::time::sleep(window).await;
+
+            let mut requests = std::mem::take(&mut *batch.lock());
+            let keys: Vec<_> = requests.iter().map(|request| request_key(&request["id"])).collect();
+            let message = match requests.len() {
+                1 => requests.remove(0),
+                _ => serde_json::Value::Array(requests),
+            };
+            if let Err(e) = write_framed(&writer, &message, delimiter, &bytes_sent).await {
+                warn!(error = %e, "Failed to write request batch");
+                for key in &keys {
+                    if let Some(tx) = pending.lock().remove(key) {
+                        let _ = tx.send(Err(McpError::TransportError(e.to_string())));
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Serialize `message` and write it followed by `delimiter`, counting the bytes in `sent`
+async fn write_framed(
+    writer: &SharedWriter,
+    message: &serde_json::Value,
+    delimiter: u8,
+    sent: &AtomicU64,
+) -> Result<(), McpError> {
+    let mut bytes = serde_json::to_vec(message)
+        .map_err(|e| McpError::ProtocolError(format!("JSON error: {}", e)))?;
+    bytes.push(delimiter);
+
+    let mut writer = writer.lock().await;
+    writer.write_all(&bytes).await
+        .map_err(|e| McpError::TransportError(format!("Write error: {}", e)))?;
+    sent.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+    writer.flush().await
+        .map_err(|e| McpError::TransportError(format!("Flush error: {}", e)))?;
+    Ok(())
+}
+
+#[async_trait]
+impl McpTransport for StreamTransport {
+    async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let key = request_key(&request["id"]);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().insert(key.clone(), tx);
+        let _guard = PendingGuard { pending: &self.pending, key };
+
+        // Checked after registering so we can't miss the reader's final drain
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(McpError::TransportError("Connection closed".into()));
+        }
+
+        match self.options.batch_window {
+            Some(window) => self.queue_batched(request, window),
+            None => self.write_message(&request).await?,
+        }
+
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => Err(McpError::TransportError("Connection closed".into())),
+        }
+    }
+
+    async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
+        self.write_message(&notification).await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.inbound.subscribe()
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        TrafficStats {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.closed.store(true, Ordering::SeqCst);
+        self.reader.abort();
+        fail_pending(&self.pending, || McpError::TransportError("Transport closed".into()));
+
+        let _ = self.writer.lock().await.shutdown().await;
+        Ok(())
+    }
+}
+
+impl Drop for StreamTransport {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Counts every byte read through it
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            self.count.fetch_add((buf.filled().len() - before) as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+/// Removes a pending entry if the request future is dropped before completion
+struct PendingGuard<'a> {
+    pending: &'a PendingMap,
+    key: String,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().remove(&self.key);
+    }
+}
+
+/// Key used to correlate a response with its request
+fn request_key(id: &serde_json::Value) -> String {
+    id.to_string()
+}
+
+/// Fail every pending request with the error produced by `error`
+fn fail_pending(pending: &PendingMap, error: impl Fn() -> McpError) {
+    for (_, tx) in pending.lock().drain() {
+        let _ = tx.send(Err(error()));
+    }
+}
+
+/// Read frames until EOF or a fatal error, dispatching each message
+async fn read_loop<R>(
+    mut reader: R,
+    pending: PendingMap,
+    closed: Arc<AtomicBool>,
+    inbound: broadcast::Sender<serde_json::Value>,
+    dropped: Arc<AtomicU64>,
+    options: TransportOptions,
+) where
+    R: AsyncBufRead + Unpin,
+{
+    let mut window = RateWindow::new();
+    let reason = loop {
+        let frame = match read_frame(&mut reader, options.delimiter).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break McpError::TransportError("Connection closed by server".into()),
+            Err(e) => break e,
+        };
+
+        let messages = match parse_message(&frame, options.max_json_depth) {
+            // A batch: rate-limit and route each message individually
+            Ok(serde_json::Value::Array(messages)) => messages,
+            Ok(message) => vec![message],
+            Err(_) if options.lossy_utf8 && !looks_like_json(&frame) => {
+                debug!(line = %String::from_utf8_lossy(&frame), "Ignoring non-JSON server output");
+                continue;
+            }
+            Err(e) => {
+                error!(error = %e, "Invalid message from server, closing connection");
+                break e;
+            }
+        };
+        for message in messages {
+            if admit_inbound(&message, &mut window, &options, &dropped) {
+                dispatch(message, &pending, &inbound);
+            }
+        }
+    };
+
+    debug!(reason = %reason, "Transport reader stopped");
+    closed.store(true, Ordering::SeqCst);
+
+    let (is_protocol, detail) = match reason {
+        McpError::ProtocolError(detail) => (true, detail),
+        McpError::TransportError(detail) => (false, detail),
+        other => (false, other.to_string()),
+    };
+    fail_pending(&pending, || {
+        if is_protocol {
+            McpError::ProtocolError(detail.clone())
+        } else {
+            McpError::TransportError(detail.clone())
+        }
+    });
+}
+
+/// Apply the inbound rate limit to `message`, counting it if dropped
+fn admit_inbound(
+    message: &serde_json::Value,
+    window: &mut RateWindow,
+    options: &TransportOptions,
+    dropped: &AtomicU64,
+) -> bool {
+    let Some(limit) = &options.inbound_rate_limit else {
+        return true;
+    };
+    if is_response(message) || window.admit(limit.max_per_second) || !is_droppable(message) {
+        return true;
+    }
+    dropped.fetch_add(1, Ordering::Relaxed);
+    if window.first_drop() {
+        warn!(limit = limit.max_per_second, "Server exceeded inbound message rate, dropping notifications");
+    }
+    false
+}
+
+/// Whether a frame starts like a JSON object or array
+fn looks_like_json(frame: &[u8]) -> bool {
+    matches!(
+        frame.iter().find(|b| !b.is_ascii_whitespace()),
+        Some(b'{') | Some(b'[')
+    )
+}
+
+/// Fixed one-second window counting server-initiated messages
+struct RateWindow {
+    started: Instant,
+    count: u32,
+    warned: bool,
+}
+
+impl RateWindow {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            count: 0,
+            warned: false,
+        }
+    }
+
+    /// Count a message, returning whether it fits in the current window
+    fn admit(&mut self, max_per_second: u32) -> bool {
+        if self.started.elapsed() >= Duration::from_secs(1) {
+            *self = Self::new();
+        }
+        self.count += 1;
+        self.count <= max_per_second
+    }
+
+    /// Whether this is the first drop in the current window (to warn once per window)
+    fn first_drop(&mut self) -> bool {
+        !std::mem::replace(&mut self.warned, true)
+    }
+}
+
+/// Whether a message answers one of our requests
+fn is_response(message: &serde_json::Value) -> bool {
+    message.get("method").is_none() && message.get("id").is_some()
+}
+
+/// Whether a message may be shed under the inbound rate limit
+fn is_droppable(message: &serde_json::Value) -> bool {
+    if message.get("id").is_some() {
+        // Server-initiated requests expect an answer
+        return false;
+    }
+    let method = message.get("method").and_then(|m| m.as_str()).unwrap_or_default();
+    !(method == "notifications/cancelled"
+        || method == "notifications/resources/updated"
+        || method == PARTIAL_CONTENT_METHOD
+        || method.ends_with("/list_changed"))
+}
+
+/// Route a message to its waiting request or to inbound subscribers.
+///
+/// A response whose id matches no outstanding request (unsolicited, or for
+/// a request that was already cancelled) is logged and dropped; it never
+/// touches other pending entries or stops the reader.
+fn dispatch(
+    message: serde_json::Value,
+    pending: &PendingMap,
+    inbound: &broadcast::Sender<serde_json::Value>,
+) {
+    if !is_response(&message) {
+        // No subscribers is fine; nobody is interested in this message
+        let _ = inbound.send(message);
+        return;
+    }
+
+    let key = request_key(&message["id"]);
+    let waiter = pending.lock().remove(&key);
+    match waiter {
+        Some(tx) => {
+            let _ = tx.send(Ok(message));
+        }
+        None => warn!(id = %key, "Dropping response with no matching request"),
     }
 }
 
 /// Stdio-based transport (spawns a child process)
 pub struct StdioTransport {
     child: tokio::sync::Mutex<Child>,
-    stdin: tokio::sync::Mutex<tokio::process::ChildStdin>,
-    stdout: tokio::sync::Mutex<BufReader<tokio::process::ChildStdout>>,
+    stream: StreamTransport,
+    stderr: Arc<Mutex<StderrBuffer>>,
+    /// Becomes true once stderr hits EOF
+    stderr_done: tokio::sync::watch::Receiver<bool>,
+    /// Set once the process has been reaped
+    exit: std::sync::OnceLock<ProcessExit>,
+    /// Set by `close`, whose kill isn't worth reporting
+    closing: AtomicBool,
+}
+
+/// Tail of a server's stderr, bounded by line count and total bytes
+struct StderrBuffer {
+    lines: VecDeque<String>,
+    bytes: usize,
+    max_lines: usize,
+    max_bytes: usize,
+}
+
+impl StderrBuffer {
+    fn new(max_lines: usize, max_bytes: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            bytes: 0,
+            max_lines,
+            max_bytes,
+        }
+    }
+
+    fn push(&mut self, mut line: String) {
+        if line.len() > self.max_bytes {
+            let mut end = self.max_bytes;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+        }
+        
+        self.bytes += line.len();
+        self.lines.push_back(line);
+        while self.lines.len() > self.max_lines || self.bytes > self.max_bytes {
+            let Some(oldest) = self.lines.pop_front() else {
+                break;
+            };
+            self.bytes -= oldest.len();
+        }
+    }
 }
 
 impl StdioTransport {
@@ -54,6 +739,7 @@ impl StdioTransport {
         command: &str,
         args: &[String],
         env: &std::collections::HashMap<String, String>,
+        options: TransportOptions,
     ) -> Result<Self, McpError> {
         debug!(command = %command, "Starting MCP server process");
         
@@ -61,13 +747,30 @@ impl StdioTransport {
         cmd.args(args)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::piped())
             .kill_on_drop(true);
         
+        if let Some(dir) = &options.spawn.cwd {
+            if !dir.is_dir() {
+                return Err(McpError::TransportError(format!(
+                    "Working directory {} does not exist or is not a directory",
+                    dir.display()
+                )));
+            }
+            cmd.current_dir(dir);
+        }
+        if options.spawn.clear_env {
+            cmd.env_clear();
+        }
+        for key in &options.spawn.remove_env {
+            cmd.env_remove(key);
+        }
         for (key, value) in env {
             cmd.env(key, value);
         }
         
+        apply_spawn_options(&mut cmd, &options.spawn);
+        
         let mut child = cmd.spawn()
             .map_err(|e| McpError::TransportError(format!("Failed to spawn: {}", e)))?;
         
@@ -75,69 +778,1003 @@ impl StdioTransport {
             .ok_or_else(|| McpError::TransportError("No stdin".into()))?;
         let stdout = child.stdout.take()
             .ok_or_else(|| McpError::TransportError("No stdout".into()))?;
+        let stderr = child.stderr.take()
+            .ok_or_else(|| McpError::TransportError("No stderr".into()))?;
+        
+        let buffer = Arc::new(Mutex::new(StderrBuffer::new(options.stderr_lines, options.stderr_bytes)));
+        let source: Arc<str> = Arc::from(command);
+        let (done_tx, stderr_done) = tokio::sync::watch::channel(false);
+        let stream = match options.protocol_stream {
+            ProtocolStream::Stdout => {
+                spawn_log_reader(stderr, source, buffer.clone(), done_tx, options.reader_pool.as_ref());
+                StreamTransport::new(stdout, stdin, options)
+            }
+            ProtocolStream::Stderr => {
+                spawn_log_reader(stdout, source, buffer.clone(), done_tx, options.reader_pool.as_ref());
+                StreamTransport::new(stderr, stdin, options)
+            }
+            ProtocolStream::Both => {
+                let protocol = spawn_demux(
+                    stdout,
+                    stderr,
+                    source,
+                    options.delimiter,
+                    buffer.clone(),
+                    done_tx,
+                    options.reader_pool.as_ref(),
+                );
+                StreamTransport::new(protocol, stdin, options)
+            }
+        };
         
         Ok(Self {
             child: tokio::sync::Mutex::new(child),
-            stdin: tokio::sync::Mutex::new(stdin),
-            stdout: tokio::sync::Mutex::new(BufReader::new(stdout)),
+            stream,
+            stderr: buffer,
+            stderr_done,
+            exit: std::sync::OnceLock::new(),
+            closing: AtomicBool::new(false),
         })
     }
+
+    /// Turn a transport failure into `McpError::ProcessExited` if the server died.
+    ///
+    /// Waits briefly for the process to be reaped, since the pipes usually
+    /// close a moment before that happens.
+    async fn explain_failure(&self, error: McpError) -> McpError {
+        if self.closing.load(Ordering::SeqCst) {
+            return error;
+        }
+        let exit = match self.exit.get() {
+            Some(exit) => *exit,
+            None => {
+                let mut child = self.child.lock().await;
+                match tokio::time::timeout(EXIT_GRACE, child.wait()).await {
+                    Ok(Ok(status)) => {
+                        let exit = *self.exit.get_or_init(|| process_exit(status));
+                        warn!(status = %exit, "Server process exited");
+                        exit
+                    }
+                    _ => return error,
+                }
+            }
+        };
+        
+        // Let the stderr reader drain what the process wrote before dying
+        let mut stderr_done = self.stderr_done.clone();
+        let _ = tokio::time::timeout(EXIT_GRACE, stderr_done.wait_for(|done| *done)).await;
+        
+        let stderr = self.recent_stderr();
+        let skip = stderr.len().saturating_sub(EXIT_STDERR_LINES);
+        McpError::ProcessExited {
+            status: exit,
+            stderr: stderr.into_iter().skip(skip).collect(),
+        }
+    }
+}
+
+/// Capture a server's log output into `tail` until EOF, then signal `done`.
+///
+/// Each line is also traced, tagged with the server's `command`.
+fn spawn_log_reader<R>(
+    stream: R,
+    command: Arc<str>,
+    tail: Arc<Mutex<StderrBuffer>>,
+    done: tokio::sync::watch::Sender<bool>,
+    pool: Option<&ReaderPool>,
+) where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    // Ends on its own at EOF, once the process exits
+    spawn_on(pool, async move {
+        let mut stream = BufReader::new(stream);
+        while let Ok(Some(line)) = read_frame(&mut stream, b'\n').await {
+            let line = String::from_utf8_lossy(&line).into_owned();
+            debug!(command = %command, line = %line, "Server stderr");
+            tail.lock().push(line);
+        }
+        let _ = done.send(true);
+    });
+}
+
+/// Merge the JSON-RPC frames of stdout and stderr into one protocol stream.
+///
+/// Frames that aren't JSON-RPC go to `tail` as logs. `done` fires once both
+/// outputs hit EOF, just before the returned stream does.
+fn spawn_demux<O, E>(
+    stdout: O,
+    stderr: E,
+    command: Arc<str>,
+    delimiter: u8,
+    tail: Arc<Mutex<StderrBuffer>>,
+    done: tokio::sync::watch::Sender<bool>,
+    pool: Option<&ReaderPool>,
+) -> tokio::io::DuplexStream
+where
+    O: AsyncRead + Send + Unpin + 'static,
+    E: AsyncRead + Send + Unpin + 'static,
+{
+    let (protocol, mut sink) = tokio::io::duplex(DEMUX_BUFFER);
+    let (frames, mut merged) = tokio::sync::mpsc::channel::<Vec<u8>>(DEMUX_FRAMES);
+    spawn_demux_reader(stdout, "stdout", command.clone(), delimiter, frames.clone(), tail.clone(), pool);
+    spawn_demux_reader(stderr, "stderr", command, delimiter, frames, tail, pool);
+    
+    spawn_on(pool, async move {
+        while let Some(mut frame) = merged.recv().await {
+            frame.push(delimiter);
+            if sink.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+        let _ = done.send(true);
+    });
+    protocol
+}
+
+/// Forward one output's JSON-RPC frames to `frames`, logging everything else
+fn spawn_demux_reader<R>(
+    stream: R,
+    name: &'static str,
+    command: Arc<str>,
+    delimiter: u8,
+    frames: tokio::sync::mpsc::Sender<Vec<u8>>,
+    tail: Arc<Mutex<StderrBuffer>>,
+    pool: Option<&ReaderPool>,
+) where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    spawn_on(pool, async move {
+        let mut stream = BufReader::new(stream);
+        while let Ok(Some(frame)) = read_frame(&mut stream, delimiter).await {
+            if is_json_rpc(&frame) {
+                if frames.send(frame).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            let line = String::from_utf8_lossy(&frame).into_owned();
+            debug!(command = %command, stream = name, line = %line, "Server output");
+            tail.lock().push(line);
+        }
+    });
+}
+
+/// Whether a frame is a JSON-RPC message or batch
+fn is_json_rpc(frame: &[u8]) -> bool {
+    let is_message = |value: &serde_json::Value| value.get("jsonrpc").and_then(|v| v.as_str()) == Some("2.0");
+    match serde_json::from_slice::<serde_json::Value>(frame) {
+        Ok(serde_json::Value::Array(batch)) => !batch.is_empty() && batch.iter().all(is_message),
+        Ok(message) => is_message(&message),
+        Err(_) => false,
+    }
+}
+
+#[cfg(unix)]
+fn apply_spawn_options(cmd: &mut Command, spawn: &SpawnOptions) {
+    if spawn.new_process_group {
+        cmd.process_group(0);
+    }
+    if spawn.nice.is_none() && spawn.rlimits.is_empty() {
+        return;
+    }
+    
+    let nice = spawn.nice;
+    let rlimits = spawn.rlimits.clone();
+    // SAFETY: the closure only makes async-signal-safe libc calls and
+    // doesn't allocate
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(nice) = nice {
+                if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            for &(limit, value) in &rlimits {
+                let resource = match limit {
+                    ResourceLimit::CpuSeconds => libc::RLIMIT_CPU,
+                    ResourceLimit::AddressSpace => libc::RLIMIT_AS,
+                    ResourceLimit::OpenFiles => libc::RLIMIT_NOFILE,
+                };
+                let rlimit = libc::rlimit {
+                    rlim_cur: value as libc::rlim_t,
+                    rlim_max: value as libc::rlim_t,
+                };
+                if libc::setrlimit(resource, &rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_spawn_options(_cmd: &mut Command, spawn: &SpawnOptions) {
+    if spawn.new_process_group || spawn.nice.is_some() || !spawn.rlimits.is_empty() {
+        warn!("Spawn attributes are only supported on Unix, ignoring");
+    }
 }
 
 #[async_trait]
 impl McpTransport for StdioTransport {
     async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
-        let request_str = serde_json::to_string(&request)
-            .map_err(|e| McpError::ProtocolError(format!("JSON error: {}", e)))?;
-        
-        // Send request
-        {
-            let mut stdin = self.stdin.lock().await;
-            stdin.write_all(request_str.as_bytes()).await
-                .map_err(|e| McpError::TransportError(format!("Write error: {}", e)))?;
-            stdin.write_all(b"\n").await
-                .map_err(|e| McpError::TransportError(format!("Write error: {}", e)))?;
-            stdin.flush().await
-                .map_err(|e| McpError::TransportError(format!("Flush error: {}", e)))?;
-        }
-        
-        // Read response
-        let mut response_line = String::new();
-        {
-            let mut stdout = self.stdout.lock().await;
-            stdout.read_line(&mut response_line).await
-                .map_err(|e| McpError::TransportError(format!("Read error: {}", e)))?;
+        match self.stream.send_request(request).await {
+            Err(e @ McpError::TransportError(_)) => Err(self.explain_failure(e).await),
+            result => result,
         }
-        
-        let response: serde_json::Value = serde_json::from_str(&response_line)
-            .map_err(|e| McpError::ProtocolError(format!("Invalid JSON response: {}", e)))?;
-        
-        Ok(response)
     }
 
     async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
-        let notification_str = serde_json::to_string(&notification)
-            .map_err(|e| McpError::ProtocolError(format!("JSON error: {}", e)))?;
-        
-        let mut stdin = self.stdin.lock().await;
-        stdin.write_all(notification_str.as_bytes()).await
-            .map_err(|e| McpError::TransportError(format!("Write error: {}", e)))?;
-        stdin.write_all(b"\n").await
-            .map_err(|e| McpError::TransportError(format!("Write error: {}", e)))?;
-        stdin.flush().await
-            .map_err(|e| McpError::TransportError(format!("Flush error: {}", e)))?;
-        
-        Ok(())
+        match self.stream.send_notification(notification).await {
+            Err(e @ McpError::TransportError(_)) => Err(self.explain_failure(e).await),
+            result => result,
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.stream.subscribe()
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.stream.dropped_messages()
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        self.stream.traffic()
+    }
+
+    fn recent_stderr(&self) -> Vec<String> {
+        self.stderr.lock().lines.iter().cloned().collect()
+    }
+
+    fn exit_status(&self) -> Option<ProcessExit> {
+        if let Some(exit) = self.exit.get() {
+            return Some(*exit);
+        }
+        let mut child = self.child.try_lock().ok()?;
+        let status = child.try_wait().ok()??;
+        Some(*self.exit.get_or_init(|| process_exit(status)))
+    }
+
+    fn has_process(&self) -> bool {
+        true
     }
 
-    async fn close(self: Box<Self>) -> Result<(), McpError> {
+    async fn wait_for_exit(&self, timeout: Duration) -> Option<ProcessExit> {
+        if let Some(exit) = self.exit.get() {
+            return Some(*exit);
+        }
+        let mut child = self.child.lock().await;
+        let status = tokio::time::timeout(timeout, child.wait()).await.ok()?.ok()?;
+        Some(*self.exit.get_or_init(|| process_exit(status)))
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.closing.store(true, Ordering::SeqCst);
+        self.stream.close().await?;
         let mut child = self.child.lock().await;
         let _ = child.kill().await;
         Ok(())
     }
 }
 
+/// Newline-delimited JSON-RPC over a Unix domain socket the server listens on
+#[cfg(unix)]
+pub struct SocketTransport {
+    stream: StreamTransport,
+}
+
+#[cfg(unix)]
+impl SocketTransport {
+    /// Connect to the server's socket at `path`
+    pub async fn connect(
+        path: impl AsRef<std::path::Path>,
+        options: TransportOptions,
+    ) -> Result<Self, McpError> {
+        let path = path.as_ref();
+        debug!(path = %path.display(), "Connecting to MCP server socket");
+
+        let socket = tokio::net::UnixStream::connect(path).await
+            .map_err(|e| McpError::TransportError(format!(
+                "Failed to connect to socket {}: {}",
+                path.display(),
+                e
+            )))?;
+        let (reader, writer) = socket.into_split();
+        Ok(Self {
+            stream: StreamTransport::new(reader, writer, options),
+        })
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl McpTransport for SocketTransport {
+    async fn send_request(&self, request: serde_json::Value) -> Result<serde_json::Value, McpError> {
+        self.stream.send_request(request).await
+    }
+
+    async fn send_notification(&self, notification: serde_json::Value) -> Result<(), McpError> {
+        self.stream.send_notification(notification).await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.stream.subscribe()
+    }
+
+    fn dropped_messages(&self) -> u64 {
+        self.stream.dropped_messages()
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        self.stream.traffic()
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.stream.close().await
+    }
+}
+
+#[cfg(unix)]
+fn process_exit(status: std::process::ExitStatus) -> ProcessExit {
+    use std::os::unix::process::ExitStatusExt;
+    ProcessExit { code: status.code(), signal: status.signal() }
+}
+
+#[cfg(not(unix))]
+fn process_exit(status: std::process::ExitStatus) -> ProcessExit {
+    ProcessExit { code: status.code(), signal: None }
+}
+
+/// Read one delimiter-terminated frame from a stream.
+///
+/// The delimiter is stripped and blank frames are skipped. A final frame
+/// with no trailing delimiter is still returned when the stream hits EOF;
+/// `Ok(None)` means EOF with nothing left to read.
+pub async fn read_frame<R>(reader: &mut R, delimiter: u8) -> Result<Option<Vec<u8>>, McpError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut frame = Vec::new();
+    loop {
+        let read = reader.read_until(delimiter, &mut frame).await
+            .map_err(|e| McpError::TransportError(format!("Read error: {}", e)))?;
+        if read == 0 {
+            return Ok(if frame.is_empty() { None } else { Some(frame) });
+        }
+
+        if frame.last() == Some(&delimiter) {
+            frame.pop();
+        }
+        if frame.iter().all(|b| b.is_ascii_whitespace()) {
+            frame.clear();
+            continue;
+        }
+        return Ok(Some(frame));
+    }
+}
+
+/// Parse a JSON message received from a server, enforcing a nesting limit
+pub fn parse_message(bytes: &[u8], max_depth: usize) -> Result<serde_json::Value, McpError> {
+    check_json_depth(bytes, max_depth)?;
+    serde_json::from_slice(bytes)
+        .map_err(|e| McpError::ProtocolError(format!("Invalid JSON response: {}", e)))
+}
+
+/// Pre-scan raw JSON and reject it if arrays/objects nest deeper than `max_depth`.
+///
+/// This runs before deserialization so a hostile server can't make us
+/// recurse arbitrarily. Brackets inside string literals are ignored.
+fn check_json_depth(bytes: &[u8], max_depth: usize) -> Result<(), McpError> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &byte in bytes {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(McpError::ProtocolError(format!(
+                        "Message exceeds maximum JSON depth of {}",
+                        max_depth
+                    )));
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    // Transport tests would require mock processes
+    use super::*;
+
+    fn nested(depth: usize) -> String {
+        format!("{}{}", "[".repeat(depth), "]".repeat(depth))
+    }
+
+    #[test]
+    fn test_parse_message_within_depth() {
+        let value = parse_message(nested(8).as_bytes(), 8).unwrap();
+        assert!(value.is_array());
+    }
+
+    #[test]
+    fn test_parse_message_exceeds_depth() {
+        let err = parse_message(nested(9).as_bytes(), 8).unwrap_err();
+        assert!(matches!(err, McpError::ProtocolError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_unterminated_final_message() {
+        let mut input: &[u8] = b"{\"id\":1}\n\n{\"id\":2}";
+
+        let first = read_frame(&mut input, b'\n').await.unwrap().unwrap();
+        assert_eq!(first, b"{\"id\":1}");
+        let last = read_frame(&mut input, b'\n').await.unwrap().unwrap();
+        assert_eq!(last, b"{\"id\":2}");
+        assert!(read_frame(&mut input, b'\n').await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_custom_delimiter() {
+        let mut input: &[u8] = b"{\"id\":1}\0{\"id\":2}\0";
+
+        let first = read_frame(&mut input, 0).await.unwrap().unwrap();
+        assert_eq!(parse_message(&first, 8).unwrap()["id"], 1);
+        let second = read_frame(&mut input, 0).await.unwrap().unwrap();
+        assert_eq!(parse_message(&second, 8).unwrap()["id"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream_transport_routes_by_id() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+
+        let transport = StreamTransport::new(client_read, client_write, TransportOptions::default());
+        let mut inbound = transport.subscribe();
+
+        let server = async {
+            let frame = read_frame(&mut server_read, b'\n').await.unwrap().unwrap();
+            let request = parse_message(&frame, 8).unwrap();
+
+            // A notification arriving first must not be mistaken for the reply
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": {"level": "info", "data": "hello"}
+            });
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": {"ok": true}
+            });
+            server_write.write_all(format!("{}\n{}\n", notification, response).as_bytes()).await.unwrap();
+        };
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 7, "method": "ping"});
+        let (response, _) = tokio::join!(transport.send_request(request), server);
+
+        assert_eq!(response.unwrap()["result"]["ok"], true);
+        assert_eq!(inbound.recv().await.unwrap()["method"], "notifications/message");
+    }
+
+    /// Send one request through a server that emits a garbage line before replying
+    async fn request_after_garbage(options: TransportOptions) -> Result<serde_json::Value, McpError> {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+
+        let transport = StreamTransport::new(client_read, client_write, options);
+
+        let server = async {
+            let frame = read_frame(&mut server_read, b'\n').await.unwrap().unwrap();
+            let request = parse_message(&frame, 8).unwrap();
+            let response = serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {}});
+
+            server_write.write_all(b"warning: \xff\xfe bad bytes\n").await.unwrap();
+            server_write.write_all(format!("{}\n", response).as_bytes()).await.unwrap();
+        };
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+        let (response, _) = tokio::join!(transport.send_request(request), server);
+        response
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_rejects_garbage() {
+        let result = request_after_garbage(TransportOptions::default()).await;
+        assert!(matches!(result, Err(McpError::ProtocolError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_lossy_mode_skips_garbage() {
+        let options = TransportOptions {
+            lossy_utf8: true,
+            ..Default::default()
+        };
+        assert!(request_after_garbage(options).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stream_transport_fails_pending_on_eof() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, client_write) = tokio::io::split(client);
+        let transport = StreamTransport::new(client_read, client_write, TransportOptions::default());
+
+        drop(server);
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+        assert!(transport.send_request(request).await.is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_stdio_spawn_nice() {
+        // Answer one request with the niceness the server runs at
+        let script = r#"read line; printf '{"jsonrpc":"2.0","id":0,"result":{"nice":%s}}\n' "$(nice)""#;
+        let options = TransportOptions {
+            spawn: SpawnOptions {
+                new_process_group: true,
+                nice: Some(5),
+                rlimits: vec![(ResourceLimit::OpenFiles, 256)],
+                ..SpawnOptions::default()
+            },
+            ..TransportOptions::default()
+        };
+        let transport = StdioTransport::new(
+            "sh",
+            &["-c".to_string(), script.to_string()],
+            &HashMap::new(),
+            options,
+        ).await.unwrap();
+
+        let response = transport
+            .send_request(serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": "probe"}))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["nice"], 5);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_spawn_environment() {
+        // Answer one request with the server's view of KEEP and HOME
+        let script = r#"read line; printf '{"jsonrpc":"2.0","id":0,"result":{"env":"%s"}}\n' "${KEEP:-}-${HOME:-unset}""#;
+        let env = HashMap::from([("KEEP".to_string(), "1".to_string())]);
+        let cleared = SpawnOptions { clear_env: true, ..SpawnOptions::default() };
+        let removed = SpawnOptions { remove_env: vec!["HOME".into(), "KEEP".into()], ..SpawnOptions::default() };
+
+        for spawn in [cleared, removed] {
+            let options = TransportOptions { spawn, ..TransportOptions::default() };
+            let transport = StdioTransport::new(
+                "/bin/sh",
+                &["-c".to_string(), script.to_string()],
+                &env,
+                options,
+            ).await.unwrap();
+
+            let response = transport
+                .send_request(serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": "probe"}))
+                .await
+                .unwrap();
+            assert_eq!(response["result"]["env"], "1-unset");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_spawn_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        // Answer one request with the server's working directory
+        let script = r#"read line; printf '{"jsonrpc":"2.0","id":0,"result":{"cwd":"%s"}}\n' "$(pwd -P)""#;
+        let options = TransportOptions {
+            spawn: SpawnOptions { cwd: Some(dir.path().to_path_buf()), ..SpawnOptions::default() },
+            ..TransportOptions::default()
+        };
+        let transport = StdioTransport::new(
+            "sh",
+            &["-c".to_string(), script.to_string()],
+            &HashMap::new(),
+            options,
+        ).await.unwrap();
+
+        let response = transport
+            .send_request(serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": "probe"}))
+            .await
+            .unwrap();
+        let expected = dir.path().canonicalize().unwrap();
+        assert_eq!(response["result"]["cwd"], expected.to_str().unwrap());
+
+        let options = TransportOptions {
+            spawn: SpawnOptions { cwd: Some(dir.path().join("missing")), ..SpawnOptions::default() },
+            ..TransportOptions::default()
+        };
+        let Err(err) = StdioTransport::new("sh", &[], &HashMap::new(), options).await else {
+            panic!("spawned in a missing directory");
+        };
+        assert!(matches!(err, McpError::TransportError(ref msg) if msg.contains("missing")));
+    }
+
+    #[tokio::test]
+    async fn test_unsolicited_response_is_dropped() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+
+        let transport = StreamTransport::new(client_read, client_write, TransportOptions::default());
+
+        // Sent before any request exists
+        let unsolicited = serde_json::json!({"jsonrpc": "2.0", "id": 99, "result": {"bogus": true}});
+        server_write.write_all(format!("{}\n", unsolicited).as_bytes()).await.unwrap();
+
+        let server = async {
+            let frame = read_frame(&mut server_read, b'\n').await.unwrap().unwrap();
+            let request = parse_message(&frame, 8).unwrap();
+            let response = serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {"ok": true}});
+            server_write.write_all(format!("{}\n", response).as_bytes()).await.unwrap();
+        };
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+        let (response, _) = tokio::join!(transport.send_request(request), server);
+
+        assert_eq!(response.unwrap()["result"]["ok"], true);
+        assert!(transport.pending.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_traffic_counts_framing() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+
+        let transport = StreamTransport::new(client_read, client_write, TransportOptions::default());
+
+        // The blank line counts as traffic too
+        let reply = r#"{"jsonrpc":"2.0","id":1,"result":{}}"#;
+        let incoming = format!("\n{}\n", reply);
+        let server = async {
+            let frame = read_frame(&mut server_read, b'\n').await.unwrap().unwrap();
+            server_write.write_all(incoming.as_bytes()).await.unwrap();
+            frame.len() as u64 + 1
+        };
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+        let (response, written) = tokio::join!(transport.send_request(request), server);
+        response.unwrap();
+
+        assert_eq!(transport.traffic(), TrafficStats {
+            bytes_sent: written,
+            bytes_received: incoming.len() as u64,
+        });
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_write_fails_lone_request() {
+        // The read side stays open, so only the failed write can end the request
+        let (client_read, _server_write) = tokio::io::duplex(64);
+        let (client_write, server_read) = tokio::io::duplex(64);
+        drop(server_read);
+
+        let options = TransportOptions {
+            batch_window: Some(Duration::from_millis(5)),
+            ..TransportOptions::default()
+        };
+        let transport = StreamTransport::new(client_read, client_write, options);
+        let request = transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "a"}));
+        let result = tokio::time::timeout(Duration::from_secs(1), request).await.unwrap();
+        assert!(matches!(result, Err(McpError::TransportError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_requests_within_window_are_batched() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+
+        let options = TransportOptions {
+            batch_window: Some(Duration::from_millis(20)),
+            ..TransportOptions::default()
+        };
+        let transport = StreamTransport::new(client_read, client_write, options);
+
+        let server = async {
+            let frame = read_frame(&mut server_read, b'\n').await.unwrap().unwrap();
+            let batch = parse_message(&frame, 8).unwrap();
+            let requests = batch.as_array().unwrap();
+            assert_eq!(requests.len(), 2);
+
+            // Answer in reverse order to exercise demultiplexing
+            let responses: Vec<_> = requests.iter().rev()
+                .map(|r| serde_json::json!({"jsonrpc": "2.0", "id": r["id"], "result": {"method": r["method"]}}))
+                .collect();
+            let reply = format!("{}\n", serde_json::Value::Array(responses));
+            server_write.write_all(reply.as_bytes()).await.unwrap();
+        };
+
+        let (a, b, _) = tokio::join!(
+            transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "a"})),
+            transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "b"})),
+            server
+        );
+        assert_eq!(a.unwrap()["result"]["method"], "a");
+        assert_eq!(b.unwrap()["result"]["method"], "b");
+    }
+
+    #[tokio::test]
+    async fn test_inbound_rate_limit_drops_notifications() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+
+        let options = TransportOptions {
+            inbound_rate_limit: Some(InboundRateLimit { max_per_second: 10, mark_unhealthy: false }),
+            ..TransportOptions::default()
+        };
+        let transport = StreamTransport::new(client_read, client_write, options);
+        let mut inbound = transport.subscribe();
+
+        let server = async {
+            let frame = read_frame(&mut server_read, b'\n').await.unwrap().unwrap();
+            let request = parse_message(&frame, 8).unwrap();
+
+            let mut flood = String::new();
+            for i in 0..50 {
+                let progress = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/progress",
+                    "params": {"progress": i}
+                });
+                flood.push_str(&format!("{}\n", progress));
+            }
+            let changed = serde_json::json!({"jsonrpc": "2.0", "method": "notifications/tools/list_changed"});
+            let response = serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {}});
+            flood.push_str(&format!("{}\n{}\n", changed, response));
+            server_write.write_all(flood.as_bytes()).await.unwrap();
+        };
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+        let (response, _) = tokio::join!(transport.send_request(request), server);
+        assert!(response.is_ok());
+
+        let mut received = Vec::new();
+        while let Ok(message) = inbound.try_recv() {
+            received.push(message["method"].as_str().unwrap().to_string());
+        }
+        assert_eq!(received.len(), 11);
+        assert_eq!(received.last().unwrap(), "notifications/tools/list_changed");
+        assert_eq!(transport.dropped_messages(), 40);
+    }
+
+    #[tokio::test]
+    async fn test_inbound_rate_limit_covers_batches() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+
+        let options = TransportOptions {
+            inbound_rate_limit: Some(InboundRateLimit { max_per_second: 10, mark_unhealthy: false }),
+            ..TransportOptions::default()
+        };
+        let transport = StreamTransport::new(client_read, client_write, options);
+        let mut inbound = transport.subscribe();
+
+        let server = async {
+            let frame = read_frame(&mut server_read, b'\n').await.unwrap().unwrap();
+            let request = parse_message(&frame, 8).unwrap();
+
+            // Five batches of ten notifications, the last one carrying the response too
+            let mut flood = String::new();
+            for batch in 0..5 {
+                let mut messages: Vec<_> = (0..10)
+                    .map(|i| serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/progress",
+                        "params": {"progress": batch * 10 + i}
+                    }))
+                    .collect();
+                if batch == 4 {
+                    messages.push(serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {}}));
+                }
+                flood.push_str(&format!("{}\n", serde_json::Value::Array(messages)));
+            }
+            server_write.write_all(flood.as_bytes()).await.unwrap();
+        };
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+        let (response, _) = tokio::join!(transport.send_request(request), server);
+        assert!(response.is_ok());
+
+        let mut received = 0;
+        while inbound.try_recv().is_ok() {
+            received += 1;
+        }
+        assert_eq!(received, 10);
+        assert_eq!(transport.dropped_messages(), 40);
+    }
+
+    #[test]
+    fn test_stderr_buffer_bounds() {
+        let mut buffer = StderrBuffer::new(3, 10);
+        for line in ["a", "b", "c", "d"] {
+            buffer.push(line.to_string());
+        }
+        assert_eq!(buffer.lines, ["b", "c", "d"]);
+
+        buffer.push("0123456789".to_string());
+        assert_eq!(buffer.lines, ["0123456789"]);
+
+        buffer.push("x".repeat(20));
+        assert_eq!(buffer.lines, ["x".repeat(10)]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_overlapping_requests_answered_out_of_order() {
+        // Replies to the second request first, with a notification in between
+        let script = r#"read a; read b
+echo '{"jsonrpc":"2.0","id":2,"result":{"n":2}}'
+echo '{"jsonrpc":"2.0","method":"notifications/message","params":{}}'
+echo '{"jsonrpc":"2.0","id":1,"result":{"n":1}}'
+read c"#;
+        let transport = StdioTransport::new(
+            "sh",
+            &["-c".to_string(), script.to_string()],
+            &HashMap::new(),
+            TransportOptions::default(),
+        ).await.unwrap();
+        let mut inbound = transport.subscribe();
+
+        let (first, second) = tokio::join!(
+            transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "a"})),
+            transport.send_request(serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "b"})),
+        );
+        assert_eq!(first.unwrap()["result"]["n"], 1);
+        assert_eq!(second.unwrap()["result"]["n"], 2);
+        assert_eq!(inbound.recv().await.unwrap()["method"], "notifications/message");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_transport_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(frame)) = read_frame(&mut reader, b'\n').await {
+                let request: serde_json::Value = serde_json::from_slice(&frame).unwrap();
+                let response = serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {"echo": request["method"]}});
+                let mut bytes = serde_json::to_vec(&response).unwrap();
+                bytes.push(b'\n');
+                writer.write_all(&bytes).await.unwrap();
+            }
+        });
+
+        let transport = SocketTransport::connect(&path, TransportOptions::default()).await.unwrap();
+        let response = transport
+            .send_request(serde_json::json!({"jsonrpc": "2.0", "id": 7, "method": "ping"}))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["echo"], "ping");
+
+        transport.close().await.unwrap();
+        let err = transport
+            .send_request(serde_json::json!({"jsonrpc": "2.0", "id": 8, "method": "ping"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McpError::TransportError(_)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_transport_missing_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("absent.sock");
+        let err = SocketTransport::connect(&path, TransportOptions::default()).await.err().unwrap();
+        assert!(matches!(err, McpError::TransportError(ref m) if m.contains("absent.sock")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_protocol_on_both_streams() {
+        // Answers on stderr, with a log line on stdout and one on stderr
+        let script = r#"read line; echo starting; echo warming up >&2; echo '{"jsonrpc":"2.0","id":0,"result":{}}' >&2; read line"#;
+        let options = TransportOptions {
+            protocol_stream: ProtocolStream::Both,
+            ..TransportOptions::default()
+        };
+        let transport = StdioTransport::new(
+            "sh",
+            &["-c".to_string(), script.to_string()],
+            &HashMap::new(),
+            options,
+        ).await.unwrap();
+
+        let response = transport
+            .send_request(serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": "ping"}))
+            .await
+            .unwrap();
+        assert_eq!(response["result"], serde_json::json!({}));
+
+        // Stdout is read by its own task, with no ordering against stderr
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut logs = transport.recent_stderr();
+        logs.sort();
+        assert_eq!(logs, ["starting", "warming up"]);
+        assert!(!is_json_rpc(br#"{"id":0}"#));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_captures_stderr() {
+        let options = TransportOptions {
+            stderr_lines: 2,
+            ..TransportOptions::default()
+        };
+        let transport = StdioTransport::new(
+            "sh",
+            &["-c".to_string(), "echo one >&2; echo two >&2; echo three >&2; read line".to_string()],
+            &HashMap::new(),
+            options,
+        ).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(transport.recent_stderr(), ["two", "three"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_reports_exit_status() {
+        let transport = StdioTransport::new(
+            "sh",
+            &["-c".to_string(), "read line; echo 'fatal: bad token' >&2; exit 3".to_string()],
+            &HashMap::new(),
+            TransportOptions::default(),
+        ).await.unwrap();
+
+        let err = transport
+            .send_request(serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": "initialize"}))
+            .await
+            .unwrap_err();
+        let McpError::ProcessExited { status, ref stderr } = err else {
+            panic!("expected ProcessExited, got {}", err);
+        };
+        assert_eq!(status, ProcessExit { code: Some(3), signal: None });
+        assert_eq!(stderr, &["fatal: bad token"]);
+        assert!(err.is_retryable());
+        assert_eq!(transport.exit_status(), Some(status));
+    }
+
+    #[test]
+    fn test_depth_ignores_brackets_in_strings() {
+        let json = r#"{"text": "[[[[[[[[ \" {{{{{{{{"}"#;
+        assert!(parse_message(json.as_bytes(), 2).is_ok());
+    }
 }
@@ -1,5 +1,6 @@
 //! MCP type definitions
 
+use std::time::{Duration, SystemTime};
 use serde::{Deserialize, Serialize};
 
 /// Tool schema from MCP server
@@ -13,6 +14,470 @@ pub struct ToolSchema {
     /// Input schema (JSON Schema)
     #[serde(rename = "inputSchema")]
     pub input_schema: serde_json::Value,
+    /// Behavioral hints about the tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+impl ToolSchema {
+    /// Stable hash of the tool's definition, for detecting changes.
+    ///
+    /// Covers name, description, input schema and annotations. Object keys
+    /// are sorted first, so schemas differing only in key order hash equal.
+    /// The hash (64-bit FNV-1a) is the same across processes and Rust
+    /// versions, so it can be stored.
+    pub fn schema_hash(&self) -> u64 {
+        let mut canonical = String::new();
+        write_canonical(&serde_json::json!({
+            "name": self.name,
+            "description": self.description,
+            "inputSchema": self.input_schema,
+            "annotations": self.annotations,
+        }), &mut canonical);
+        
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in canonical.bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    /// Lightweight descriptor of the tool's input, e.g. for generating forms
+    pub fn shape(&self) -> SchemaShape {
+        SchemaShape::from_schema(&self.input_schema)
+    }
+
+    /// Whether the tool declares that it doesn't modify its environment
+    pub fn is_read_only(&self) -> bool {
+        self.annotations.as_ref()
+            .and_then(|a| a.read_only_hint)
+            .unwrap_or(false)
+    }
+
+    /// Whether the tool may perform destructive updates.
+    ///
+    /// Follows the spec defaults: a tool that isn't read-only is assumed
+    /// destructive unless it says otherwise.
+    pub fn is_destructive(&self) -> bool {
+        if self.is_read_only() {
+            return false;
+        }
+        self.annotations.as_ref()
+            .and_then(|a| a.destructive_hint)
+            .unwrap_or(true)
+    }
+}
+
+/// Runtime description of a value accepted by a JSON Schema.
+///
+/// Covers the subset tool schemas commonly use: `type`, `properties`,
+/// `required`, `items` and string `enum`. Anything else (`anyOf`, `$ref`,
+/// mixed-type enums, ...) becomes `Unknown`, which callers can render as raw
+/// JSON input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaShape {
+    /// A string, restricted to `choices` when the schema has an `enum`
+    String { choices: Vec<String> },
+    /// A number; `integer` for `"type": "integer"`
+    Number { integer: bool },
+    /// A boolean
+    Bool,
+    /// An array of items of one shape
+    Array(Box<SchemaShape>),
+    /// An object with named fields, sorted by name
+    Object(Vec<FieldShape>),
+    /// A construct outside the supported subset
+    Unknown,
+}
+
+/// One property of a [`SchemaShape::Object`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldShape {
+    /// Property name
+    pub name: String,
+    /// Whether the property is listed in `required`
+    pub required: bool,
+    /// Property description, if any
+    pub description: Option<String>,
+    /// Shape of the property's value
+    pub shape: SchemaShape,
+}
+
+impl SchemaShape {
+    /// Describe `schema`, degrading to `Unknown` where it leaves the supported subset
+    pub fn from_schema(schema: &serde_json::Value) -> Self {
+        if let Some(values) = schema.get("enum") {
+            let choices: Option<Vec<String>> = values.as_array()
+                .and_then(|v| v.iter().map(|c| c.as_str().map(str::to_string)).collect());
+            return match choices {
+                Some(choices) => SchemaShape::String { choices },
+                None => SchemaShape::Unknown,
+            };
+        }
+        
+        let type_name = match schema.get("type") {
+            Some(serde_json::Value::String(name)) => Some(name.as_str()),
+            // `["string", "null"]` is how optional values are often spelled
+            Some(serde_json::Value::Array(names)) => {
+                let mut types = names.iter().filter(|n| n.as_str() != Some("null"));
+                match (types.next(), types.next()) {
+                    (Some(name), None) => name.as_str(),
+                    _ => None,
+                }
+            }
+            // Objects are often described by properties alone
+            None if schema.get("properties").is_some() => Some("object"),
+            _ => None,
+        };
+        
+        match type_name {
+            Some("string") => SchemaShape::String { choices: Vec::new() },
+            Some("number") => SchemaShape::Number { integer: false },
+            Some("integer") => SchemaShape::Number { integer: true },
+            Some("boolean") => SchemaShape::Bool,
+            Some("array") => SchemaShape::Array(Box::new(
+                schema.get("items").map_or(SchemaShape::Unknown, SchemaShape::from_schema),
+            )),
+            Some("object") => {
+                let required: Vec<&str> = schema.get("required")
+                    .and_then(|r| r.as_array())
+                    .map(|r| r.iter().filter_map(|k| k.as_str()).collect())
+                    .unwrap_or_default();
+                let mut fields: Vec<FieldShape> = schema.get("properties")
+                    .and_then(|p| p.as_object())
+                    .map(|properties| properties.iter().map(|(name, property)| FieldShape {
+                        name: name.clone(),
+                        required: required.contains(&name.as_str()),
+                        description: property.get("description").and_then(|d| d.as_str()).map(str::to_string),
+                        shape: SchemaShape::from_schema(property),
+                    }).collect())
+                    .unwrap_or_default();
+                fields.sort_by(|a, b| a.name.cmp(&b.name));
+                SchemaShape::Object(fields)
+            }
+            _ => SchemaShape::Unknown,
+        }
+    }
+}
+
+/// Result of a `tools/call`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallResult {
+    /// Content blocks (text, images, embedded resources)
+    #[serde(default)]
+    pub content: Vec<serde_json::Value>,
+    /// Structured output, for tools with an output schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<serde_json::Value>,
+    /// Whether the tool reported failure through its content
+    #[serde(default)]
+    pub is_error: bool,
+    /// Whether content was cut to fit the connection's result size limit
+    #[serde(skip)]
+    pub truncated: bool,
+    /// Serialized size of the content before truncation, if truncated
+    #[serde(skip)]
+    pub original_size: Option<usize>,
+}
+
+/// Content block of a tool result, by its `type`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ToolContent {
+    /// Plain text
+    Text {
+        text: String,
+    },
+    /// Base64-encoded image
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    /// Base64-encoded audio
+    Audio {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    /// Resource embedded in the result
+    Resource {
+        resource: ResourceContents,
+    },
+    /// Block of a type this crate doesn't know, or one that failed to parse
+    #[serde(other)]
+    Unknown,
+}
+
+impl ToolCallResult {
+    /// Content blocks parsed into [`ToolContent`], in order
+    pub fn typed_content(&self) -> Vec<ToolContent> {
+        self.content.iter()
+            .map(|block| serde_json::from_value(block.clone()).unwrap_or(ToolContent::Unknown))
+            .collect()
+    }
+
+    /// Concatenated text of all text content blocks
+    pub fn text(&self) -> String {
+        self.content.iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect()
+    }
+
+    /// Cut content to roughly `max_bytes` of serialized JSON.
+    ///
+    /// Blocks are kept in order while they fit; the first text block that
+    /// doesn't is shortened to the remaining budget and everything after it
+    /// is dropped. Structured content that doesn't fit is dropped entirely.
+    pub fn truncate_to(&mut self, max_bytes: usize) {
+        let size = |value: &serde_json::Value| serde_json::to_vec(value).map_or(0, |v| v.len());
+        let content_size: usize = self.content.iter().map(size).sum();
+        let structured_size = self.structured_content.as_ref().map_or(0, size);
+        if content_size + structured_size <= max_bytes {
+            return;
+        }
+
+        let mut budget = max_bytes;
+        let mut kept = Vec::new();
+        for mut block in std::mem::take(&mut self.content) {
+            let block_size = size(&block);
+            if block_size <= budget {
+                budget -= block_size;
+                kept.push(block);
+                continue;
+            }
+            if let Some(text) = block["text"].as_str() {
+                let mut end = budget.min(text.len());
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                block["text"] = serde_json::Value::String(text[..end].to_string());
+                kept.push(block);
+            }
+            break;
+        }
+        self.content = kept;
+
+        if structured_size > max_bytes {
+            self.structured_content = None;
+        }
+        self.truncated = true;
+        self.original_size = Some(content_size + structured_size);
+    }
+}
+
+/// Notification carrying partial `content` of a tool call before its result.
+///
+/// Params are `{"progressToken": ..., "content": [...]}`, correlated with the
+/// call through the progress token in its `_meta`.
+pub const PARTIAL_CONTENT_METHOD: &str = "notifications/tools/partialContent";
+
+/// Item of a streamed tool call, see `McpConnection::call_tool_streaming`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolOutputChunk {
+    /// Content blocks the server pushed ahead of the result
+    Partial(Vec<serde_json::Value>),
+    /// The complete result; always the last item of a successful call
+    Final(ToolCallResult),
+}
+
+/// Update from a `notifications/progress` message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Progress token of the request being reported on
+    pub token: serde_json::Value,
+    /// Work done so far; increases with every update
+    pub progress: f64,
+    /// Total work, if known
+    pub total: Option<f64>,
+    /// Human-readable status
+    pub message: Option<String>,
+}
+
+impl Progress {
+    /// Parse a server message, if it is a progress notification for `token`
+    pub fn from_notification(message: &serde_json::Value, token: &serde_json::Value) -> Option<Self> {
+        if message["method"] != "notifications/progress" {
+            return None;
+        }
+        let params = &message["params"];
+        if &params["progressToken"] != token {
+            return None;
+        }
+        Some(Self {
+            token: token.clone(),
+            progress: params["progress"].as_f64()?,
+            total: params["total"].as_f64(),
+            message: params["message"].as_str().map(String::from),
+        })
+    }
+
+    /// Fraction complete in `0.0..=1.0`, if the total is known
+    pub fn fraction(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0.0)
+            .map(|total| (self.progress / total).clamp(0.0, 1.0))
+    }
+}
+
+/// Resource advertised by `resources/list`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceSchema {
+    /// Resource URI
+    pub uri: String,
+    /// Human-readable name
+    pub name: String,
+    /// What the resource holds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// MIME type, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// Prompt template advertised by `prompts/list`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptSchema {
+    /// Prompt name, passed to `prompts/get`
+    pub name: String,
+    /// What the prompt is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Arguments the prompt accepts
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+}
+
+/// Argument of a prompt template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptArgument {
+    /// Argument name
+    pub name: String,
+    /// What the argument means
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Whether `prompts/get` fails without it
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Prompt rendered by `prompts/get`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptResult {
+    /// Description of the rendered prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Messages to place in the conversation, in order
+    pub messages: Vec<PromptMessage>,
+}
+
+/// One message of a rendered prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptMessage {
+    /// `user` or `assistant`
+    pub role: String,
+    /// Content block (text, image or embedded resource)
+    pub content: serde_json::Value,
+}
+
+/// Contents of a resource, as returned by `resources/read`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    /// Resource URI
+    pub uri: String,
+    /// MIME type, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Text content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Binary content, base64-encoded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+/// A subscribed resource changed, from `notifications/resources/updated`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceUpdate {
+    /// URI of the changed resource
+    pub uri: String,
+    /// New contents, if the server pushed them; otherwise re-read the URI
+    pub contents: Option<Vec<ResourceContents>>,
+}
+
+impl ResourceUpdate {
+    /// Parse the params of a `notifications/resources/updated` message.
+    ///
+    /// The spec only carries `uri`; `contents`, in the shape of a
+    /// `resources/read` result, is picked up when a server includes it.
+    pub fn from_params(params: &serde_json::Value) -> Option<Self> {
+        let uri = params.get("uri")?.as_str()?.to_string();
+        let contents = params.get("contents")
+            .and_then(|c| serde_json::from_value(c.clone()).ok());
+        Some(Self { uri, contents })
+    }
+}
+
+/// Function-calling definition format expected by an LLM provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionFormat {
+    /// `{"type": "function", "function": {name, description, parameters}}`
+    OpenAi,
+    /// `{name, description, input_schema}`
+    Anthropic,
+}
+
+impl ToolSchema {
+    /// Convert to a provider function-calling definition
+    pub fn to_function_schema(&self, format: FunctionFormat) -> serde_json::Value {
+        self.to_function_schema_named(&self.name, format)
+    }
+
+    /// Convert to a function-calling definition exposed under `name`
+    pub fn to_function_schema_named(&self, name: &str, format: FunctionFormat) -> serde_json::Value {
+        match format {
+            FunctionFormat::OpenAi => serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": name,
+                    "description": self.description,
+                    "parameters": self.input_schema
+                }
+            }),
+            FunctionFormat::Anthropic => serde_json::json!({
+                "name": name,
+                "description": self.description,
+                "input_schema": self.input_schema
+            }),
+        }
+    }
+}
+
+/// Tool behavior hints. These are advisory and come from the server, so
+/// they should not be trusted from untrusted servers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    /// Human-readable title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Tool does not modify its environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    /// Tool may perform destructive updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    /// Repeated calls with the same arguments have no additional effect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    /// Tool interacts with external entities
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
 }
 
 /// Server information returned on initialize
@@ -30,6 +495,60 @@ pub struct ServerInfo {
     /// Server capabilities
     #[serde(default)]
     pub capabilities: ServerCapabilities,
+    /// Vendor metadata, e.g. build hash or region
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<serde_json::Value>,
+}
+
+/// Protocol revision that added tool annotations, audio content and completions
+pub const PROTOCOL_2025_03_26: &str = "2025-03-26";
+
+/// Protocol revision that added structured tool output and elicitation
+pub const PROTOCOL_2025_06_18: &str = "2025-06-18";
+
+impl ServerInfo {
+    /// Whether the negotiated protocol is `version` or newer
+    pub fn protocol_at_least(&self, version: &str) -> bool {
+        // Revisions are ISO dates, so they order lexicographically
+        !self.protocol_version.is_empty() && self.protocol_version.as_str() >= version
+    }
+
+    /// Tool results may carry `structuredContent` and tools an `outputSchema`
+    pub fn supports_structured_output(&self) -> bool {
+        self.protocol_at_least(PROTOCOL_2025_06_18) && self.capabilities.tools.is_some()
+    }
+
+    /// The server may send `elicitation/create` requests
+    pub fn supports_elicitation(&self) -> bool {
+        self.protocol_at_least(PROTOCOL_2025_06_18)
+    }
+
+    /// The server answers `completion/complete`
+    pub fn supports_completions(&self) -> bool {
+        self.protocol_at_least(PROTOCOL_2025_03_26) && self.capabilities.unknown("completions").is_some()
+    }
+
+    /// Tools may carry behavior annotations such as `readOnlyHint`
+    pub fn supports_tool_annotations(&self) -> bool {
+        self.protocol_at_least(PROTOCOL_2025_03_26) && self.capabilities.tools.is_some()
+    }
+
+    /// The server accepts `resources/subscribe`
+    pub fn supports_resource_subscriptions(&self) -> bool {
+        self.capabilities.resources.as_ref().is_some_and(|r| r.subscribe)
+    }
+
+    /// Whether the server advertised `capability`, e.g. `"resources"` or `"logging"`
+    pub fn supports(&self, capability: &str) -> bool {
+        let capabilities = &self.capabilities;
+        match capability {
+            "tools" => capabilities.tools.is_some(),
+            "resources" => capabilities.resources.is_some(),
+            "prompts" => capabilities.prompts.is_some(),
+            "sampling" => capabilities.sampling.is_some(),
+            other => capabilities.unknown(other).is_some(),
+        }
+    }
 }
 
 /// Server capabilities
@@ -47,6 +566,117 @@ pub struct ServerCapabilities {
     /// Sampling capability
     #[serde(default)]
     pub sampling: Option<SamplingCapability>,
+    /// Capabilities without a typed field above, e.g. `experimental`, as sent
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ServerCapabilities {
+    /// Capability keys the server advertised that have no typed field, sorted.
+    ///
+    /// Use [`McpConnection::request`](crate::McpConnection::request) and
+    /// [`notify`](crate::McpConnection::notify) to talk to such capabilities.
+    pub fn unknown_keys(&self) -> Vec<&str> {
+        let mut keys: Vec<_> = self.other.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Raw value of a capability without a typed field
+    pub fn unknown(&self, key: &str) -> Option<&serde_json::Value> {
+        self.other.get(key)
+    }
+}
+
+/// Capabilities the client advertises in `initialize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientCapabilities {
+    /// Advertise `tools`
+    pub tools: bool,
+    /// Advertise `sampling`
+    pub sampling: bool,
+}
+
+impl Default for ClientCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            sampling: true,
+        }
+    }
+}
+
+impl ClientCapabilities {
+    /// The `capabilities` object sent in `initialize`
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut capabilities = serde_json::Map::new();
+        if self.tools {
+            capabilities.insert("tools".into(), serde_json::json!({}));
+        }
+        if self.sampling {
+            capabilities.insert("sampling".into(), serde_json::json!({}));
+        }
+        serde_json::Value::Object(capabilities)
+    }
+}
+
+/// What an initialized connection can actually do.
+///
+/// Features both sides advertise (tools, sampling) require both; features
+/// only servers advertise require the server's capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EffectiveCapabilities {
+    tools: bool,
+    sampling: bool,
+    resources: bool,
+    resource_subscriptions: bool,
+    prompts: bool,
+    completions: bool,
+}
+
+impl EffectiveCapabilities {
+    /// Intersect what the client advertised with the server's initialize result
+    pub fn negotiate(client: &ClientCapabilities, server: &ServerInfo) -> Self {
+        let advertised = &server.capabilities;
+        Self {
+            tools: client.tools && advertised.tools.is_some(),
+            sampling: client.sampling && advertised.sampling.is_some(),
+            resources: advertised.resources.is_some(),
+            resource_subscriptions: server.supports_resource_subscriptions(),
+            prompts: advertised.prompts.is_some(),
+            completions: server.supports_completions(),
+        }
+    }
+
+    /// `tools/list` and `tools/call` are available
+    pub fn can_use_tools(&self) -> bool {
+        self.tools
+    }
+
+    /// Sampling requests may be exchanged
+    pub fn can_use_sampling(&self) -> bool {
+        self.sampling
+    }
+
+    /// `resources/list` and `resources/read` are available
+    pub fn can_use_resources(&self) -> bool {
+        self.resources
+    }
+
+    /// `resources/subscribe` is available
+    pub fn can_subscribe_resources(&self) -> bool {
+        self.resource_subscriptions
+    }
+
+    /// `prompts/list` and `prompts/get` are available
+    pub fn can_use_prompts(&self) -> bool {
+        self.prompts
+    }
+
+    /// `completion/complete` is available
+    pub fn can_use_completions(&self) -> bool {
+        self.completions
+    }
 }
 
 /// Tools capability
@@ -80,8 +710,54 @@ pub struct PromptsCapability {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SamplingCapability {}
 
+/// Which of a server's tools are exposed and callable.
+///
+/// A tool is allowed if it is on the allowlist (when there is one) and not
+/// on the denylist; the denylist wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolPolicy {
+    allow: Option<std::collections::HashSet<String>>,
+    deny: std::collections::HashSet<String>,
+}
+
+impl ToolPolicy {
+    /// Allow only the named tools
+    pub fn allow_only<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allow: Some(names.into_iter().map(Into::into).collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Also block the named tools
+    pub fn deny<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.deny.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether `tool` may be listed and called
+    pub fn allows(&self, tool: &str) -> bool {
+        if self.deny.contains(tool) {
+            return false;
+        }
+        match &self.allow {
+            Some(allow) => allow.contains(tool),
+            None => true,
+        }
+    }
+}
+
 /// Server health status
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ServerHealth {
     /// Server is healthy
     Healthy,
@@ -99,10 +775,618 @@ impl Default for ServerHealth {
     }
 }
 
+impl ServerHealth {
+    /// Whether the server is known to be failing (unhealthy or disconnected)
+    pub fn is_degraded(&self) -> bool {
+        matches!(self, ServerHealth::Unhealthy | ServerHealth::Disconnected)
+    }
+}
+
+impl std::fmt::Display for ServerHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ServerHealth::Healthy => "healthy",
+            ServerHealth::Unhealthy => "unhealthy",
+            ServerHealth::Disconnected => "disconnected",
+            ServerHealth::Unknown => "unknown",
+        })
+    }
+}
+
+/// Server health with detail from recent health checks
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    /// Last known health
+    pub health: ServerHealth,
+    /// Health checks failed in a row; zero after a passing check
+    pub consecutive_failures: u32,
+    /// Why the most recent failed check failed
+    pub last_error: Option<String>,
+    /// Round trip of the last successful ping
+    pub last_ping_latency: Option<Duration>,
+    /// When the server was last checked
+    pub last_checked: Option<SystemTime>,
+}
+
+impl ServerStatus {
+    /// Record the outcome of a health check
+    pub(crate) fn record(&mut self, outcome: Result<Duration, String>) {
+        self.last_checked = Some(SystemTime::now());
+        match outcome {
+            Ok(latency) => {
+                self.consecutive_failures = 0;
+                self.last_ping_latency = Some(latency);
+            }
+            Err(error) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(error);
+            }
+        }
+    }
+}
+
+/// How tool calls treat a server the health check has marked degraded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnhealthyPolicy {
+    /// Dispatch regardless of health
+    AllowAnyway,
+    /// Fail immediately with `McpError::ServerUnhealthy`
+    RejectUnhealthy,
+    /// Wait up to the given duration for the server to become healthy again
+    WaitForHealthy(Duration),
+}
+
+impl Default for UnhealthyPolicy {
+    fn default() -> Self {
+        UnhealthyPolicy::AllowAnyway
+    }
+}
+
+/// Server catalog that can announce changes via `notifications/*/list_changed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListKind {
+    /// `tools/list`
+    Tools,
+    /// `resources/list`
+    Resources,
+    /// `prompts/list`
+    Prompts,
+}
+
+impl ListKind {
+    /// Map a `list_changed` notification method to the catalog it concerns
+    pub fn from_notification(method: &str) -> Option<Self> {
+        match method {
+            "notifications/tools/list_changed" => Some(ListKind::Tools),
+            "notifications/resources/list_changed" => Some(ListKind::Resources),
+            "notifications/prompts/list_changed" => Some(ListKind::Prompts),
+            _ => None,
+        }
+    }
+}
+
+/// Stage of `McpManager::connect` in which a connect failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectPhase {
+    /// Spawning the transport and the `initialize` handshake
+    Initialize,
+    /// Initial `tools/list`
+    Discovery,
+}
+
+impl std::fmt::Display for ConnectPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectPhase::Initialize => write!(f, "initialize"),
+            ConnectPhase::Discovery => write!(f, "discovery"),
+        }
+    }
+}
+
+/// Log severity, as used by MCP `logging/setLevel` and `notifications/message`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+/// Log message emitted by a server via `notifications/message`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerLogEntry {
+    /// Server that emitted the message
+    pub server_id: String,
+    /// Message severity
+    pub level: LogLevel,
+    /// Logger name, if the server provided one
+    #[serde(default)]
+    pub logger: Option<String>,
+    /// Arbitrary message payload
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// What a tool call would do, as determined by a dry run
+#[derive(Debug, Clone)]
+pub struct DryRunReport {
+    /// Server the call would be routed to
+    pub server_id: String,
+    /// Tool that would be invoked
+    pub tool: ToolSchema,
+    /// Current health of the target server
+    pub health: ServerHealth,
+    /// Params that would be sent with `tools/call`
+    pub params: serde_json::Value,
+}
+
+/// Bytes moved over a transport, framing included
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// Bytes written to the server
+    pub bytes_sent: u64,
+    /// Bytes read from the server
+    pub bytes_received: u64,
+}
+
+/// How a server process ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessExit {
+    /// Exit code, if the process exited normally
+    pub code: Option<i32>,
+    /// Terminating signal, if the process was killed (unix only)
+    pub signal: Option<i32>,
+}
+
+impl ProcessExit {
+    /// Whether the process exited with code 0
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl std::fmt::Display for ProcessExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exit code {}", code),
+            (None, Some(signal)) => write!(f, "signal {}", signal),
+            (None, None) => write!(f, "unknown status"),
+        }
+    }
+}
+
+/// Point-in-time view of a server connection, for operators
+#[derive(Debug, Clone)]
+pub struct ServerDiagnostics {
+    /// Server ID
+    pub server_id: String,
+    /// Last known health
+    pub health: ServerHealth,
+    /// Whether the connection is currently up
+    pub connected: bool,
+    /// Info from the initialize result, if initialized
+    pub server_info: Option<ServerInfo>,
+    /// Number of cached tools
+    pub tool_count: usize,
+    /// How the server process ended, if it has
+    pub exit_status: Option<ProcessExit>,
+    /// Most recent stderr lines, oldest first
+    pub recent_stderr: Vec<String>,
+    /// Traffic over the current transport
+    pub traffic: TrafficStats,
+}
+
+/// Serialize `value` as compact JSON with object keys in sorted order
+fn write_canonical(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// Tools added to, removed from and changed in a server's catalog, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolsDiff {
+    /// Tools present now but not before
+    pub added: Vec<String>,
+    /// Tools present before but not now
+    pub removed: Vec<String>,
+    /// Tools present in both whose definition changed (see `ToolSchema::schema_hash`)
+    pub modified: Vec<String>,
+}
+
+impl ToolsDiff {
+    /// Compare two listings of the same server
+    pub fn between(old: &[ToolSchema], new: &[ToolSchema]) -> Self {
+        let hashes = |tools: &[ToolSchema]| -> std::collections::BTreeMap<String, u64> {
+            tools.iter().map(|t| (t.name.clone(), t.schema_hash())).collect()
+        };
+        let (old, new) = (hashes(old), hashes(new));
+        
+        Self {
+            added: new.keys().filter(|name| !old.contains_key(*name)).cloned().collect(),
+            removed: old.keys().filter(|name| !new.contains_key(*name)).cloned().collect(),
+            modified: new.iter()
+                .filter(|(name, hash)| old.get(*name).is_some_and(|old| old != *hash))
+                .map(|(name, _)| name.clone())
+                .collect(),
+        }
+    }
+
+    /// Whether the catalog is unchanged
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Something that happened across the manager's connections
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ManagerEvent {
+    /// A server's cached tool catalog changed
+    ToolsChanged {
+        server_id: String,
+        added: Vec<String>,
+        removed: Vec<String>,
+        modified: Vec<String>,
+    },
+    /// A subscribed resource changed on a server
+    ResourceUpdated {
+        server_id: String,
+        update: ResourceUpdate,
+    },
+    /// A server's connection recovered from a broken transport
+    Reconnected {
+        server_id: String,
+    },
+}
+
+/// Something that happened to a single connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// Automatic recovery replaced a broken transport; the server may now
+    /// expose different tools, resources and prompts
+    Reconnected {
+        /// Reconnect attempts it took, from one
+        attempts: u32,
+    },
+}
+
+/// A completed tool call, as kept in a connection's invocation history
+#[derive(Debug, Clone)]
+pub struct ToolInvocation {
+    /// Tool name
+    pub tool: String,
+    /// Serialized size of the arguments in bytes
+    pub argument_size: usize,
+    /// When the call was sent
+    pub started_at: std::time::SystemTime,
+    /// Time until the response (or failure) arrived
+    pub duration: std::time::Duration,
+    /// Error message if the call failed
+    pub error: Option<String>,
+}
+
+impl ToolInvocation {
+    /// Whether the call succeeded
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// How a server answered a probe request, see `McpConnection::probe_methods`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// The server returned a result
+    Supported,
+    /// The server answered with JSON-RPC "method not found" (-32601)
+    NotFound,
+    /// Any other failure, e.g. a different error code or a timeout
+    Failed(String),
+}
+
+impl ProbeOutcome {
+    /// Whether the method worked
+    pub fn is_supported(&self) -> bool {
+        matches!(self, ProbeOutcome::Supported)
+    }
+}
+
+/// A request awaiting its response
+#[derive(Debug, Clone)]
+pub struct InFlightRequest {
+    /// JSON-RPC request id
+    pub id: u64,
+    /// JSON-RPC method, e.g. `tools/call`
+    pub method: String,
+    /// Time since the request was sent
+    pub elapsed: std::time::Duration,
+}
+
+/// A tool together with the server exposing it
+#[derive(Debug, Clone)]
+pub struct QualifiedTool {
+    /// Server ID
+    pub server_id: String,
+    /// Tool schema, under the tool's own name
+    pub tool: ToolSchema,
+}
+
+impl QualifiedTool {
+    /// Name unique across servers: `server::tool`
+    pub fn qualified_name(&self) -> String {
+        crate::manager::qualified_name(&self.server_id, &self.tool.name)
+    }
+}
+
+/// Tool cache state for one server
+#[derive(Debug, Clone)]
+pub struct ToolCacheStats {
+    /// Server ID
+    pub server_id: String,
+    /// Number of cached tools
+    pub tool_count: usize,
+    /// When the tools were last fetched
+    pub last_refresh: Option<std::time::SystemTime>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_server_status_serializes() {
+        let mut status = ServerStatus::default();
+        status.record(Err("ping failed".into()));
+        status.record(Err("ping failed".into()));
+        assert_eq!(status.consecutive_failures, 2);
+        status.record(Ok(Duration::from_millis(3)));
+        status.health = ServerHealth::Healthy;
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["health"], "healthy");
+        assert_eq!(json["consecutiveFailures"], 0);
+        assert_eq!(json["lastError"], "ping failed");
+        assert_eq!(ServerHealth::Disconnected.to_string(), "disconnected");
+    }
+
+    #[test]
+    fn test_progress_from_notification() {
+        let token = serde_json::json!("t1");
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": {"progressToken": "t1", "progress": 3, "total": 4, "message": "indexing"}
+        });
+
+        let progress = Progress::from_notification(&message, &token).unwrap();
+        assert_eq!(progress.fraction(), Some(0.75));
+        assert_eq!(progress.message.as_deref(), Some("indexing"));
+        assert!(Progress::from_notification(&message, &serde_json::json!("t2")).is_none());
+    }
+
+    #[test]
+    fn test_typed_content() {
+        let result: ToolCallResult = serde_json::from_value(serde_json::json!({"content": [
+            {"type": "text", "text": "hi"},
+            {"type": "image", "data": "aGk=", "mimeType": "image/png"},
+            {"type": "resource", "resource": {"uri": "file:///a", "text": "a"}},
+            {"type": "hologram", "data": "?"},
+            {"type": "audio"}
+        ]})).unwrap();
+
+        assert_eq!(result.typed_content(), [
+            ToolContent::Text { text: "hi".into() },
+            ToolContent::Image { data: "aGk=".into(), mime_type: "image/png".into() },
+            ToolContent::Resource {
+                resource: ResourceContents {
+                    uri: "file:///a".into(),
+                    mime_type: None,
+                    text: Some("a".into()),
+                    blob: None,
+                },
+            },
+            ToolContent::Unknown,
+            ToolContent::Unknown,
+        ]);
+    }
+
+    #[test]
+    fn test_tool_policy() {
+        let policy = ToolPolicy::default().deny(["delete"]);
+        assert!(policy.allows("read"));
+        assert!(!policy.allows("delete"));
+
+        let policy = ToolPolicy::allow_only(["read", "delete"]).deny(["delete"]);
+        assert!(policy.allows("read"));
+        assert!(!policy.allows("delete"));
+        assert!(!policy.allows("write"));
+    }
+
+    #[test]
+    fn test_effective_capabilities() {
+        let server: ServerInfo = serde_json::from_value(serde_json::json!({
+            "name": "fs",
+            "protocolVersion": "2024-11-05",
+            "capabilities": {"tools": {}, "sampling": {}, "resources": {"subscribe": true}}
+        })).unwrap();
+
+        let effective = EffectiveCapabilities::negotiate(&ClientCapabilities::default(), &server);
+        assert!(effective.can_use_tools());
+        assert!(effective.can_use_sampling());
+        assert!(effective.can_subscribe_resources());
+        assert!(!effective.can_use_prompts());
+
+        let client = ClientCapabilities { sampling: false, ..ClientCapabilities::default() };
+        assert!(!EffectiveCapabilities::negotiate(&client, &server).can_use_sampling());
+        assert_eq!(client.to_json(), serde_json::json!({"tools": {}}));
+    }
+
+    #[test]
+    fn test_schema_shape() {
+        let shape = SchemaShape::from_schema(&serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "File to read"},
+                "mode": {"enum": ["text", "binary"]},
+                "limit": {"type": ["integer", "null"]},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "filter": {"anyOf": [{"type": "string"}, {"type": "number"}]}
+            },
+            "required": ["path"]
+        }));
+        let SchemaShape::Object(fields) = shape else {
+            panic!("expected an object shape");
+        };
+        let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["filter", "limit", "mode", "path", "tags"]);
+        assert_eq!(fields[0].shape, SchemaShape::Unknown);
+        assert_eq!(fields[1].shape, SchemaShape::Number { integer: true });
+        assert_eq!(fields[2].shape, SchemaShape::String { choices: vec!["text".into(), "binary".into()] });
+        assert!(fields[3].required);
+        assert_eq!(fields[3].description.as_deref(), Some("File to read"));
+        assert_eq!(fields[4].shape, SchemaShape::Array(Box::new(SchemaShape::String { choices: vec![] })));
+        assert_eq!(SchemaShape::from_schema(&serde_json::json!({"enum": [1, 2]})), SchemaShape::Unknown);
+    }
+
+    #[test]
+    fn test_server_info_meta() {
+        let info: ServerInfo = serde_json::from_value(serde_json::json!({
+            "name": "fs",
+            "_meta": {"build": "abc123"}
+        })).unwrap();
+        assert_eq!(info.meta.unwrap()["build"], "abc123");
+    }
+
+    #[test]
+    fn test_feature_detection() {
+        let info = |version: &str| -> ServerInfo {
+            serde_json::from_value(serde_json::json!({
+                "name": "fs",
+                "protocolVersion": version,
+                "capabilities": {"tools": {}, "completions": {}, "resources": {"subscribe": true}}
+            })).unwrap()
+        };
+
+        let legacy = info("2024-11-05");
+        assert!(!legacy.supports_structured_output());
+        assert!(!legacy.supports_completions());
+        assert!(legacy.supports_resource_subscriptions());
+
+        let middle = info("2025-03-26");
+        assert!(middle.supports_completions());
+        assert!(middle.supports_tool_annotations());
+        assert!(!middle.supports_elicitation());
+
+        let current = info("2025-06-18");
+        assert!(current.supports_structured_output());
+        assert!(current.supports_elicitation());
+
+        let mut no_tools = info("2025-06-18");
+        no_tools.capabilities.tools = None;
+        assert!(!no_tools.supports_structured_output());
+        assert!(!ServerInfo { protocol_version: String::new(), ..current }.protocol_at_least("2024-11-05"));
+    }
+
+    #[test]
+    fn test_resource_update_params() {
+        let update = ResourceUpdate::from_params(&serde_json::json!({"uri": "file:///a"})).unwrap();
+        assert_eq!(update.uri, "file:///a");
+        assert!(update.contents.is_none());
+
+        let update = ResourceUpdate::from_params(&serde_json::json!({
+            "uri": "file:///a",
+            "contents": [{"uri": "file:///a", "text": "new"}]
+        })).unwrap();
+        assert_eq!(update.contents.unwrap()[0].text.as_deref(), Some("new"));
+
+        assert!(ResourceUpdate::from_params(&serde_json::json!({})).is_none());
+    }
+
+    #[test]
+    fn test_schema_hash_ignores_key_order() {
+        let tool = |schema: &str| -> ToolSchema {
+            serde_json::from_value(serde_json::json!({
+                "name": "read",
+                "inputSchema": serde_json::from_str::<serde_json::Value>(schema).unwrap()
+            })).unwrap()
+        };
+        let a = tool(r#"{"type": "object", "properties": {"path": {"type": "string"}, "n": {"type": "integer"}}}"#);
+        let b = tool(r#"{"properties": {"n": {"type": "integer"}, "path": {"type": "string"}}, "type": "object"}"#);
+        let c = tool(r#"{"type": "object", "properties": {"path": {"type": "number"}}}"#);
+
+        assert_eq!(a.schema_hash(), b.schema_hash());
+        assert_ne!(a.schema_hash(), c.schema_hash());
+
+        let diff = ToolsDiff::between(&[a], &[c]);
+        assert_eq!(diff.modified, ["read"]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+    }
+
+    #[test]
+    fn test_truncate_tool_result() {
+        let mut result: ToolCallResult = serde_json::from_value(serde_json::json!({
+            "content": [
+                {"type": "text", "text": "short"},
+                {"type": "text", "text": "x".repeat(1000)},
+                {"type": "image", "data": "...", "mimeType": "image/png"}
+            ]
+        })).unwrap();
+
+        result.truncate_to(200);
+        assert!(result.truncated);
+        assert!(result.original_size.unwrap() > 1000);
+        assert_eq!(result.content.len(), 2);
+        assert!(result.text().len() < 200);
+
+        let mut small = ToolCallResult::default();
+        small.truncate_to(10);
+        assert!(!small.truncated);
+    }
+
+    #[test]
+    fn test_unknown_capabilities() {
+        let capabilities: ServerCapabilities = serde_json::from_value(serde_json::json!({
+            "tools": {},
+            "experimental": {"shell": {}},
+            "logging": {}
+        })).unwrap();
+
+        assert!(capabilities.tools.is_some());
+        assert_eq!(capabilities.unknown_keys(), ["experimental", "logging"]);
+        assert!(capabilities.unknown("experimental").unwrap()["shell"].is_object());
+    }
+
     #[test]
     fn test_tool_schema_deserialize() {
         let json = r#"{
@@ -119,5 +1403,38 @@ mod tests {
         let schema: ToolSchema = serde_json::from_str(json).unwrap();
         assert_eq!(schema.name, "test_tool");
         assert_eq!(schema.description, "A test tool");
+        assert!(schema.annotations.is_none());
+        assert!(schema.is_destructive());
+    }
+
+    #[test]
+    fn test_tool_annotations() {
+        let json = r#"{
+            "name": "read_file",
+            "inputSchema": {"type": "object"},
+            "annotations": {"title": "Read file", "readOnlyHint": true}
+        }"#;
+
+        let schema: ToolSchema = serde_json::from_str(json).unwrap();
+        assert!(schema.is_read_only());
+        assert!(!schema.is_destructive());
+        assert_eq!(schema.annotations.unwrap().title.as_deref(), Some("Read file"));
+    }
+
+    #[test]
+    fn test_to_function_schema() {
+        let schema: ToolSchema = serde_json::from_value(serde_json::json!({
+            "name": "search",
+            "description": "Search docs",
+            "inputSchema": {"type": "object"}
+        })).unwrap();
+
+        let openai = schema.to_function_schema(FunctionFormat::OpenAi);
+        assert_eq!(openai["function"]["name"], "search");
+        assert_eq!(openai["function"]["parameters"]["type"], "object");
+
+        let anthropic = schema.to_function_schema_named("docs__search", FunctionFormat::Anthropic);
+        assert_eq!(anthropic["name"], "docs__search");
+        assert_eq!(anthropic["input_schema"]["type"], "object");
     }
 }
@@ -0,0 +1,269 @@
+//! Lightweight tool argument validation
+//!
+//! Checks the common subset of JSON Schema used by MCP tool input schemas:
+//! `type`, `properties`, `required` and `additionalProperties: false`.
+//! Anything outside that subset is accepted as-is.
+
+use std::fmt;
+use serde_json::Value;
+
+/// Something wrong with a tool's input schema that makes it unusable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaProblem {
+    /// The schema (or a nested schema) is not a JSON object
+    NotAnObject { path: String },
+    /// The top-level schema doesn't declare `"type": "object"`
+    NotObjectType,
+    /// `type` names something JSON Schema doesn't define
+    UnknownType { path: String, name: String },
+    /// `properties` is not an object
+    InvalidProperties { path: String },
+    /// `required` is not an array of strings
+    InvalidRequired { path: String },
+    /// A `required` key has no entry in `properties`
+    UndefinedRequired { path: String, key: String },
+}
+
+impl fmt::Display for SchemaProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaProblem::NotAnObject { path } => write!(f, "{}: schema is not an object", path),
+            SchemaProblem::NotObjectType => write!(f, "schema: top-level type is not \"object\""),
+            SchemaProblem::UnknownType { path, name } => write!(f, "{}: unknown type '{}'", path, name),
+            SchemaProblem::InvalidProperties { path } => write!(f, "{}: properties is not an object", path),
+            SchemaProblem::InvalidRequired { path } => write!(f, "{}: required is not an array of strings", path),
+            SchemaProblem::UndefinedRequired { path, key } => {
+                write!(f, "{}: required property '{}' is not defined", path, key)
+            }
+        }
+    }
+}
+
+/// Check that a tool's input schema is usable.
+///
+/// Covers the same subset as [`validate_arguments`], recursing into
+/// `properties` and `items`. An empty list means the schema is sound.
+pub fn check_schema(schema: &Value) -> Vec<SchemaProblem> {
+    let mut problems = Vec::new();
+    if schema.is_object() && schema.get("type").and_then(Value::as_str) != Some("object") {
+        problems.push(SchemaProblem::NotObjectType);
+    }
+    check_subschema(schema, "schema", &mut problems);
+    problems
+}
+
+/// Validate `arguments` against a tool's input schema.
+///
+/// Returns a list of human-readable problems; an empty list means the
+/// arguments are acceptable.
+pub fn validate_arguments(schema: &Value, arguments: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_value(schema, arguments, "arguments", &mut errors);
+    errors
+}
+
+/// Validate one property of an object against the object's schema.
+///
+/// Flags keys the schema doesn't declare, so typos are caught as soon as a
+/// field is set. Schemas without `properties` accept any key.
+pub(crate) fn validate_property(schema: &Value, key: &str, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    let path = format!("arguments.{}", key);
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        match properties.get(key) {
+            Some(field_schema) => validate_value(field_schema, value, &path, &mut errors),
+            None => errors.push(format!("arguments: unknown property '{}'", key)),
+        }
+    }
+    errors
+}
+
+/// Required properties of an object schema missing from `object`
+pub(crate) fn missing_required(schema: &Value, object: &serde_json::Map<String, Value>) -> Vec<String> {
+    schema.get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter(|key| !object.contains_key(*key))
+        .map(|key| format!("arguments: missing required property '{}'", key))
+        .collect()
+}
+
+fn validate_value(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(expected) = schema.get("type") {
+        if !matches_type(expected, value) {
+            errors.push(format!("{}: expected {}, got {}", path, expected, type_name(value)));
+            return;
+        }
+    }
+
+    let Some(object) = value.as_object() else {
+        return;
+    };
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        for key in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(key) {
+                errors.push(format!("{}: missing required property '{}'", path, key));
+            }
+        }
+    }
+
+    for (key, field) in object {
+        match properties.and_then(|p| p.get(key)) {
+            Some(field_schema) => {
+                validate_value(field_schema, field, &format!("{}.{}", path, key), errors);
+            }
+            None => {
+                if schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+                    errors.push(format!("{}: unknown property '{}'", path, key));
+                }
+            }
+        }
+    }
+}
+
+fn check_subschema(schema: &Value, path: &str, problems: &mut Vec<SchemaProblem>) {
+    let Some(object) = schema.as_object() else {
+        // `true`/`false` are valid boolean schemas
+        if !schema.is_boolean() {
+            problems.push(SchemaProblem::NotAnObject { path: path.to_string() });
+        }
+        return;
+    };
+
+    let type_names = match object.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    for name in type_names {
+        if !KNOWN_TYPES.contains(&name) {
+            problems.push(SchemaProblem::UnknownType { path: path.to_string(), name: name.to_string() });
+        }
+    }
+
+    let properties = match object.get("properties") {
+        None => None,
+        Some(Value::Object(properties)) => Some(properties),
+        Some(_) => {
+            problems.push(SchemaProblem::InvalidProperties { path: path.to_string() });
+            None
+        }
+    };
+
+    if let Some(required) = object.get("required") {
+        match required.as_array().filter(|keys| keys.iter().all(Value::is_string)) {
+            Some(keys) => {
+                for key in keys.iter().filter_map(Value::as_str) {
+                    if properties.is_some_and(|p| !p.contains_key(key)) {
+                        problems.push(SchemaProblem::UndefinedRequired {
+                            path: path.to_string(),
+                            key: key.to_string(),
+                        });
+                    }
+                }
+            }
+            None => problems.push(SchemaProblem::InvalidRequired { path: path.to_string() }),
+        }
+    }
+
+    for (key, field) in properties.into_iter().flatten() {
+        check_subschema(field, &format!("{}.{}", path, key), problems);
+    }
+    if let Some(items) = object.get("items") {
+        check_subschema(items, &format!("{}[]", path), problems);
+    }
+}
+
+const KNOWN_TYPES: &[&str] = &["object", "array", "string", "boolean", "null", "number", "integer"];
+
+fn matches_type(expected: &Value, value: &Value) -> bool {
+    match expected {
+        Value::String(name) => matches_type_name(name, value),
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|name| matches_type_name(name, value)),
+        _ => true,
+    }
+}
+
+fn matches_type_name(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "limit": {"type": "integer"}
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_arguments() {
+        let errors = validate_arguments(&schema(), &json!({"path": "/tmp", "limit": 3}));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let errors = validate_arguments(&schema(), &json!({"limit": "3", "pth": "/tmp"}));
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn test_check_schema() {
+        assert!(check_schema(&schema()).is_empty());
+
+        let problems = check_schema(&json!({
+            "type": "object",
+            "properties": {
+                "mode": "fast",
+                "tags": {"type": "array", "items": {"type": "text"}}
+            },
+            "required": ["path"]
+        }));
+        assert_eq!(problems, [
+            SchemaProblem::UndefinedRequired { path: "schema".into(), key: "path".into() },
+            SchemaProblem::NotAnObject { path: "schema.mode".into() },
+            SchemaProblem::UnknownType { path: "schema.tags[]".into(), name: "text".into() },
+        ]);
+
+        assert_eq!(check_schema(&json!({})), [SchemaProblem::NotObjectType]);
+        assert_eq!(check_schema(&json!("object")), [SchemaProblem::NotAnObject { path: "schema".into() }]);
+    }
+}